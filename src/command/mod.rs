@@ -0,0 +1,143 @@
+// Модуль для запуска команд ОС
+// Все вызовы PowerShell и cmd.exe идут через CommandRunner, чтобы их можно было
+// записать на Windows и воспроизвести на любой машине
+
+pub mod replay;
+pub mod system;
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+const POWERSHELL_PATH: &str = r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe";
+const CMD_PATH: &str = r"C:\Windows\System32\cmd.exe";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    pub timeout: Duration,
+}
+
+impl CommandSpec {
+    pub fn powershell(command: &str) -> Self {
+        Self {
+            program: POWERSHELL_PATH.to_string(),
+            args: vec!["-Command".to_string(), command.to_string()],
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn cmd(command: &str) -> Self {
+        Self {
+            program: CMD_PATH.to_string(),
+            args: vec!["/C".to_string(), command.to_string()],
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CommandOutput {
+    // None - процесс завершен сигналом или убит по таймауту
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum CommandError {
+    Spawn(String),
+    Timeout(Duration),
+    // Воспроизведение: для команды нет записи
+    NotRecorded(String),
+    // Запись: команда выполнена, но результат не удалось сохранить в файл
+    NotSaved(String),
+    // Процесс отработал, но вернул ненулевой код
+    Failed { exit_code: Option<i32>, stderr: String },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Spawn(e) => write!(f, "Failed to execute command: {}", e),
+            CommandError::Timeout(timeout) => write!(f, "Command timed out after {:.1}s", timeout.as_secs_f64()),
+            CommandError::NotRecorded(command) => write!(f, "No recorded output for command: {}", command),
+            CommandError::NotSaved(e) => write!(f, "Command ran but was not recorded: {}", e),
+            CommandError::Failed { exit_code, stderr } if stderr.is_empty() => {
+                write!(f, "Command failed with exit code {:?}", exit_code)
+            }
+            CommandError::Failed { stderr, .. } => write!(f, "{}", stderr),
+        }
+    }
+}
+
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, spec: &CommandSpec) -> Result<CommandOutput, CommandError>;
+}
+
+// Прежняя семантика run_powershell_command: stdout при успехе, stderr в ошибке
+pub fn run_checked(runner: &dyn CommandRunner, spec: &CommandSpec) -> Result<String, CommandError> {
+    let output = runner.run(spec)?;
+
+    if output.success() {
+        Ok(output.stdout.trim().to_string())
+    } else {
+        Err(CommandError::Failed {
+            exit_code: output.exit_code,
+            stderr: output.stderr.trim().to_string(),
+        })
+    }
+}
+
+// Раннер по умолчанию. Переменные окружения позволяют записать команды в файл
+// (DNS_MANAGER_RECORD) или прогнать приложение на записанных ответах (DNS_MANAGER_REPLAY)
+pub fn default_runner() -> Result<Arc<dyn CommandRunner>, String> {
+    runner_for(std::env::var("DNS_MANAGER_REPLAY").ok(), std::env::var("DNS_MANAGER_RECORD").ok())
+}
+
+// Если запись не загрузилась, это ошибка: сессия воспроизведения не должна
+// молча перейти на настоящие команды с правами администратора
+fn runner_for(replay: Option<String>, record: Option<String>) -> Result<Arc<dyn CommandRunner>, String> {
+    if let Some(path) = replay {
+        let runner = replay::ReplayRunner::from_file(&path)
+            .map_err(|e| format!("Не удалось загрузить записи команд {}: {}", path, e))?;
+        return Ok(Arc::new(runner));
+    }
+
+    if let Some(path) = record {
+        return Ok(Arc::new(replay::RecordingRunner::new(system::SystemRunner, path)?));
+    }
+
+    Ok(Arc::new(system::SystemRunner))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_replay_file_is_an_error() {
+        let missing = std::env::temp_dir().join("dns-manager-no-such-recording.json");
+        let error = runner_for(Some(missing.display().to_string()), None).err().unwrap();
+        assert!(error.contains("dns-manager-no-such-recording.json"), "{}", error);
+
+        let unwritable = std::env::temp_dir().join("dns-manager-no-such-dir").join("record.json");
+        assert!(runner_for(None, Some(unwritable.display().to_string())).is_err());
+    }
+}
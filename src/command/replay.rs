@@ -0,0 +1,89 @@
+// Запись и воспроизведение команд
+// Файл записи - JSON массив пар "команда - результат", его же используют как фикстуры
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use super::{CommandError, CommandOutput, CommandRunner, CommandSpec};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Recording {
+    pub program: String,
+    pub args: Vec<String>,
+    #[serde(flatten)]
+    pub output: CommandOutput,
+}
+
+impl Recording {
+    fn matches(&self, spec: &CommandSpec) -> bool {
+        self.program == spec.program && self.args == spec.args
+    }
+}
+
+// Выполняет команды через вложенный раннер и дописывает каждую в файл
+pub struct RecordingRunner<R: CommandRunner> {
+    inner: R,
+    path: PathBuf,
+    recordings: Mutex<Vec<Recording>>,
+}
+
+impl<R: CommandRunner> RecordingRunner<R> {
+    // Файл создается сразу, чтобы недоступный путь был виден при запуске, а не после первой команды
+    pub fn new(inner: R, path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        fs::write(&path, "[]").map_err(|e| format!("Не удалось создать файл записи команд {}: {}", path.display(), e))?;
+        Ok(Self {
+            inner,
+            path,
+            recordings: Mutex::new(Vec::new()),
+        })
+    }
+}
+
+impl<R: CommandRunner> CommandRunner for RecordingRunner<R> {
+    fn run(&self, spec: &CommandSpec) -> Result<CommandOutput, CommandError> {
+        let output = self.inner.run(spec)?;
+
+        let mut recordings = self.recordings.lock().unwrap_or_else(|e| e.into_inner());
+        recordings.push(Recording {
+            program: spec.program.clone(),
+            args: spec.args.clone(),
+            output: output.clone(),
+        });
+        // Файл переписывается целиком - запись не теряется, даже если приложение упадет
+        let json = serde_json::to_string_pretty(&*recordings).map_err(|e| CommandError::NotSaved(e.to_string()))?;
+        fs::write(&self.path, json).map_err(|e| CommandError::NotSaved(format!("{}: {}", self.path.display(), e)))?;
+
+        Ok(output)
+    }
+}
+
+// Отвечает записанными результатами, ничего не запуская
+pub struct ReplayRunner {
+    recordings: Vec<Recording>,
+}
+
+impl ReplayRunner {
+    pub fn new(recordings: Vec<Recording>) -> Self {
+        Self { recordings }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let content = fs::read_to_string(path.as_ref()).map_err(|e| e.to_string())?;
+        let recordings = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+        Ok(Self::new(recordings))
+    }
+}
+
+impl CommandRunner for ReplayRunner {
+    fn run(&self, spec: &CommandSpec) -> Result<CommandOutput, CommandError> {
+        self.recordings
+            .iter()
+            .find(|recording| recording.matches(spec))
+            .map(|recording| recording.output.clone())
+            .ok_or_else(|| CommandError::NotRecorded(spec.args.join(" ")))
+    }
+}
@@ -0,0 +1,92 @@
+// Запуск реальных процессов с таймаутом
+
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{CommandError, CommandOutput, CommandRunner, CommandSpec};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, spec: &CommandSpec) -> Result<CommandOutput, CommandError> {
+        let mut child = Command::new(&spec.program)
+            .args(&spec.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| CommandError::Spawn(e.to_string()))?;
+
+        // Читаем pipe в отдельных потоках, иначе процесс с большим выводом зависнет на записи
+        let stdout = child.stdout.take().map(read_in_background);
+        let stderr = child.stderr.take().map(read_in_background);
+
+        let deadline = Instant::now() + spec.timeout;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(CommandError::Timeout(spec.timeout));
+                }
+                Ok(None) => thread::sleep(POLL_INTERVAL),
+                Err(e) => return Err(CommandError::Spawn(e.to_string())),
+            }
+        };
+
+        Ok(CommandOutput {
+            exit_code: status.code(),
+            stdout: stdout.map(join_output).unwrap_or_default(),
+            stderr: stderr.map(join_output).unwrap_or_default(),
+        })
+    }
+}
+
+fn read_in_background<R: Read + Send + 'static>(mut pipe: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        let _ = pipe.read_to_end(&mut buffer);
+        buffer
+    })
+}
+
+fn join_output(handle: thread::JoinHandle<Vec<u8>>) -> String {
+    let bytes = handle.join().unwrap_or_default();
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> CommandSpec {
+        CommandSpec {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn captures_exit_code_and_stderr() {
+        let output = SystemRunner.run(&sh("echo out; echo err >&2; exit 3")).unwrap();
+
+        assert_eq!(output.exit_code, Some(3));
+        assert_eq!(output.stdout.trim(), "out");
+        assert_eq!(output.stderr.trim(), "err");
+    }
+
+    #[test]
+    fn kills_command_after_timeout() {
+        let spec = sh("sleep 5").with_timeout(Duration::from_millis(100));
+        let started = Instant::now();
+
+        assert!(matches!(SystemRunner.run(&spec), Err(CommandError::Timeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
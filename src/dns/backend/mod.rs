@@ -0,0 +1,124 @@
+// Модуль бэкендов DNS
+// Каждый бэкенд умеет читать, устанавливать и сбрасывать DNS на своей ОС
+
+pub mod powershell;
+pub mod resolv_conf;
+#[cfg(target_os = "linux")]
+pub mod network_manager;
+#[cfg(target_os = "linux")]
+pub mod resolved;
+#[cfg(all(test, target_os = "linux"))]
+mod test_bus;
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use super::error::{AdapterChange, ApplyReport, DnsError};
+use super::plan::ChangePlan;
+use crate::command::CommandRunner;
+use crate::network::adapters::AdapterSelection;
+
+// Send + Sync: бэкенд нужен и фоновым задачам, например замеру скорости
+pub trait DnsBackend: Send + Sync {
+    // Человекочитаемое имя бэкенда для статуса в UI
+    fn name(&self) -> &'static str;
+
+    fn get_current_dns(&self) -> Result<String, DnsError>;
+
+    // Серверы, которые сейчас использует каждый интерфейс: (имя, адреса).
+    // Пусто, если DNS адаптеров уже известны без бэкенда (Windows) или они общие для системы (resolv.conf)
+    fn adapter_dns(&self) -> Result<Vec<(String, Vec<String>)>, DnsError> {
+        Ok(Vec::new())
+    }
+
+    // servers - адреса обоих семейств (IPv4 и IPv6) в порядке приоритета.
+    // Ok - изменения применены на всех выбранных адаптерах, частичный успех приходит
+    // как DnsError::PartialSuccess с тем же отчетом внутри
+    fn set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ApplyReport, DnsError>;
+
+    fn reset_dns(&self, selection: &AdapterSelection) -> Result<ApplyReport, DnsError>;
+
+    // Что сделают set_dns и reset_dns с теми же аргументами - без изменения системы
+    fn plan_set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ChangePlan, DnsError>;
+
+    fn plan_reset_dns(&self, selection: &AdapterSelection) -> Result<ChangePlan, DnsError>;
+
+    // Сброс кэша резолвера, чтобы новые DNS начали работать сразу
+    fn flush_cache(&self) -> Result<ApplyReport, DnsError>;
+}
+
+// Кэш общий для системы, поэтому в отчете он один
+pub(crate) const DNS_CACHE: &str = "кэш DNS";
+
+// Выбираем бэкенд под текущую ОС при запуске приложения
+pub fn detect_backend(runner: Arc<dyn CommandRunner>) -> Box<dyn DnsBackend> {
+    if cfg!(windows) {
+        return Box::new(powershell::PowerShellBackend::new(runner));
+    }
+
+    // NetworkManager перезаписывает DNS при переподключении, поэтому меняем его профили
+    #[cfg(target_os = "linux")]
+    if let Some(backend) = network_manager::NetworkManagerBackend::system() {
+        return Box::new(backend);
+    }
+
+    // systemd-resolved перезаписывает resolv.conf, поэтому если он запущен - работаем через него
+    #[cfg(target_os = "linux")]
+    if let Some(backend) = resolved::ResolvedBackend::system() {
+        return Box::new(backend);
+    }
+
+    Box::new(resolv_conf::ResolvConfBackend::default())
+}
+
+// Общий формат строки текущих DNS для всех бэкендов:
+// адреса без повторов и пометка, если они похожи на выданные DHCP
+pub(crate) fn format_dns_servers(servers: &[String]) -> String {
+    let mut seen = HashSet::new();
+    let unique: Vec<&str> = servers
+        .iter()
+        .map(|s| s.as_str())
+        .filter(|s| seen.insert(*s))
+        .collect();
+    let addresses = unique.join(", ");
+
+    if is_dhcp_dns(&addresses) {
+        format!("Автопилот (DHCP): {}", addresses)
+    } else {
+        addresses
+    }
+}
+
+// Пустые адреса пропускаем - резервные DNS необязательны, остальные должны быть IP.
+// Но хотя бы один сервер нужен: пустой resolv.conf, пустой ignore-auto-dns профиль NetworkManager
+// или линк resolved без DNS с маршрутом "." ломают разрешение имен во всей системе
+pub(crate) fn parse_servers(servers: &[String]) -> Result<Vec<IpAddr>, DnsError> {
+    let servers = servers
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().map_err(|_| DnsError::InvalidAddress(s.to_string())))
+        .collect::<Result<Vec<IpAddr>, DnsError>>()?;
+    if servers.is_empty() {
+        return Err(DnsError::InvalidAddress("не указан ни один сервер".to_string()));
+    }
+    Ok(servers)
+}
+
+// Явно запрошенные, но отсутствующие адаптеры попадают в отчет как ошибки
+pub(crate) fn missing_changes(missing: Vec<String>) -> Vec<AdapterChange> {
+    missing
+        .into_iter()
+        .map(|name| AdapterChange::err(name.clone(), DnsError::AdapterNotFound(name)))
+        .collect()
+}
+
+pub(crate) fn join_servers(servers: &[IpAddr]) -> String {
+    servers.iter().map(|ip| ip.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn is_dhcp_dns(addresses: &str) -> bool {
+    // Простая проверка на DHCP адреса
+    addresses.contains("192.168.") || addresses.contains("10.") || addresses.contains("172.")
+}
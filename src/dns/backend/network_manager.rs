@@ -0,0 +1,743 @@
+// Бэкенд DNS для Linux через профили подключений NetworkManager (по D-Bus)
+// Изменения пишутся в профиль (ipv4.dns, ipv4.ignore-auto-dns, ipv6.dns), а подключение
+// активируется заново - иначе NetworkManager вернет старые DNS при переподключении
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use zbus::blocking::{fdo::DBusProxy, Connection};
+use zbus::dbus_proxy;
+use zbus::names::BusName;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use super::resolved::ResolvedBackend;
+use super::{format_dns_servers, join_servers, missing_changes, parse_servers, DnsBackend, DNS_CACHE};
+use crate::dns::error::{AdapterChange, ApplyReport, DnsError};
+use crate::dns::plan::{AdapterPlan, ChangePlan};
+use crate::network::adapters::{AdapterSelection, NetworkConnection};
+use crate::network::netlink;
+
+const NM_SERVICE: &str = "org.freedesktop.NetworkManager";
+
+type ConnectionSettings = HashMap<String, HashMap<String, OwnedValue>>;
+
+// Разделы профиля с секретами, которые NetworkManager хранит сам: пароли Wi-Fi и 802.1x,
+// ключи WireGuard, VPN и мобильных подключений
+const SECRET_SETTINGS: [&str; 8] =
+    ["802-11-wireless-security", "802-1x", "wireguard", "vpn", "gsm", "cdma", "pppoe", "macsec"];
+
+#[dbus_proxy(
+    interface = "org.freedesktop.NetworkManager",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager",
+    gen_async = false
+)]
+trait NetworkManager {
+    fn activate_connection(
+        &self,
+        connection: &OwnedObjectPath,
+        device: &OwnedObjectPath,
+        specific_object: &OwnedObjectPath,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    #[dbus_proxy(property)]
+    fn active_connections(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.NetworkManager.Settings",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager/Settings",
+    gen_async = false
+)]
+trait Settings {
+    fn list_connections(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.NetworkManager.Settings.Connection",
+    default_service = "org.freedesktop.NetworkManager",
+    gen_async = false
+)]
+trait SettingsConnection {
+    fn get_settings(&self) -> zbus::Result<ConnectionSettings>;
+
+    fn get_secrets(&self, setting_name: &str) -> zbus::Result<ConnectionSettings>;
+
+    fn update(&self, properties: &ConnectionSettings) -> zbus::Result<()>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.NetworkManager.Connection.Active",
+    default_service = "org.freedesktop.NetworkManager",
+    gen_async = false
+)]
+trait ActiveConnection {
+    #[dbus_proxy(property)]
+    fn connection(&self) -> zbus::Result<OwnedObjectPath>;
+
+    #[dbus_proxy(property)]
+    fn devices(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+
+    #[dbus_proxy(property, name = "Type")]
+    fn connection_type(&self) -> zbus::Result<String>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.NetworkManager.Device",
+    default_service = "org.freedesktop.NetworkManager",
+    gen_async = false
+)]
+trait Device {
+    #[dbus_proxy(property)]
+    fn interface(&self) -> zbus::Result<String>;
+
+    // "/" - у устройства нет настроенного IPv4 или IPv6
+    #[dbus_proxy(property, name = "Ip4Config")]
+    fn ip4_config(&self) -> zbus::Result<OwnedObjectPath>;
+
+    #[dbus_proxy(property, name = "Ip6Config")]
+    fn ip6_config(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+// Итоговая конфигурация устройства: DNS из профиля или полученные от DHCP
+#[dbus_proxy(
+    interface = "org.freedesktop.NetworkManager.IP4Config",
+    default_service = "org.freedesktop.NetworkManager",
+    gen_async = false
+)]
+trait Ip4Config {
+    #[dbus_proxy(property)]
+    fn nameservers(&self) -> zbus::Result<Vec<u32>>;
+}
+
+#[dbus_proxy(
+    interface = "org.freedesktop.NetworkManager.IP6Config",
+    default_service = "org.freedesktop.NetworkManager",
+    gen_async = false
+)]
+trait Ip6Config {
+    #[dbus_proxy(property)]
+    fn nameservers(&self) -> zbus::Result<Vec<Vec<u8>>>;
+}
+
+// Активное подключение вместе с путями, нужными для повторной активации
+struct ActiveProfile {
+    id: String,
+    device: String,
+    settings_path: OwnedObjectPath,
+    device_path: OwnedObjectPath,
+}
+
+impl ActiveProfile {
+    // Имя в отчетах и планах: профиль и устройство, на котором он активен
+    fn label(&self) -> String {
+        format!("{} ({})", self.id, self.device)
+    }
+}
+
+pub struct NetworkManagerBackend {
+    connection: Connection,
+}
+
+impl NetworkManagerBackend {
+    // Подключение к системной шине, если на ней есть NetworkManager
+    pub fn system() -> Option<Self> {
+        let connection = Connection::system().ok()?;
+        let dbus = DBusProxy::new(&connection).ok()?;
+        let name = BusName::try_from(NM_SERVICE).ok()?;
+        if dbus.name_has_owner(name).unwrap_or(false) {
+            Some(Self::with_connection(connection))
+        } else {
+            None
+        }
+    }
+
+    pub fn with_connection(connection: Connection) -> Self {
+        Self { connection }
+    }
+
+    fn manager(&self) -> Result<NetworkManagerProxy<'_>, DnsError> {
+        Ok(NetworkManagerProxy::new(&self.connection)?)
+    }
+
+    fn settings_connection(&self, path: &OwnedObjectPath) -> Result<SettingsConnectionProxy<'_>, DnsError> {
+        SettingsConnectionProxy::builder(&self.connection)
+            .path(path.clone())
+            .and_then(|builder| builder.build())
+            .map_err(DnsError::from)
+    }
+
+    // Все профили подключений вместе с признаком активности и устройством
+    pub fn connections(&self) -> Result<Vec<NetworkConnection>, DnsError> {
+        let settings = SettingsProxy::new(&self.connection)?;
+        let paths = settings.list_connections()?;
+
+        let active = self.active_profiles()?;
+
+        let mut connections = Vec::new();
+        for path in paths {
+            let profile = self.settings_connection(&path)?.get_settings()?;
+
+            let device = active
+                .iter()
+                .find(|a| a.settings_path == path)
+                .map(|a| a.device.clone());
+
+            connections.push(NetworkConnection {
+                id: setting_str(&profile, "connection", "id"),
+                uuid: setting_str(&profile, "connection", "uuid"),
+                connection_type: setting_str(&profile, "connection", "type"),
+                active: device.is_some(),
+                device: device.unwrap_or_default(),
+            });
+        }
+
+        Ok(connections)
+    }
+
+    // Активные подключения кроме loopback - аналог "Status -eq 'Up'" на Windows
+    fn active_profiles(&self) -> Result<Vec<ActiveProfile>, DnsError> {
+        let paths = self.manager()?.active_connections()?;
+
+        let mut profiles = Vec::new();
+        for path in paths {
+            let active = ActiveConnectionProxy::builder(&self.connection)
+                .path(path.clone())
+                .and_then(|builder| builder.build())?;
+
+            if active.connection_type().unwrap_or_default() == "loopback" {
+                continue;
+            }
+
+            let settings_path = active.connection()?;
+            let device_path = active
+                .devices()
+                .ok()
+                .and_then(|devices| devices.into_iter().next())
+                .unwrap_or_else(root_path);
+
+            let settings = self.settings_connection(&settings_path)?.get_settings()?;
+
+            profiles.push(ActiveProfile {
+                id: setting_str(&settings, "connection", "id"),
+                device: self.device_interface(&device_path),
+                settings_path,
+                device_path,
+            });
+        }
+
+        Ok(profiles)
+    }
+
+    fn device_interface(&self, path: &OwnedObjectPath) -> String {
+        DeviceProxy::builder(&self.connection)
+            .path(path.clone())
+            .and_then(|builder| builder.build())
+            .and_then(|device| device.interface())
+            .unwrap_or_default()
+    }
+
+    // Серверы, которые устройство использует сейчас; в профиле на DHCP их нет, поэтому берем из IP4Config/IP6Config
+    fn device_dns(&self, path: &OwnedObjectPath) -> Vec<IpAddr> {
+        let Ok(device) = DeviceProxy::builder(&self.connection).path(path.clone()).and_then(|builder| builder.build()) else {
+            return Vec::new();
+        };
+
+        let mut servers = Vec::new();
+        let ipv4 = device
+            .ip4_config()
+            .and_then(|config| Ip4ConfigProxy::builder(&self.connection).path(config)?.build())
+            .and_then(|config| config.nameservers());
+        servers.extend(ipv4.unwrap_or_default().into_iter().map(|raw| IpAddr::from(raw.to_ne_bytes())));
+
+        let ipv6 = device
+            .ip6_config()
+            .and_then(|config| Ip6ConfigProxy::builder(&self.connection).path(config)?.build())
+            .and_then(|config| config.nameservers());
+        servers.extend(
+            ipv6.unwrap_or_default()
+                .into_iter()
+                .filter_map(|bytes| <[u8; 16]>::try_from(bytes).ok())
+                .map(IpAddr::from),
+        );
+        servers
+    }
+
+    // Активные профили выбранных адаптеров и имена, для которых профиля нет
+    fn select_profiles(&self, selection: &AdapterSelection) -> Result<(Vec<ActiveProfile>, Vec<String>), DnsError> {
+        let adapters = netlink::get_network_adapters().map_err(DnsError::BackendUnavailable)?;
+        let (selected, mut missing) = selection.resolve(&adapters);
+        let mut profiles = self.active_profiles()?;
+
+        let mut chosen = Vec::new();
+        for adapter in selected {
+            match profiles.iter().position(|p| p.device == adapter.name) {
+                Some(position) => chosen.push(profiles.swap_remove(position)),
+                // Неуправляемые NM интерфейсы (docker0 и т.п.) молча пропускаем, если их не просили явно
+                None if matches!(selection, AdapterSelection::Names(_)) => {
+                    missing.push(format!("{} (нет активного профиля NetworkManager)", adapter.name));
+                }
+                None => {}
+            }
+        }
+
+        Ok((chosen, missing))
+    }
+
+    // Меняет DNS в активных профилях выбранных адаптеров и активирует их заново
+    fn update_profiles(
+        &self,
+        selection: &AdapterSelection,
+        servers: &[IpAddr],
+        ignore_auto_dns: bool,
+    ) -> Result<ApplyReport, DnsError> {
+        let manager = self.manager()?;
+        let (profiles, missing) = self.select_profiles(selection)?;
+
+        let change = if ignore_auto_dns { format!("DNS: {}", join_servers(servers)) } else { "DNS: DHCP".to_string() };
+
+        let mut changes = missing_changes(missing);
+        for profile in &profiles {
+            let result = self
+                .update_profile(&manager, profile, servers, ignore_auto_dns)
+                .map(|_| change.clone());
+            changes.push(AdapterChange { adapter: profile.label(), result });
+        }
+
+        ApplyReport::new(self.name(), changes).into_result()
+    }
+
+    // Update и ActivateConnection показываем как эквивалентные команды nmcli
+    fn plan_profiles(&self, selection: &AdapterSelection, servers: &[IpAddr], ignore_auto_dns: bool) -> Result<ChangePlan, DnsError> {
+        let (profiles, missing) = self.select_profiles(selection)?;
+
+        let (ipv4, ipv6): (Vec<IpAddr>, Vec<IpAddr>) = servers.iter().partition(|ip| ip.is_ipv4());
+        let ignore = if ignore_auto_dns { "yes" } else { "no" };
+
+        let mut plans = Vec::new();
+        for profile in &profiles {
+            let settings = self.settings_connection(&profile.settings_path)?.get_settings()?;
+            // Снимок - только DNS самого профиля: адреса от DHCP сюда не попадают,
+            // так что у профиля на DHCP список пуст и откат его сбросит
+            let current = configured_dns(&settings).iter().map(|ip| ip.to_string()).collect();
+
+            let id = shell_quote(&profile.id);
+            let commands = vec![
+                format!(
+                    "nmcli connection modify {} ipv4.dns {} ipv4.ignore-auto-dns {} ipv6.dns {} ipv6.ignore-auto-dns {}",
+                    id,
+                    shell_quote(&join_servers(&ipv4).replace(", ", " ")),
+                    ignore,
+                    shell_quote(&join_servers(&ipv6).replace(", ", " ")),
+                    ignore
+                ),
+                format!("nmcli connection up {} ifname {}", id, profile.device),
+            ];
+
+            let plan = if ignore_auto_dns {
+                AdapterPlan::set(profile.label(), current, servers, commands)
+            } else {
+                AdapterPlan::reset(profile.label(), current, commands)
+            };
+            plans.push(AdapterPlan { interface: profile.device.clone(), ..plan });
+        }
+
+        Ok(ChangePlan::new(self.name(), plans, missing))
+    }
+
+    fn update_profile(
+        &self,
+        manager: &NetworkManagerProxy<'_>,
+        profile: &ActiveProfile,
+        servers: &[IpAddr],
+        ignore_auto_dns: bool,
+    ) -> Result<(), DnsError> {
+        let settings_connection = self.settings_connection(&profile.settings_path)?;
+        let mut settings = settings_connection.get_settings()?;
+
+        // GetSettings отдает профиль без секретов, а Update заменяет его целиком -
+        // без GetSecrets сохраненные пароли Wi-Fi и 802.1x пропали бы из профиля
+        for section in SECRET_SETTINGS {
+            if settings.contains_key(section) {
+                for (name, secrets) in settings_connection.get_secrets(section)? {
+                    settings.entry(name).or_default().extend(secrets);
+                }
+            }
+        }
+
+        apply_dns_settings(&mut settings, servers, ignore_auto_dns);
+
+        settings_connection.update(&settings)?;
+        manager
+            .activate_connection(&profile.settings_path, &profile.device_path, &root_path())?;
+
+        Ok(())
+    }
+}
+
+impl DnsBackend for NetworkManagerBackend {
+    fn name(&self) -> &'static str {
+        "NetworkManager"
+    }
+
+    fn adapter_dns(&self) -> Result<Vec<(String, Vec<String>)>, DnsError> {
+        Ok(self
+            .active_profiles()?
+            .into_iter()
+            .filter_map(|profile| {
+                let servers: Vec<String> = self.device_dns(&profile.device_path).iter().map(|ip| ip.to_string()).collect();
+                (!servers.is_empty()).then_some((profile.device, servers))
+            })
+            .collect())
+    }
+
+    fn get_current_dns(&self) -> Result<String, DnsError> {
+        let mut dns_servers = Vec::new();
+        let mut ignore_auto_dns = false;
+
+        for profile in self.active_profiles()? {
+            let settings = self.settings_connection(&profile.settings_path)?.get_settings()?;
+
+            dns_servers.extend(configured_dns(&settings).into_iter().map(|ip| ip.to_string()));
+            ignore_auto_dns |= setting_bool(&settings, "ipv4", "ignore-auto-dns")
+                || setting_bool(&settings, "ipv6", "ignore-auto-dns");
+        }
+
+        if dns_servers.is_empty() {
+            // В профилях ничего не задано - DNS приходят от DHCP
+            Ok("Автопилот (DHCP): адреса не получены".to_string())
+        } else if ignore_auto_dns {
+            Ok(dns_servers.join(", "))
+        } else {
+            Ok(format_dns_servers(&dns_servers))
+        }
+    }
+
+    fn set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ApplyReport, DnsError> {
+        let servers = parse_servers(servers)?;
+        self.update_profiles(selection, &servers, true)
+    }
+
+    fn reset_dns(&self, selection: &AdapterSelection) -> Result<ApplyReport, DnsError> {
+        self.update_profiles(selection, &[], false)
+    }
+
+    fn plan_set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ChangePlan, DnsError> {
+        let servers = parse_servers(servers)?;
+        self.plan_profiles(selection, &servers, true)
+    }
+
+    fn plan_reset_dns(&self, selection: &AdapterSelection) -> Result<ChangePlan, DnsError> {
+        self.plan_profiles(selection, &[], false)
+    }
+
+    // Сам NetworkManager DNS не кэширует - кэш держит systemd-resolved, если он запущен
+    fn flush_cache(&self) -> Result<ApplyReport, DnsError> {
+        match ResolvedBackend::on_bus(self.connection.clone()) {
+            Some(resolved) => resolved.flush_cache(),
+            None => Ok(ApplyReport::new(self.name(), vec![AdapterChange::ok(DNS_CACHE, "локального кэша нет")])),
+        }
+    }
+}
+
+fn apply_dns_settings(settings: &mut ConnectionSettings, servers: &[IpAddr], ignore_auto_dns: bool) {
+    let ipv4: Vec<u32> = servers
+        .iter()
+        .filter_map(|ip| match ip {
+            // NetworkManager хранит IPv4 как u32 в сетевом порядке байт
+            IpAddr::V4(v4) => Some(u32::from_ne_bytes(v4.octets())),
+            IpAddr::V6(_) => None,
+        })
+        .collect();
+    let ipv6: Vec<Vec<u8>> = servers
+        .iter()
+        .filter_map(|ip| match ip {
+            IpAddr::V4(_) => None,
+            IpAddr::V6(v6) => Some(v6.octets().to_vec()),
+        })
+        .collect();
+
+    let ipv4_section = settings.entry("ipv4".to_string()).or_default();
+    // dns-data в новых версиях NM имеет приоритет над dns - убираем, чтобы не было конфликта
+    ipv4_section.remove("dns-data");
+    ipv4_section.insert("dns".to_string(), Value::from(ipv4).into());
+    ipv4_section.insert("ignore-auto-dns".to_string(), Value::from(ignore_auto_dns).into());
+
+    let ipv6_section = settings.entry("ipv6".to_string()).or_default();
+    ipv6_section.remove("dns-data");
+    ipv6_section.insert("dns".to_string(), Value::from(ipv6).into());
+    ipv6_section.insert("ignore-auto-dns".to_string(), Value::from(ignore_auto_dns).into());
+}
+
+fn configured_dns(settings: &ConnectionSettings) -> Vec<IpAddr> {
+    let mut servers = Vec::new();
+
+    if let Some(value) = settings.get("ipv4").and_then(|section| section.get("dns")) {
+        if let Ok(ipv4) = Vec::<u32>::try_from(value.clone()) {
+            servers.extend(ipv4.into_iter().map(|raw| IpAddr::from(raw.to_ne_bytes())));
+        }
+    }
+
+    if let Some(value) = settings.get("ipv6").and_then(|section| section.get("dns")) {
+        if let Ok(ipv6) = Vec::<Vec<u8>>::try_from(value.clone()) {
+            servers.extend(
+                ipv6.into_iter()
+                    .filter_map(|bytes| <[u8; 16]>::try_from(bytes).ok())
+                    .map(IpAddr::from),
+            );
+        }
+    }
+
+    servers
+}
+
+fn setting_str(settings: &ConnectionSettings, section: &str, key: &str) -> String {
+    settings
+        .get(section)
+        .and_then(|s| s.get(key))
+        .and_then(|v| v.downcast_ref::<str>())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn setting_bool(settings: &ConnectionSettings, section: &str, key: &str) -> bool {
+    settings
+        .get(section)
+        .and_then(|s| s.get(key))
+        .and_then(|v| v.downcast_ref::<bool>())
+        .copied()
+        .unwrap_or(false)
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// "/" в ActivateConnection означает "выбрать автоматически"
+fn root_path() -> OwnedObjectPath {
+    OwnedObjectPath::try_from("/").expect("корневой путь D-Bus всегда корректен")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use zbus::dbus_interface;
+
+    use super::*;
+    use crate::dns::backend::test_bus::PrivateBus;
+
+    const PROFILE: &str = "/org/freedesktop/NetworkManager/Settings/1";
+    const ACTIVE: &str = "/org/freedesktop/NetworkManager/ActiveConnection/1";
+    const DEVICE: &str = "/org/freedesktop/NetworkManager/Devices/1";
+    const IP4_CONFIG: &str = "/org/freedesktop/NetworkManager/IP4Config/1";
+
+    type Calls = Arc<Mutex<Vec<String>>>;
+
+    fn path(path: &str) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(path).unwrap()
+    }
+
+    fn section(values: Vec<(&str, Value<'static>)>) -> HashMap<String, OwnedValue> {
+        values.into_iter().map(|(key, value)| (key.to_string(), value.into())).collect()
+    }
+
+    // Заглушки объектов NetworkManager: один Wi-Fi профиль, активный на "lo"
+    struct StubManager {
+        calls: Calls,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.NetworkManager")]
+    impl StubManager {
+        fn activate_connection(
+            &self,
+            connection: OwnedObjectPath,
+            device: OwnedObjectPath,
+            specific_object: OwnedObjectPath,
+        ) -> OwnedObjectPath {
+            self.calls.lock().unwrap().push(format!(
+                "ActivateConnection {} {} {}",
+                connection.as_str(),
+                device.as_str(),
+                specific_object.as_str()
+            ));
+            path(ACTIVE)
+        }
+
+        #[dbus_interface(property)]
+        fn active_connections(&self) -> Vec<OwnedObjectPath> {
+            vec![path(ACTIVE)]
+        }
+    }
+
+    struct StubSettings;
+
+    #[dbus_interface(name = "org.freedesktop.NetworkManager.Settings")]
+    impl StubSettings {
+        fn list_connections(&self) -> Vec<OwnedObjectPath> {
+            vec![path(PROFILE)]
+        }
+    }
+
+    // Как и настоящий NM, GetSettings отдает профиль без секретов
+    struct StubProfile {
+        settings: Arc<Mutex<ConnectionSettings>>,
+        secrets: ConnectionSettings,
+        calls: Calls,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.NetworkManager.Settings.Connection")]
+    impl StubProfile {
+        fn get_settings(&self) -> ConnectionSettings {
+            let mut settings = self.settings.lock().unwrap().clone();
+            if let Some(security) = settings.get_mut("802-11-wireless-security") {
+                security.remove("psk");
+            }
+            settings
+        }
+
+        fn get_secrets(&self, setting_name: String) -> ConnectionSettings {
+            self.calls.lock().unwrap().push(format!("GetSecrets {}", setting_name));
+            self.secrets.iter().filter(|(name, _)| **name == setting_name).map(|(k, v)| (k.clone(), v.clone())).collect()
+        }
+
+        fn update(&self, properties: ConnectionSettings) {
+            self.calls.lock().unwrap().push("Update".to_string());
+            *self.settings.lock().unwrap() = properties;
+        }
+    }
+
+    struct StubActive;
+
+    #[dbus_interface(name = "org.freedesktop.NetworkManager.Connection.Active")]
+    impl StubActive {
+        #[dbus_interface(property)]
+        fn connection(&self) -> OwnedObjectPath {
+            path(PROFILE)
+        }
+
+        #[dbus_interface(property)]
+        fn devices(&self) -> Vec<OwnedObjectPath> {
+            vec![path(DEVICE)]
+        }
+
+        #[dbus_interface(property, name = "Type")]
+        fn connection_type(&self) -> String {
+            "802-11-wireless".to_string()
+        }
+    }
+
+    struct StubDevice;
+
+    #[dbus_interface(name = "org.freedesktop.NetworkManager.Device")]
+    impl StubDevice {
+        #[dbus_interface(property)]
+        fn interface(&self) -> String {
+            "lo".to_string()
+        }
+
+        #[dbus_interface(property, name = "Ip4Config")]
+        fn ip4_config(&self) -> OwnedObjectPath {
+            path(IP4_CONFIG)
+        }
+
+        // IPv6 на устройстве не настроен
+        #[dbus_interface(property, name = "Ip6Config")]
+        fn ip6_config(&self) -> OwnedObjectPath {
+            root_path()
+        }
+    }
+
+    // Адрес от DHCP: в профиле его нет
+    struct StubIp4Config;
+
+    #[dbus_interface(name = "org.freedesktop.NetworkManager.IP4Config")]
+    impl StubIp4Config {
+        #[dbus_interface(property)]
+        fn nameservers(&self) -> Vec<u32> {
+            vec![u32::from_be(0x0a00_0035)]
+        }
+    }
+
+    fn wifi_profile() -> ConnectionSettings {
+        HashMap::from([
+            (
+                "connection".to_string(),
+                section(vec![("id", Value::from("Дом")), ("uuid", Value::from("4b9a")), ("type", Value::from("802-11-wireless"))]),
+            ),
+            (
+                "802-11-wireless-security".to_string(),
+                section(vec![("key-mgmt", Value::from("wpa-psk")), ("psk", Value::from("secret-psk"))]),
+            ),
+            (
+                "ipv4".to_string(),
+                section(vec![("method", Value::from("auto")), ("dns", Value::from(vec![u32::from_be(0xc0a8_0101)]))]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn backend_updates_profiles_on_a_stubbed_network_manager() {
+        let Some(bus) = PrivateBus::start() else {
+            // Без dbus-daemon проверять не на чем
+            return;
+        };
+
+        let calls = Calls::default();
+        let settings = Arc::new(Mutex::new(wifi_profile()));
+        let secrets = HashMap::from([(
+            "802-11-wireless-security".to_string(),
+            section(vec![("psk", Value::from("secret-psk"))]),
+        )]);
+        let service = bus.serve(NM_SERVICE, "/org/freedesktop/NetworkManager", StubManager { calls: calls.clone() });
+        let objects = service.object_server();
+        objects.at("/org/freedesktop/NetworkManager/Settings", StubSettings).unwrap();
+        objects.at(PROFILE, StubProfile { settings: settings.clone(), secrets, calls: calls.clone() }).unwrap();
+        objects.at(ACTIVE, StubActive).unwrap();
+        objects.at(DEVICE, StubDevice).unwrap();
+        objects.at(IP4_CONFIG, StubIp4Config).unwrap();
+
+        let backend = NetworkManagerBackend::with_connection(bus.connect());
+        let selection = AdapterSelection::Names(vec!["lo".to_string()]);
+
+        let connections = backend.connections().unwrap();
+        assert_eq!((connections[0].id.as_str(), connections[0].device.as_str()), ("Дом", "lo"));
+        assert!(connections[0].active);
+        // IPv4 в профиле - u32 в сетевом порядке байт
+        assert_eq!(backend.get_current_dns().unwrap(), "Автопилот (DHCP): 192.168.1.1");
+        // DNS адаптера - итоговые с устройства, а не из профиля
+        assert_eq!(backend.adapter_dns().unwrap(), vec![("lo".to_string(), vec!["10.0.0.53".to_string()])]);
+
+        let servers = ["1.2.3.4".to_string(), "2606:4700:4700::1111".to_string()];
+        let plan = backend.plan_set_dns(&selection, &servers).unwrap();
+        assert_eq!((plan.adapters[0].adapter.as_str(), plan.adapters[0].interface.as_str()), ("Дом (lo)", "lo"));
+        assert_eq!(plan.adapters[0].current, vec!["192.168.1.1"]);
+        assert!(calls.lock().unwrap().is_empty(), "план ничего не меняет");
+
+        // ignore-auto-dns без серверов оставил бы профиль вовсе без DNS
+        for empty in [Vec::new(), vec!["".to_string(), "  ".to_string()]] {
+            assert!(matches!(backend.set_dns(&selection, &empty), Err(DnsError::InvalidAddress(_))));
+            assert!(matches!(backend.plan_set_dns(&selection, &empty), Err(DnsError::InvalidAddress(_))));
+        }
+        assert!(calls.lock().unwrap().is_empty(), "пустой список отклонен до Update");
+
+        backend.set_dns(&selection, &servers).unwrap();
+        let stored = settings.lock().unwrap().clone();
+        assert_eq!(Vec::<u32>::try_from(stored["ipv4"]["dns"].clone()).unwrap(), vec![u32::from_be(0x0102_0304)]);
+        assert_eq!(configured_dns(&stored), vec!["1.2.3.4".parse::<IpAddr>().unwrap(), "2606:4700:4700::1111".parse().unwrap()]);
+        assert!(setting_bool(&stored, "ipv4", "ignore-auto-dns") && setting_bool(&stored, "ipv6", "ignore-auto-dns"));
+        assert_eq!(setting_str(&stored, "802-11-wireless-security", "psk"), "secret-psk", "пароль Wi-Fi не потерян");
+        assert_eq!(backend.get_current_dns().unwrap(), "1.2.3.4, 2606:4700:4700::1111");
+
+        backend.reset_dns(&selection).unwrap();
+        let stored = settings.lock().unwrap().clone();
+        assert!(configured_dns(&stored).is_empty());
+        assert!(!setting_bool(&stored, "ipv4", "ignore-auto-dns"));
+        assert_eq!(setting_str(&stored, "802-11-wireless-security", "psk"), "secret-psk");
+
+        let activate = format!("ActivateConnection {} {} /", PROFILE, DEVICE);
+        let update = ["GetSecrets 802-11-wireless-security", "Update", activate.as_str()];
+        assert_eq!(*calls.lock().unwrap(), [update, update].concat());
+    }
+}
@@ -0,0 +1,312 @@
+// Бэкенд DNS для Windows через PowerShell и cmd.exe
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{format_dns_servers, join_servers, missing_changes, parse_servers, DnsBackend, DNS_CACHE};
+use crate::command::{run_checked, CommandRunner, CommandSpec};
+use std::net::IpAddr;
+
+use crate::dns::error::{AdapterChange, ApplyReport, DnsError};
+use crate::dns::plan::{configured_servers, AdapterPlan, ChangePlan};
+use crate::network::adapters::{query_powershell_adapters, AdapterSelection};
+
+const CURRENT_DNS_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct PowerShellBackend {
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl PowerShellBackend {
+    pub fn new(runner: Arc<dyn CommandRunner>) -> Self {
+        Self { runner }
+    }
+
+    fn run_powershell_command(&self, command: &str) -> Result<String, DnsError> {
+        Ok(run_checked(self.runner.as_ref(), &CommandSpec::powershell(command))?)
+    }
+
+    // Отдельная команда на каждый адаптер, чтобы ошибка одного не скрывала результат остальных
+    fn for_each_adapter(
+        &self,
+        selection: &AdapterSelection,
+        change: &str,
+        command_for: impl Fn(&str) -> String,
+    ) -> Result<ApplyReport, DnsError> {
+        let adapters = query_powershell_adapters(self.runner.as_ref())?;
+        let (selected, missing) = selection.resolve(&adapters);
+
+        let mut changes = missing_changes(missing);
+        for adapter in selected {
+            changes.push(match self.run_powershell_command(&command_for(&adapter.name)) {
+                Ok(_) => AdapterChange::ok(&adapter.name, change),
+                Err(e) => AdapterChange::err(&adapter.name, e),
+            });
+        }
+
+        ApplyReport::new(self.name(), changes).into_result()
+    }
+
+    // Get-NetAdapter только читает состояние, так что план ничего не меняет
+    fn plan_adapters(
+        &self,
+        selection: &AdapterSelection,
+        plan_for: impl Fn(&str, Vec<String>) -> AdapterPlan,
+    ) -> Result<ChangePlan, DnsError> {
+        let adapters = query_powershell_adapters(self.runner.as_ref())?;
+        let (selected, missing) = selection.resolve(&adapters);
+
+        let plans = selected
+            .iter()
+            .map(|adapter| {
+                plan_for(&adapter.name, configured_servers(&adapter.dns_servers)).with_dhcp_source(adapter.dns_from_dhcp)
+            })
+            .collect();
+        Ok(ChangePlan::new(self.name(), plans, missing))
+    }
+}
+
+// Строка PowerShell в одинарных кавычках - сами кавычки удваиваются
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// Команды set/reset нужны и для выполнения, и для плана - собираем их в одном месте
+fn set_command(adapter: &str, servers: &[IpAddr]) -> String {
+    let server_list = servers
+        .iter()
+        .map(|ip| quote(&ip.to_string()))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("Set-DnsClientServerAddress -InterfaceAlias {} -ServerAddresses ({})", quote(adapter), server_list)
+}
+
+fn reset_command(adapter: &str) -> String {
+    // Сбрасываем все DNS настройки и явно включаем получение от DHCP
+    format!(
+        "Set-DnsClientServerAddress -InterfaceAlias {0} -ResetServerAddresses\nSet-NetIPInterface -InterfaceAlias {0} -Dhcp Enabled",
+        quote(adapter)
+    )
+}
+
+impl DnsBackend for PowerShellBackend {
+    fn name(&self) -> &'static str {
+        "Windows PowerShell"
+    }
+
+    fn get_current_dns(&self) -> Result<String, DnsError> {
+        // Сначала пробуем PowerShell для получения текущих DNS серверов;
+        // долго не ждем - есть запасной вариант через ipconfig
+        let spec = CommandSpec::powershell("Get-DnsClientServerAddress | Sort-Object AddressFamily | Select-Object -ExpandProperty ServerAddresses")
+            .with_timeout(CURRENT_DNS_TIMEOUT);
+        let ps_output = run_checked(self.runner.as_ref(), &spec);
+
+        if let Ok(dns_list) = ps_output {
+            if !dns_list.is_empty() {
+                let dns_servers: Vec<String> = dns_list.split_whitespace().map(|s| s.to_string()).collect();
+                if !dns_servers.is_empty() {
+                    return Ok(format_dns_servers(&dns_servers));
+                }
+            }
+        }
+
+        // Fallback - используем ipconfig напрямую с полным путем
+        let stdout = run_checked(self.runner.as_ref(), &CommandSpec::cmd("ipconfig /all"))?;
+        Ok(parse_ipconfig_all(&stdout))
+    }
+
+    fn set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ApplyReport, DnsError> {
+        let servers = parse_servers(servers)?;
+        self.for_each_adapter(selection, &format!("DNS: {}", join_servers(&servers)), |name| {
+            set_command(name, &servers)
+        })
+    }
+
+    fn reset_dns(&self, selection: &AdapterSelection) -> Result<ApplyReport, DnsError> {
+        self.for_each_adapter(selection, "DNS: DHCP", reset_command)
+    }
+
+    fn plan_set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ChangePlan, DnsError> {
+        let servers = parse_servers(servers)?;
+        self.plan_adapters(selection, |adapter, current| {
+            AdapterPlan::set(adapter, current, &servers, vec![set_command(adapter, &servers)])
+        })
+    }
+
+    fn plan_reset_dns(&self, selection: &AdapterSelection) -> Result<ChangePlan, DnsError> {
+        self.plan_adapters(selection, |adapter, current| {
+            AdapterPlan::reset(adapter, current, vec![reset_command(adapter)])
+        })
+    }
+
+    fn flush_cache(&self) -> Result<ApplyReport, DnsError> {
+        self.run_powershell_command("Clear-DnsClientCache")?;
+        Ok(ApplyReport::new(self.name(), vec![AdapterChange::ok(DNS_CACHE, "очищен")]))
+    }
+}
+
+// Разбор вывода "ipconfig /all": строка "DNS Servers . . . : адрес" и адреса
+// следующих серверов на строках продолжения без двоеточия-разделителя
+fn parse_ipconfig_all(stdout: &str) -> String {
+    let mut dns_servers = Vec::new();
+    let mut is_dhcp = false;
+    let mut in_dns_block = false;
+
+    for line in stdout.lines() {
+        if line.contains("DNS servers configured through DHCP") {
+            is_dhcp = true;
+        }
+
+        if line.contains("DNS Servers") && !line.contains("configured through DHCP") {
+            in_dns_block = true;
+            // Делим по " : ", а не по ':' - иначе IPv6 адрес развалится на части
+            let ip_part = line.split_once(" : ").map(|(_, value)| value).unwrap_or("");
+            push_dns_server(&mut dns_servers, ip_part);
+        } else if in_dns_block && !line.trim().is_empty() && !line.contains(" : ") {
+            push_dns_server(&mut dns_servers, line);
+        } else {
+            in_dns_block = false;
+        }
+    }
+
+    if dns_servers.is_empty() {
+        "Автопилот (DHCP): адреса не получены".to_string()
+    } else if is_dhcp {
+        format!("Автопилот (DHCP): {}", dns_servers.join(", "))
+    } else {
+        dns_servers.join(", ")
+    }
+}
+
+fn push_dns_server(dns_servers: &mut Vec<String>, ip_part: &str) {
+    let ip_part = ip_part.trim();
+    if ip_part.is_empty() || ip_part.contains("None") {
+        return;
+    }
+
+    // Разбиваем на отдельные IP адреса
+    for ip in ip_part.split(',') {
+        let clean_ip = ip.trim();
+        if !clean_ip.is_empty() && clean_ip != "0.0.0.0" {
+            dns_servers.push(clean_ip.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::replay::{Recording, ReplayRunner};
+    use crate::command::CommandOutput;
+
+    fn fixture(name: &str) -> Arc<dyn CommandRunner> {
+        let path = format!("{}/fixtures/commands/{}", env!("CARGO_MANIFEST_DIR"), name);
+        Arc::new(ReplayRunner::from_file(path).unwrap())
+    }
+
+    #[test]
+    fn current_dns_from_powershell_is_deduplicated() {
+        let spec = CommandSpec::powershell("Get-DnsClientServerAddress | Sort-Object AddressFamily | Select-Object -ExpandProperty ServerAddresses");
+        let runner = ReplayRunner::new(vec![Recording {
+            program: spec.program,
+            args: spec.args,
+            output: CommandOutput {
+                exit_code: Some(0),
+                stdout: "192.168.1.1\r\n8.8.8.8\r\n192.168.1.1\r\n".to_string(),
+                stderr: String::new(),
+            },
+        }]);
+
+        let backend = PowerShellBackend::new(Arc::new(runner));
+        assert_eq!(backend.get_current_dns().unwrap(), "Автопилот (DHCP): 192.168.1.1, 8.8.8.8");
+    }
+
+    #[test]
+    fn current_dns_falls_back_to_ipconfig() {
+        let backend = PowerShellBackend::new(fixture("ipconfig_all.json"));
+        assert_eq!(
+            backend.get_current_dns().unwrap(),
+            "1.1.1.1, 1.0.0.1, 2606:4700:4700::1111, fec0:0:0:ffff::1%1"
+        );
+    }
+
+    #[test]
+    fn unrecorded_command_is_an_error() {
+        let backend = PowerShellBackend::new(fixture("ipconfig_all.json"));
+        assert!(matches!(
+            backend.reset_dns(&AdapterSelection::AllUp),
+            Err(DnsError::BackendUnavailable(_))
+        ));
+    }
+
+    #[test]
+    fn set_dns_reports_each_selected_adapter() {
+        let path = format!("{}/fixtures/commands/get_net_adapter_two.json", env!("CARGO_MANIFEST_DIR"));
+        let mut recordings: Vec<Recording> = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let spec = CommandSpec::powershell("Set-DnsClientServerAddress -InterfaceAlias 'Ethernet' -ServerAddresses ('1.1.1.1')");
+        recordings.push(Recording {
+            program: spec.program,
+            args: spec.args,
+            output: CommandOutput { exit_code: Some(0), ..Default::default() },
+        });
+
+        let backend = PowerShellBackend::new(Arc::new(ReplayRunner::new(recordings)));
+        let selection = AdapterSelection::Names(vec!["Ethernet".to_string(), "Wi-Fi".to_string()]);
+        let Err(DnsError::PartialSuccess(report)) = backend.set_dns(&selection, &["1.1.1.1".to_string()]) else {
+            panic!("ожидался частичный успех");
+        };
+
+        assert_eq!(report.changes.len(), 2);
+        assert_eq!(report.changes[0].adapter, "Wi-Fi");
+        assert!(matches!(report.changes[0].result, Err(DnsError::AdapterNotFound(_))));
+        assert_eq!(report.changes[1].adapter, "Ethernet");
+        assert_eq!(report.changes[1].result.as_ref().unwrap(), "DNS: 1.1.1.1");
+    }
+
+    #[test]
+    fn invalid_server_address_is_rejected_before_running_commands() {
+        // Пустой ReplayRunner упал бы на любой команде с BackendUnavailable
+        let backend = PowerShellBackend::new(Arc::new(ReplayRunner::new(Vec::new())));
+        assert!(matches!(
+            backend.set_dns(&AdapterSelection::AllUp, &["1.1.1.1".to_string(), "1.0.0.x".to_string()]),
+            Err(DnsError::InvalidAddress(address)) if address == "1.0.0.x"
+        ));
+    }
+
+    #[test]
+    fn empty_server_list_is_rejected_before_running_commands() {
+        // Иначе ушло бы Set-DnsClientServerAddress -ServerAddresses ()
+        let backend = PowerShellBackend::new(Arc::new(ReplayRunner::new(Vec::new())));
+        for servers in [Vec::new(), vec!["".to_string(), "  ".to_string()]] {
+            assert!(matches!(backend.set_dns(&AdapterSelection::AllUp, &servers), Err(DnsError::InvalidAddress(_))));
+            assert!(matches!(backend.plan_set_dns(&AdapterSelection::AllUp, &servers), Err(DnsError::InvalidAddress(_))));
+        }
+    }
+
+    #[test]
+    fn plan_lists_current_servers_and_exact_commands() {
+        let backend = PowerShellBackend::new(fixture("get_net_adapter_two.json"));
+        let selection = AdapterSelection::Names(vec!["Ethernet".to_string(), "Wi-Fi".to_string()]);
+
+        let servers = ["1.1.1.1".to_string(), "1.0.0.1".to_string(), "2606:4700:4700::1111".to_string()];
+        let plan = backend.plan_set_dns(&selection, &servers).unwrap();
+        assert_eq!(plan.missing, vec!["Wi-Fi"]);
+        assert_eq!(plan.adapters.len(), 1);
+
+        let ethernet = &plan.adapters[0];
+        // Снимок для отката - оба семейства, иначе откат потерял бы IPv6 DNS
+        assert_eq!(ethernet.current, vec!["192.168.1.1", "8.8.8.8", "2001:4860:4860::8888"]);
+        assert_eq!(ethernet.new, servers);
+        assert!(!ethernet.to_dhcp);
+        // В реестре нет NameServer - текущие DNS от DHCP, и откат должен их сбросить, а не закрепить
+        assert!(ethernet.from_dhcp);
+        assert_eq!(
+            ethernet.commands,
+            vec!["Set-DnsClientServerAddress -InterfaceAlias 'Ethernet' -ServerAddresses ('1.1.1.1','1.0.0.1','2606:4700:4700::1111')"]
+        );
+
+        let reset = backend.plan_reset_dns(&selection).unwrap();
+        assert!(reset.adapters[0].to_dhcp);
+        assert!(reset.adapters[0].commands[0].contains("-ResetServerAddresses"));
+    }
+}
@@ -0,0 +1,337 @@
+// Бэкенд DNS для Linux через прямую запись /etc/resolv.conf
+// Корень файловой системы настраивается, чтобы бэкенд можно было гонять на временной папке
+
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use super::{join_servers, parse_servers, DnsBackend, DNS_CACHE};
+use crate::dns::error::{AdapterChange, ApplyReport, DnsError};
+use crate::dns::plan::{AdapterPlan, ChangePlan};
+use crate::network::adapters::AdapterSelection;
+
+const MANAGED_HEADER: &str = "# Generated by dns-manager";
+
+// resolv.conf общий для всей системы, поэтому выбор адаптеров здесь не применяется
+const ALL_INTERFACES: &str = "resolv.conf (все интерфейсы)";
+
+pub struct ResolvConfBackend {
+    root: PathBuf,
+}
+
+impl Default for ResolvConfBackend {
+    fn default() -> Self {
+        Self::with_root("/")
+    }
+}
+
+impl ResolvConfBackend {
+    pub fn with_root(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn resolv_conf_path(&self) -> PathBuf {
+        self.root.join("etc/resolv.conf")
+    }
+
+    // Копия исходного содержимого, которую возвращаем при сбросе
+    fn backup_path(&self) -> PathBuf {
+        self.root.join("etc/resolv.conf.dns-manager.bak")
+    }
+
+    // Если resolv.conf был символической ссылкой (например, на stub systemd-resolved),
+    // запоминаем куда она указывала, чтобы восстановить ссылку, а не файл
+    fn link_backup_path(&self) -> PathBuf {
+        self.root.join("etc/resolv.conf.dns-manager.link")
+    }
+
+    fn is_managed(&self) -> bool {
+        self.backup_path().exists() || self.link_backup_path().exists()
+    }
+
+    fn save_backup(&self) -> io::Result<()> {
+        if self.is_managed() {
+            // Бэкап уже сделан при первой установке - не затираем оригинал своими же настройками
+            return Ok(());
+        }
+
+        let path = self.resolv_conf_path();
+        match fs::symlink_metadata(&path) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                let target = fs::read_link(&path)?;
+                fs::write(self.link_backup_path(), target.to_string_lossy().as_bytes())
+            }
+            Ok(_) => fs::copy(&path, self.backup_path()).map(|_| ()),
+            // Файла не было - сохраняем пустой бэкап, чтобы сброс вернул пустую конфигурацию
+            Err(e) if e.kind() == io::ErrorKind::NotFound => fs::write(self.backup_path(), ""),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn current_nameservers(&self) -> Vec<String> {
+        fs::read_to_string(self.resolv_conf_path())
+            .map(|content| parse_nameservers(&content))
+            .unwrap_or_default()
+    }
+
+    fn report(&self, change: impl Into<String>) -> ApplyReport {
+        ApplyReport::new(self.name(), vec![AdapterChange::ok(ALL_INTERFACES, change)])
+    }
+}
+
+// Сохраняем тип ошибки (нет прав - PrivilegeMissing), но добавляем путь к файлу
+fn file_error(action: &str, path: &Path, error: io::Error) -> DnsError {
+    match DnsError::from(error) {
+        DnsError::PrivilegeMissing(detail) => {
+            DnsError::PrivilegeMissing(format!("не удалось {} {}: {}", action, path.display(), detail))
+        }
+        other => DnsError::Failed(format!("Не удалось {} {}: {}", action, path.display(), other)),
+    }
+}
+
+impl DnsBackend for ResolvConfBackend {
+    fn name(&self) -> &'static str {
+        "resolv.conf"
+    }
+
+    fn get_current_dns(&self) -> Result<String, DnsError> {
+        let path = self.resolv_conf_path();
+        let content = fs::read_to_string(&path).map_err(|e| file_error("прочитать", &path, e))?;
+
+        let dns_servers = parse_nameservers(&content);
+
+        if dns_servers.is_empty() {
+            Ok("Автопилот (DHCP): адреса не получены".to_string())
+        } else if self.is_managed() {
+            Ok(dns_servers.join(", "))
+        } else {
+            Ok(format!("Автопилот (DHCP): {}", dns_servers.join(", ")))
+        }
+    }
+
+    fn set_dns(&self, _selection: &AdapterSelection, servers: &[String]) -> Result<ApplyReport, DnsError> {
+        let servers = parse_servers(servers)?;
+        let path = self.resolv_conf_path();
+        self.save_backup()
+            .map_err(|e| file_error("сохранить резервную копию", &path, e))?;
+
+        let current = fs::read_to_string(&path).unwrap_or_default();
+        write_atomically(&path, &render_resolv_conf(&current, &servers))
+            .map_err(|e| file_error("записать", &path, e))?;
+
+        Ok(self.report(format!("nameserver {}", join_servers(&servers))))
+    }
+
+    fn reset_dns(&self, _selection: &AdapterSelection) -> Result<ApplyReport, DnsError> {
+        let path = self.resolv_conf_path();
+        let backup = self.backup_path();
+        let link_backup = self.link_backup_path();
+
+        if link_backup.exists() {
+            let target = fs::read_to_string(&link_backup).map_err(|e| file_error("прочитать", &link_backup, e))?;
+            restore_symlink(&path, Path::new(target.trim()))
+                .map_err(|e| file_error("восстановить ссылку", &path, e))?;
+            fs::remove_file(&link_backup).map_err(|e| file_error("удалить", &link_backup, e))?;
+            Ok(self.report(format!("{} -> {}", path.display(), target.trim())))
+        } else if backup.exists() {
+            fs::rename(&backup, &path).map_err(|e| file_error("восстановить", &path, e))?;
+            Ok(self.report("восстановлен из резервной копии"))
+        } else {
+            Ok(self.report("не изменялся - сбрасывать нечего"))
+        }
+    }
+
+    fn plan_set_dns(&self, _selection: &AdapterSelection, servers: &[String]) -> Result<ChangePlan, DnsError> {
+        let servers = parse_servers(servers)?;
+        let path = self.resolv_conf_path();
+
+        let mut commands = Vec::new();
+        if !self.is_managed() {
+            commands.push(format!("cp -P {} {}", path.display(), self.backup_path().display()));
+        }
+        let nameservers: Vec<String> = servers.iter().map(|ip| format!("nameserver {}", ip)).collect();
+        commands.push(format!("write {}: {}", path.display(), nameservers.join("; ")));
+
+        // Пока файл не наш, его пишет система (DHCP, NetworkManager) - откат вернет резервную копию
+        let plan = AdapterPlan::set(ALL_INTERFACES, self.current_nameservers(), &servers, commands)
+            .with_dhcp_source(!self.is_managed());
+        Ok(ChangePlan::new(self.name(), vec![plan], Vec::new()))
+    }
+
+    fn plan_reset_dns(&self, _selection: &AdapterSelection) -> Result<ChangePlan, DnsError> {
+        let path = self.resolv_conf_path();
+        let backup = self.backup_path();
+        let link_backup = self.link_backup_path();
+
+        let commands = if link_backup.exists() {
+            let target = fs::read_to_string(&link_backup).map_err(|e| file_error("прочитать", &link_backup, e))?;
+            vec![
+                format!("ln -sf {} {}", target.trim(), path.display()),
+                format!("rm {}", link_backup.display()),
+            ]
+        } else if backup.exists() {
+            vec![format!("mv {} {}", backup.display(), path.display())]
+        } else {
+            Vec::new()
+        };
+
+        let plan = AdapterPlan::reset(ALL_INTERFACES, self.current_nameservers(), commands);
+        Ok(ChangePlan::new(self.name(), vec![plan], Vec::new()))
+    }
+
+    // glibc читает resolv.conf на каждый запрос и ничего не кэширует
+    fn flush_cache(&self) -> Result<ApplyReport, DnsError> {
+        Ok(ApplyReport::new(self.name(), vec![AdapterChange::ok(DNS_CACHE, "локального кэша нет")]))
+    }
+}
+
+fn parse_nameservers(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some("nameserver"), Some(ip)) => Some(ip.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// Сохраняем search/options и прочие строки, заменяем только nameserver
+fn render_resolv_conf(current: &str, servers: &[IpAddr]) -> String {
+    let mut output = String::new();
+    output.push_str(MANAGED_HEADER);
+    output.push('\n');
+
+    for line in current.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("nameserver") || trimmed == MANAGED_HEADER {
+            continue;
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    // glibc читает только первые три nameserver (MAXNS) - IPv4 адреса провайдера идут первыми
+    for server in servers {
+        output.push_str(&format!("nameserver {}\n", server));
+    }
+
+    output
+}
+
+// Пишем во временный файл и переименовываем, чтобы резолвер никогда не увидел половину файла
+fn write_atomically(path: &Path, content: &str) -> io::Result<()> {
+    let tmp = path.with_extension("dns-manager.tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(unix)]
+fn restore_symlink(path: &Path, target: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path)?;
+    }
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(not(unix))]
+fn restore_symlink(_path: &Path, _target: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "символические ссылки не поддерживаются"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::wire::random_id;
+    use std::env;
+
+    // Временный корень с пустой etc/, чтобы не трогать настоящий /etc/resolv.conf
+    fn temp_root() -> PathBuf {
+        let root = env::temp_dir().join(format!("dns-manager-resolv-{}-{}", std::process::id(), random_id()));
+        fs::create_dir_all(root.join("etc")).unwrap();
+        root
+    }
+
+    fn servers(addresses: &[&str]) -> Vec<String> {
+        addresses.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn rendered_file_keeps_options_and_replaces_nameservers() {
+        let current = "# Generated by dns-manager\nsearch lan\nnameserver 192.168.1.1\noptions edns0\n";
+        let servers: Vec<IpAddr> = vec!["1.1.1.1".parse().unwrap(), "2606:4700:4700::1111".parse().unwrap()];
+
+        assert_eq!(
+            render_resolv_conf(current, &servers),
+            "# Generated by dns-manager\nsearch lan\noptions edns0\nnameserver 1.1.1.1\nnameserver 2606:4700:4700::1111\n"
+        );
+    }
+
+    #[test]
+    fn set_and_reset_restore_the_original_file() {
+        let root = temp_root();
+        let backend = ResolvConfBackend::with_root(&root);
+        let path = root.join("etc/resolv.conf");
+        let original = "search lan\nnameserver 192.168.1.1\n";
+        fs::write(&path, original).unwrap();
+        assert_eq!(backend.get_current_dns().unwrap(), "Автопилот (DHCP): 192.168.1.1");
+        let plan = backend.plan_set_dns(&AdapterSelection::AllUp, &servers(&["1.1.1.1"])).unwrap();
+        assert!(plan.adapters[0].from_dhcp, "чужой файл откатывается восстановлением копии");
+
+        backend.set_dns(&AdapterSelection::AllUp, &servers(&["1.1.1.1", "1.0.0.1"])).unwrap();
+        assert_eq!(backend.get_current_dns().unwrap(), "1.1.1.1, 1.0.0.1");
+        // Повторная установка не затирает бэкап уже измененным файлом
+        backend.set_dns(&AdapterSelection::AllUp, &servers(&["8.8.8.8"])).unwrap();
+        assert_eq!(fs::read_to_string(root.join("etc/resolv.conf.dns-manager.bak")).unwrap(), original);
+
+        let reset = backend.plan_reset_dns(&AdapterSelection::AllUp).unwrap();
+        let reapply = backend.plan_set_dns(&AdapterSelection::AllUp, &servers(&["1.1.1.1"])).unwrap();
+        assert!(!reapply.adapters[0].from_dhcp, "свои серверы откатываются установкой");
+        assert!(reset.adapters[0].commands[0].starts_with("mv "));
+        backend.reset_dns(&AdapterSelection::AllUp).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        assert!(!backend.is_managed());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_stub_is_restored_as_a_link() {
+        let root = temp_root();
+        let backend = ResolvConfBackend::with_root(&root);
+        let path = root.join("etc/resolv.conf");
+        let stub = root.join("stub-resolv.conf");
+        fs::write(&stub, "nameserver 127.0.0.53\noptions edns0 trust-ad\n").unwrap();
+        std::os::unix::fs::symlink(&stub, &path).unwrap();
+
+        backend.set_dns(&AdapterSelection::AllUp, &servers(&["9.9.9.9"])).unwrap();
+        // Пишем свой файл вместо ссылки, сам stub не меняется
+        assert!(!fs::symlink_metadata(&path).unwrap().file_type().is_symlink());
+        assert!(fs::read_to_string(&path).unwrap().contains("options edns0 trust-ad\nnameserver 9.9.9.9\n"));
+        assert_eq!(fs::read_to_string(&stub).unwrap(), "nameserver 127.0.0.53\noptions edns0 trust-ad\n");
+
+        backend.reset_dns(&AdapterSelection::AllUp).unwrap();
+        assert_eq!(fs::read_link(&path).unwrap(), stub);
+        assert!(!backend.link_backup_path().exists());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn empty_server_list_is_rejected_without_touching_the_file() {
+        let root = temp_root();
+        let backend = ResolvConfBackend::with_root(&root);
+        let path = root.join("etc/resolv.conf");
+        fs::write(&path, "nameserver 192.168.1.1\n").unwrap();
+
+        for servers in [servers(&[]), servers(&["", "  "])] {
+            assert!(matches!(backend.set_dns(&AdapterSelection::AllUp, &servers), Err(DnsError::InvalidAddress(_))));
+            assert!(matches!(backend.plan_set_dns(&AdapterSelection::AllUp, &servers), Err(DnsError::InvalidAddress(_))));
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "nameserver 192.168.1.1\n");
+        assert!(!backend.is_managed());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,349 @@
+// Бэкенд DNS для Linux через systemd-resolved (org.freedesktop.resolve1 по D-Bus)
+// Соединение передается снаружи, чтобы бэкенд можно было проверить на мок-сервисе в приватной шине
+
+use std::net::IpAddr;
+
+use zbus::blocking::{fdo::DBusProxy, Connection};
+use zbus::dbus_proxy;
+use zbus::names::BusName;
+
+use super::{format_dns_servers, join_servers, missing_changes, parse_servers, DnsBackend, DNS_CACHE};
+use crate::dns::error::{AdapterChange, ApplyReport, DnsError};
+use crate::dns::plan::{AdapterPlan, ChangePlan};
+use crate::network::adapters::AdapterSelection;
+use crate::network::netlink;
+
+const RESOLVE1_SERVICE: &str = "org.freedesktop.resolve1";
+
+const AF_INET: i32 = 2;
+const AF_INET6: i32 = 10;
+
+#[dbus_proxy(
+    interface = "org.freedesktop.resolve1.Manager",
+    default_service = "org.freedesktop.resolve1",
+    default_path = "/org/freedesktop/resolve1",
+    gen_async = false
+)]
+trait Manager {
+    #[dbus_proxy(name = "SetLinkDNS")]
+    fn set_link_dns(&self, ifindex: i32, addresses: &[(i32, Vec<u8>)]) -> zbus::Result<()>;
+
+    fn set_link_domains(&self, ifindex: i32, domains: &[(&str, bool)]) -> zbus::Result<()>;
+
+    fn revert_link(&self, ifindex: i32) -> zbus::Result<()>;
+
+    fn flush_caches(&self) -> zbus::Result<()>;
+
+    // Все серверы, известные резолверу: (ifindex, семейство, адрес)
+    #[dbus_proxy(property, name = "DNS")]
+    fn dns(&self) -> zbus::Result<Vec<(i32, i32, Vec<u8>)>>;
+
+    // Домены поиска и маршрутизации: (ifindex, домен, только для маршрутизации)
+    #[dbus_proxy(property)]
+    fn domains(&self) -> zbus::Result<Vec<(i32, String, bool)>>;
+}
+
+pub struct ResolvedBackend {
+    connection: Connection,
+}
+
+impl ResolvedBackend {
+    // Подключение к системной шине, если на ней есть systemd-resolved
+    pub fn system() -> Option<Self> {
+        Self::on_bus(Connection::system().ok()?)
+    }
+
+    // Бэкенд на уже открытой шине, если на ней есть systemd-resolved
+    pub fn on_bus(connection: Connection) -> Option<Self> {
+        let dbus = DBusProxy::new(&connection).ok()?;
+        let name = BusName::try_from(RESOLVE1_SERVICE).ok()?;
+        if dbus.name_has_owner(name).unwrap_or(false) {
+            Some(Self::with_connection(connection))
+        } else {
+            None
+        }
+    }
+
+    pub fn with_connection(connection: Connection) -> Self {
+        Self { connection }
+    }
+
+    fn manager(&self) -> Result<ManagerProxy<'_>, DnsError> {
+        Ok(ManagerProxy::new(&self.connection)?)
+    }
+
+    // Интерфейсы берутся из netlink и фильтруются по выбору пользователя
+    fn for_each_link(
+        &self,
+        selection: &AdapterSelection,
+        apply: impl Fn(i32) -> Result<String, DnsError>,
+    ) -> Result<ApplyReport, DnsError> {
+        let adapters = netlink::get_network_adapters().map_err(DnsError::BackendUnavailable)?;
+        let (selected, missing) = selection.resolve(&adapters);
+
+        let mut changes = missing_changes(missing);
+        for adapter in selected {
+            changes.push(AdapterChange {
+                adapter: adapter.name.clone(),
+                result: apply(adapter.index as i32),
+            });
+        }
+
+        ApplyReport::new(self.name(), changes).into_result()
+    }
+
+    // D-Bus вызовы показываем как эквивалентные команды resolvectl
+    fn plan_links(
+        &self,
+        selection: &AdapterSelection,
+        plan_for: impl Fn(&str, Vec<String>) -> AdapterPlan,
+    ) -> Result<ChangePlan, DnsError> {
+        let manager = self.manager()?;
+        let entries = manager.dns()?;
+        let domains = manager.domains()?;
+        let adapters = netlink::get_network_adapters().map_err(DnsError::BackendUnavailable)?;
+        let (selected, missing) = selection.resolve(&adapters);
+
+        let plans = selected
+            .iter()
+            .map(|adapter| {
+                let current = link_servers(&entries, adapter.index);
+                // Серверы, заданные через D-Bus (нами или VPN вроде wg-quick), идут вместе с маршрутом ".".
+                // Без него они пришли от networkd или DHCP, и откат вернет их через RevertLink
+                let from_dhcp = !domains
+                    .iter()
+                    .any(|(index, domain, routing_only)| *index == adapter.index as i32 && domain == "." && *routing_only);
+                plan_for(&adapter.name, current).with_dhcp_source(from_dhcp)
+            })
+            .collect();
+        Ok(ChangePlan::new(self.name(), plans, missing))
+    }
+}
+
+impl DnsBackend for ResolvedBackend {
+    fn name(&self) -> &'static str {
+        "systemd-resolved"
+    }
+
+    fn get_current_dns(&self) -> Result<String, DnsError> {
+        let entries = self.manager()?.dns()?;
+
+        let dns_servers: Vec<String> = entries
+            .into_iter()
+            .filter_map(|(_, family, address)| decode_address(family, &address))
+            .map(|ip| ip.to_string())
+            .collect();
+
+        if dns_servers.is_empty() {
+            Ok("Автопилот (DHCP): адреса не получены".to_string())
+        } else {
+            Ok(format_dns_servers(&dns_servers))
+        }
+    }
+
+    // Серверы линков вместе с полученными от DHCP; глобальные (ifindex 0) ни к одному адаптеру не относятся
+    fn adapter_dns(&self) -> Result<Vec<(String, Vec<String>)>, DnsError> {
+        let entries = self.manager()?.dns()?;
+        let adapters = netlink::get_network_adapters().map_err(DnsError::BackendUnavailable)?;
+
+        Ok(adapters
+            .into_iter()
+            .filter_map(|adapter| {
+                let servers = link_servers(&entries, adapter.index);
+                (!servers.is_empty()).then_some((adapter.name, servers))
+            })
+            .collect())
+    }
+
+    fn set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ApplyReport, DnsError> {
+        let servers = parse_servers(servers)?;
+        let addresses: Vec<(i32, Vec<u8>)> = servers.iter().map(|ip| encode_address(*ip)).collect();
+        let change = format!("DNS: {}", join_servers(&servers));
+
+        let manager = self.manager()?;
+        self.for_each_link(selection, |index| {
+            manager.set_link_dns(index, &addresses)?;
+            // Домен "." только для маршрутизации ("~." в resolvectl) делает интерфейс
+            // маршрутом по умолчанию для всех DNS запросов
+            manager.set_link_domains(index, &[(".", true)])?;
+            Ok(change.clone())
+        })
+    }
+
+    fn reset_dns(&self, selection: &AdapterSelection) -> Result<ApplyReport, DnsError> {
+        let manager = self.manager()?;
+        self.for_each_link(selection, |index| {
+            manager.revert_link(index)?;
+            Ok("DNS: DHCP".to_string())
+        })
+    }
+
+    fn plan_set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ChangePlan, DnsError> {
+        let servers = parse_servers(servers)?;
+        self.plan_links(selection, |adapter, current| {
+            let commands = vec![
+                format!("resolvectl dns {} {}", adapter, join_servers(&servers).replace(", ", " ")),
+                format!("resolvectl domain {} '~.'", adapter),
+            ];
+            AdapterPlan::set(adapter, current, &servers, commands)
+        })
+    }
+
+    fn plan_reset_dns(&self, selection: &AdapterSelection) -> Result<ChangePlan, DnsError> {
+        self.plan_links(selection, |adapter, current| {
+            AdapterPlan::reset(adapter, current, vec![format!("resolvectl revert {}", adapter)])
+        })
+    }
+
+    fn flush_cache(&self) -> Result<ApplyReport, DnsError> {
+        self.manager()?.flush_caches()?;
+        Ok(ApplyReport::new(self.name(), vec![AdapterChange::ok(DNS_CACHE, "очищен")]))
+    }
+}
+
+fn encode_address(ip: IpAddr) -> (i32, Vec<u8>) {
+    match ip {
+        IpAddr::V4(v4) => (AF_INET, v4.octets().to_vec()),
+        IpAddr::V6(v6) => (AF_INET6, v6.octets().to_vec()),
+    }
+}
+
+// Серверы одного линка из свойства DNS
+fn link_servers(entries: &[(i32, i32, Vec<u8>)], index: u32) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|(link, _, _)| *link == index as i32)
+        .filter_map(|(_, family, address)| decode_address(*family, address))
+        .map(|ip| ip.to_string())
+        .collect()
+}
+
+fn decode_address(family: i32, bytes: &[u8]) -> Option<IpAddr> {
+    match family {
+        AF_INET => <[u8; 4]>::try_from(bytes).ok().map(IpAddr::from),
+        AF_INET6 => <[u8; 16]>::try_from(bytes).ok().map(IpAddr::from),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use zbus::dbus_interface;
+
+    use super::*;
+    use crate::dns::backend::test_bus::PrivateBus;
+
+    // Мок org.freedesktop.resolve1: записывает вызовы, отдает заранее заданные серверы
+    // и запоминает домены, как настоящий resolved до RevertLink
+    #[derive(Clone, Default)]
+    struct MockResolve {
+        calls: Arc<Mutex<Vec<String>>>,
+        dns: Vec<(i32, i32, Vec<u8>)>,
+        domains: Arc<Mutex<Vec<(i32, String, bool)>>>,
+    }
+
+    impl MockResolve {
+        fn record(&self, call: String) {
+            self.calls.lock().unwrap().push(call);
+        }
+    }
+
+    #[dbus_interface(name = "org.freedesktop.resolve1.Manager")]
+    impl MockResolve {
+        #[dbus_interface(name = "SetLinkDNS")]
+        fn set_link_dns(&self, ifindex: i32, addresses: Vec<(i32, Vec<u8>)>) {
+            let servers: Vec<String> = addresses
+                .iter()
+                .filter_map(|(family, address)| decode_address(*family, address))
+                .map(|ip| ip.to_string())
+                .collect();
+            self.record(format!("SetLinkDNS {} {}", ifindex, servers.join(" ")));
+        }
+
+        fn set_link_domains(&self, ifindex: i32, domains: Vec<(String, bool)>) {
+            let labels: Vec<String> = domains.iter().map(|(domain, routing)| format!("{}:{}", domain, routing)).collect();
+            self.record(format!("SetLinkDomains {} {}", ifindex, labels.join(" ")));
+            let mut stored = self.domains.lock().unwrap();
+            stored.retain(|(index, _, _)| *index != ifindex);
+            stored.extend(domains.into_iter().map(|(domain, routing)| (ifindex, domain, routing)));
+        }
+
+        fn revert_link(&self, ifindex: i32) {
+            self.record(format!("RevertLink {}", ifindex));
+            self.domains.lock().unwrap().retain(|(index, _, _)| *index != ifindex);
+        }
+
+        fn flush_caches(&self) {
+            self.record("FlushCaches".to_string());
+        }
+
+        #[dbus_interface(property, name = "DNS")]
+        fn dns(&self) -> Vec<(i32, i32, Vec<u8>)> {
+            self.dns.clone()
+        }
+
+        #[dbus_interface(property)]
+        fn domains(&self) -> Vec<(i32, String, bool)> {
+            self.domains.lock().unwrap().clone()
+        }
+    }
+
+    fn loopback_index() -> i32 {
+        let adapters = netlink::get_network_adapters().unwrap();
+        adapters.iter().find(|adapter| adapter.name == "lo").unwrap().index as i32
+    }
+
+    #[test]
+    fn backend_drives_resolve1_on_a_private_bus() {
+        let Some(bus) = PrivateBus::start() else {
+            // Без dbus-daemon проверять не на чем
+            return;
+        };
+        assert!(ResolvedBackend::on_bus(bus.connect()).is_none(), "resolve1 на шине еще нет");
+
+        let lo = loopback_index();
+        let global_v6 = encode_address("2001:db8::53".parse().unwrap()).1;
+        let mock = MockResolve {
+            dns: vec![(lo, AF_INET, vec![192, 168, 1, 1]), (0, AF_INET6, global_v6)],
+            ..Default::default()
+        };
+        let calls = mock.calls.clone();
+        let _service = bus.serve(RESOLVE1_SERVICE, "/org/freedesktop/resolve1", mock);
+        let backend = ResolvedBackend::on_bus(bus.connect()).expect("resolve1 зарегистрирован");
+        let selection = AdapterSelection::Names(vec!["lo".to_string()]);
+
+        assert_eq!(backend.get_current_dns().unwrap(), "Автопилот (DHCP): 192.168.1.1, 2001:db8::53");
+        // Глобальный сервер не приписывается ни одному адаптеру
+        assert_eq!(backend.adapter_dns().unwrap(), vec![("lo".to_string(), vec!["192.168.1.1".to_string()])]);
+        let plan = backend.plan_set_dns(&selection, &["1.1.1.1".to_string(), "2606:4700:4700::1111".to_string()]).unwrap();
+        assert_eq!(plan.adapters[0].current, vec!["192.168.1.1"]);
+        assert!(plan.adapters[0].from_dhcp, "без маршрута \".\" серверы пришли от сети");
+        assert_eq!(plan.adapters[0].commands[0], "resolvectl dns lo 1.1.1.1 2606:4700:4700::1111");
+        assert!(calls.lock().unwrap().is_empty(), "план ничего не меняет");
+
+        // Линк без DNS с маршрутом "." забрал бы себе все запросы системы
+        for servers in [Vec::new(), vec!["".to_string(), "  ".to_string()]] {
+            assert!(matches!(backend.set_dns(&selection, &servers), Err(DnsError::InvalidAddress(_))));
+            assert!(matches!(backend.plan_set_dns(&selection, &servers), Err(DnsError::InvalidAddress(_))));
+        }
+        assert!(calls.lock().unwrap().is_empty(), "пустой список отклонен до вызовов resolve1");
+
+        backend.set_dns(&selection, &["1.1.1.1".to_string(), "2606:4700:4700::1111".to_string()]).unwrap();
+        let plan = backend.plan_set_dns(&selection, &["9.9.9.9".to_string()]).unwrap();
+        assert!(!plan.adapters[0].from_dhcp, "серверы с маршрутом \".\" заданы вручную");
+        backend.reset_dns(&selection).unwrap();
+        backend.flush_cache().unwrap();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                format!("SetLinkDNS {} 1.1.1.1 2606:4700:4700::1111", lo),
+                format!("SetLinkDomains {} .:true", lo),
+                format!("RevertLink {}", lo),
+                "FlushCaches".to_string(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,56 @@
+// Приватная шина D-Bus для тестов бэкендов: свой dbus-daemon на время теста,
+// чтобы мок-сервисы не пересекались с настоящими resolved и NetworkManager
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+
+use zbus::blocking::{Connection, ConnectionBuilder};
+
+pub struct PrivateBus {
+    daemon: Child,
+    address: String,
+}
+
+impl PrivateBus {
+    // None - dbus-daemon в системе нет, тест с шиной пропускается
+    pub fn start() -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+
+        // Адрес шины - первая строка вывода, после нее демон готов принимать подключения
+        let mut address = String::new();
+        let read = daemon.stdout.take().map(|stdout| BufReader::new(stdout).read_line(&mut address));
+        let bus = Self { daemon, address: address.trim().to_string() };
+        match read {
+            Some(Ok(_)) if !bus.address.is_empty() => Some(bus),
+            _ => None,
+        }
+    }
+
+    pub fn connect(&self) -> Connection {
+        ConnectionBuilder::address(self.address.as_str()).unwrap().build().unwrap()
+    }
+
+    // Подключение мок-сервиса: занимает имя и отвечает на вызовы объекта по пути
+    pub fn serve<I: zbus::Interface>(&self, name: &str, path: &str, iface: I) -> Connection {
+        ConnectionBuilder::address(self.address.as_str())
+            .unwrap()
+            .name(name.to_string())
+            .unwrap()
+            .serve_at(path.to_string(), iface)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
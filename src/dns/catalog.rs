@@ -0,0 +1,458 @@
+// Каталог провайдеров в папке настроек пользователя: providers.toml или providers.json.
+// Если файла нет, он создается со встроенными провайдерами - их можно править и дополнять
+// своими резолверами без пересборки. Файл перечитывается, когда меняется на диске
+
+use std::collections::HashSet;
+use std::fs;
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use super::doh::DohEndpoint;
+use super::providers::{get_dns_providers, DNSProvider};
+use super::stamp::decode_hex;
+use super::tls;
+
+// Версия схемы файла; поднимается при несовместимых изменениях формата
+pub const CATALOG_VERSION: u32 = 1;
+const CATALOG_DIR: &str = "dns-manager";
+const TOML_FILE: &str = "providers.toml";
+const JSON_FILE: &str = "providers.json";
+// Как часто проверять, не изменился ли файл
+const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CatalogFile {
+    // Option - чтобы на файл без версии ругаться понятно, а не "missing field"
+    version: Option<u32>,
+    #[serde(default)]
+    providers: Vec<DNSProvider>,
+}
+
+pub struct ProviderCatalog {
+    // None - папки настроек нет, работаем на встроенных провайдерах
+    pub path: Option<PathBuf>,
+    pub providers: Vec<DNSProvider>,
+    // Почему файл не принят; провайдеры при этом остаются от последней удачной загрузки
+    pub error: Option<String>,
+    modified: Option<SystemTime>,
+    checked: Instant,
+}
+
+impl ProviderCatalog {
+    // Файл в папке настроек ОС (~/.config на Linux, %APPDATA% на Windows)
+    pub fn load() -> Self {
+        match dirs::config_dir() {
+            Some(dir) => Self::load_from(&dir.join(CATALOG_DIR)),
+            None => Self {
+                path: None,
+                providers: get_dns_providers(),
+                error: None,
+                modified: None,
+                checked: Instant::now(),
+            },
+        }
+    }
+
+    // TOML важнее JSON, если есть оба; без файлов создается TOML со встроенными провайдерами
+    pub fn load_from(dir: &Path) -> Self {
+        let json = dir.join(JSON_FILE);
+        let path = if json.exists() && !dir.join(TOML_FILE).exists() { json } else { dir.join(TOML_FILE) };
+
+        let mut catalog =
+            Self { path: Some(path.clone()), providers: get_dns_providers(), error: None, modified: None, checked: Instant::now() };
+        if !path.exists() {
+            if let Err(e) = write_defaults(&path) {
+                catalog.error = Some(format!("не удалось создать {}: {}", path.display(), e));
+                return catalog;
+            }
+        }
+        catalog.reload();
+        catalog
+    }
+
+    // true - файл перечитан (удачно или с ошибкой), список провайдеров мог измениться
+    pub fn reload_if_changed(&mut self) -> bool {
+        if self.checked.elapsed() < RELOAD_INTERVAL {
+            return false;
+        }
+        self.checked = Instant::now();
+
+        let Some(path) = &self.path else {
+            return false;
+        };
+        if modified(path) == self.modified {
+            return false;
+        }
+        self.reload();
+        true
+    }
+
+    // Добавляет провайдера или заменяет original (при редактировании) и сразу пишет файл
+    pub fn save_provider(&mut self, original: Option<&str>, provider: DNSProvider) -> Result<(), String> {
+        let mut providers = self.providers.clone();
+        match original.and_then(|name| providers.iter().position(|existing| existing.name == name)) {
+            Some(index) => providers[index] = provider,
+            None => providers.push(provider),
+        }
+        self.replace(providers)
+    }
+
+    pub fn remove_provider(&mut self, name: &str) -> Result<(), String> {
+        let mut providers = self.providers.clone();
+        providers.retain(|provider| provider.name != name);
+        if providers.is_empty() {
+            return Err("в каталоге должен остаться хотя бы один провайдер".to_string());
+        }
+        self.replace(providers)
+    }
+
+    // Свободное имя для копии: "Имя (копия)", "Имя (копия 2)", ...
+    pub fn copy_name(&self, name: &str) -> String {
+        let taken = |candidate: &str| self.providers.iter().any(|provider| provider.name.eq_ignore_ascii_case(candidate));
+        let mut candidate = format!("{} (копия)", name);
+        let mut number = 2;
+        while taken(&candidate) {
+            candidate = format!("{} (копия {})", name, number);
+            number += 1;
+        }
+        candidate
+    }
+
+    // Те же проверки, что при чтении файла; без папки настроек изменения живут до перезапуска.
+    // Пока файл не читается, он не перезаписывается - иначе пропадут ручные правки
+    fn replace(&mut self, providers: Vec<DNSProvider>) -> Result<(), String> {
+        if let Some(error) = &self.error {
+            return Err(format!("файл каталога не сохранен - сначала исправьте его вручную: {}", error));
+        }
+        validate_all(&providers)?;
+        if let Some(path) = &self.path {
+            write_catalog(path, &providers)?;
+            self.modified = modified(path);
+        }
+        self.providers = providers;
+        Ok(())
+    }
+
+    fn reload(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        self.modified = modified(path);
+
+        let result = fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| parse(path, &text));
+        match result {
+            Ok(providers) => {
+                self.providers = providers;
+                self.error = None;
+            }
+            Err(e) => self.error = Some(format!("{}: {}", path.display(), e)),
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn write_defaults(path: &Path) -> Result<(), String> {
+    write_catalog(path, &get_dns_providers())
+}
+
+// В том же формате, в каком файл читается
+fn write_catalog(path: &Path, providers: &[DNSProvider]) -> Result<(), String> {
+    let file = CatalogFile { version: Some(CATALOG_VERSION), providers: providers.to_vec() };
+    let text = if path.extension().is_some_and(|extension| extension == "json") {
+        serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?
+    } else {
+        toml::to_string_pretty(&file).map_err(|e| e.to_string())?
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    fs::write(path, text).map_err(|e| e.to_string())
+}
+
+// Формат по расширению; ошибки всех провайдеров собираются вместе, по одной на строку
+pub fn parse(path: &Path, text: &str) -> Result<Vec<DNSProvider>, String> {
+    let file: CatalogFile = if path.extension().is_some_and(|extension| extension == "json") {
+        serde_json::from_str(text).map_err(|e| format!("ошибка JSON: {}", e))?
+    } else {
+        toml::from_str(text).map_err(|e| format!("ошибка TOML: {}", e))?
+    };
+
+    match file.version {
+        None => return Err(format!("нет поля version (текущая версия схемы - {})", CATALOG_VERSION)),
+        Some(version) if version > CATALOG_VERSION => {
+            return Err(format!("версия схемы {} новее поддерживаемой {}", version, CATALOG_VERSION));
+        }
+        Some(0) => return Err("версия схемы начинается с 1".to_string()),
+        Some(_) => {}
+    }
+    validate_all(&file.providers)?;
+    Ok(file.providers)
+}
+
+fn validate_all(providers: &[DNSProvider]) -> Result<(), String> {
+    if providers.is_empty() {
+        return Err("в каталоге нет ни одного провайдера".to_string());
+    }
+
+    let mut names = HashSet::new();
+    let mut errors = Vec::new();
+    for (index, provider) in providers.iter().enumerate() {
+        let problems = validate(provider, &mut names);
+        if !problems.is_empty() {
+            errors.push(format!("провайдер {} ({}): {}", index + 1, provider.name, problems.join("; ")));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+fn validate(provider: &DNSProvider, names: &mut HashSet<String>) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if provider.name.trim().is_empty() {
+        problems.push("пустое имя".to_string());
+    } else if !names.insert(provider.name.to_lowercase()) {
+        problems.push("имя уже занято другим провайдером".to_string());
+    }
+    for (field, address) in [("primary", &provider.primary), ("secondary", &provider.secondary)] {
+        if address.parse::<IpAddr>().is_err() {
+            problems.push(format!("{} - не IP адрес: {:?}", field, address));
+        }
+    }
+    for (field, address) in [("primary_v6", &provider.primary_v6), ("secondary_v6", &provider.secondary_v6)] {
+        if let Some(address) = address.as_ref().filter(|address| address.parse::<Ipv6Addr>().is_err()) {
+            problems.push(format!("{} - не IPv6 адрес: {:?}", field, address));
+        }
+    }
+    if provider.primary_v6.is_none() && provider.secondary_v6.is_some() {
+        problems.push("secondary_v6 без primary_v6".to_string());
+    }
+    if let Some(url) = &provider.doh {
+        if let Err(e) = DohEndpoint::parse(url) {
+            problems.push(e);
+        }
+    }
+    for (field, name) in [("dot", &provider.dot), ("doq", &provider.doq)] {
+        if let Some(Err(e)) = name.as_deref().map(tls::server_name) {
+            problems.push(format!("{}: {}", field, e));
+        }
+    }
+    if let Some(dnscrypt) = &provider.dnscrypt {
+        if dnscrypt.provider_name.trim().is_empty() {
+            problems.push("dnscrypt: пустое provider_name".to_string());
+        }
+        match decode_hex(&dnscrypt.public_key) {
+            Ok(key) if key.len() == 32 => {}
+            Ok(key) => problems.push(format!("dnscrypt: public_key - {} байт вместо 32", key.len())),
+            Err(e) => problems.push(format!("dnscrypt: {}", e)),
+        }
+    }
+    for hash in &provider.cert_hashes {
+        if !decode_hex(hash).is_ok_and(|hash| hash.len() == 32) {
+            problems.push(format!("cert_hashes: не SHA-256 в hex: {:?}", hash));
+        }
+    }
+    if provider.encrypted_only && provider.doh.is_none() && provider.dnscrypt.is_none() {
+        problems.push("encrypted_only без doh и dnscrypt: к провайдеру не обратиться".to_string());
+    }
+    if let Some(url) = &provider.logging_policy {
+        let link = url.parse::<http::Uri>().ok().filter(|uri| matches!(uri.scheme_str(), Some("https" | "http")) && uri.host().is_some());
+        if link.is_none() {
+            problems.push(format!("logging_policy - не ссылка http(s): {:?}", url));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::wire::random_id;
+    use std::env;
+
+    fn temp_dir() -> PathBuf {
+        let dir = env::temp_dir().join(format!("dns-manager-catalog-{}-{}", std::process::id(), random_id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn missing_file_is_created_with_builtin_providers() {
+        let dir = temp_dir();
+        let catalog = ProviderCatalog::load_from(&dir);
+
+        assert_eq!(catalog.error, None);
+        assert_eq!(catalog.providers, get_dns_providers());
+        let text = fs::read_to_string(dir.join(TOML_FILE)).unwrap();
+        assert!(text.starts_with("version = 1"), "{}", text);
+        assert_eq!(parse(Path::new(TOML_FILE), &text).unwrap(), get_dns_providers());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn json_catalog_is_accepted() {
+        let text = r#"{"version": 1, "providers": [{"name": "Офис", "primary": "10.0.0.53", "secondary": "10.0.1.53"}]}"#;
+        let providers = parse(Path::new(JSON_FILE), text).unwrap();
+        assert_eq!((providers[0].name.as_str(), providers[0].doh.as_deref()), ("Офис", None));
+    }
+
+    #[test]
+    fn every_bad_entry_is_reported() {
+        let text = r#"
+version = 1
+
+[[providers]]
+name = "Офис"
+primary = "10.0.0.53"
+secondary = "10.0.0.300"
+
+[[providers]]
+name = "офис"
+primary = "10.0.1.53"
+secondary = "10.0.1.54"
+doh = "http://doh.example/dns-query"
+
+[[providers]]
+name = "Дом"
+primary = "192.168.1.1"
+secondary = "192.168.1.1"
+primary_v6 = "192.168.1.1"
+
+[[providers]]
+name = "Крипт"
+primary = "10.0.2.53"
+secondary = "10.0.2.53"
+dnssec = true
+dnscrypt = { provider_name = "2.dnscrypt-cert.example", public_key = "abcd" }
+filtering = "ads"
+ecs = "off"
+logging_policy = "privacy.example"
+"#;
+        let error = parse(Path::new(TOML_FILE), text).unwrap_err();
+        let lines: Vec<&str> = error.lines().collect();
+        assert_eq!(lines.len(), 4, "{}", error);
+        assert_eq!(lines[0], "провайдер 1 (Офис): secondary - не IP адрес: \"10.0.0.300\"");
+        assert!(lines[1].starts_with("провайдер 2 (офис): имя уже занято"), "{}", lines[1]);
+        assert!(lines[1].contains("только по https"), "{}", lines[1]);
+        assert_eq!(lines[2], "провайдер 3 (Дом): primary_v6 - не IPv6 адрес: \"192.168.1.1\"");
+        assert_eq!(
+            lines[3],
+            "провайдер 4 (Крипт): dnscrypt: public_key - 2 байт вместо 32; logging_policy - не ссылка http(s): \"privacy.example\""
+        );
+        assert!(parse(Path::new(TOML_FILE), &text.replace("\"ads\"", "\"adult\"")).unwrap_err().starts_with("ошибка TOML"));
+
+        let unversioned = "[[providers]]\nname = \"x\"\nprimary = \"10.0.0.1\"\nsecondary = \"10.0.0.2\"";
+        assert!(parse(Path::new(TOML_FILE), unversioned).unwrap_err().starts_with("нет поля version"));
+        assert!(parse(Path::new(TOML_FILE), "version = 2").unwrap_err().contains("новее"));
+        assert!(parse(Path::new(TOML_FILE), "version = 1\nproviders = []\nextra = 1").unwrap_err().starts_with("ошибка TOML"));
+    }
+
+    #[test]
+    fn edits_are_validated_and_written_back() {
+        let dir = temp_dir();
+        let mut catalog = ProviderCatalog::load_from(&dir);
+        let office = DNSProvider {
+            name: "Офис".to_string(),
+            primary: "10.0.0.53".to_string(),
+            secondary: "10.0.1.53".to_string(),
+            description: String::new(),
+            doh: None,
+            dot: None,
+            doq: None,
+            primary_v6: Some("fd00::53".to_string()),
+            secondary_v6: None,
+            dnssec: false,
+            no_logs: false,
+            dnscrypt: None,
+            cert_hashes: Vec::new(),
+            encrypted_only: false,
+            filtering: None,
+            ecs: None,
+            logging_policy: None,
+        };
+
+        catalog.save_provider(None, office.clone()).unwrap();
+        let copy = DNSProvider { name: catalog.copy_name("Офис"), ..office.clone() };
+        assert_eq!(copy.name, "Офис (копия)");
+        catalog.save_provider(None, copy).unwrap();
+        assert_eq!(catalog.copy_name("Офис"), "Офис (копия 2)");
+
+        // Переименование в занятое имя и кривой адрес не сохраняются
+        let clash = DNSProvider { name: "Google".to_string(), ..office.clone() };
+        assert!(catalog.save_provider(Some("Офис"), clash).unwrap_err().contains("имя уже занято"));
+        let bad = DNSProvider { secondary: "10.0.1".to_string(), ..office.clone() };
+        assert!(catalog.save_provider(Some("Офис"), bad).unwrap_err().contains("secondary - не IP адрес"));
+        let unreachable = DNSProvider { encrypted_only: true, ..office.clone() };
+        assert!(catalog.save_provider(Some("Офис"), unreachable).unwrap_err().contains("encrypted_only без doh"));
+
+        let renamed = DNSProvider { name: "Офис-2".to_string(), ..office };
+        catalog.save_provider(Some("Офис"), renamed).unwrap();
+        catalog.remove_provider("Cloudflare").unwrap();
+
+        let saved = parse(&dir.join(TOML_FILE), &fs::read_to_string(dir.join(TOML_FILE)).unwrap()).unwrap();
+        assert_eq!(saved, catalog.providers);
+        let names: Vec<&str> = saved.iter().map(|provider| provider.name.as_str()).collect();
+        assert_eq!(names.len(), get_dns_providers().len() + 1);
+        assert!(names.contains(&"Офис-2") && names.contains(&"Офис (копия)") && !names.contains(&"Cloudflare"));
+        // Своя запись не считается внешним изменением файла
+        catalog.checked -= RELOAD_INTERVAL;
+        assert!(!catalog.reload_if_changed());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn broken_file_is_not_overwritten_by_edits() {
+        let dir = temp_dir();
+        let path = dir.join(TOML_FILE);
+        let broken = "version = 1\n[[providers]]\nname = \"A\"\nprimary = \"10.0.0.1\"\n";
+        fs::write(&path, broken).unwrap();
+        let mut catalog = ProviderCatalog::load_from(&dir);
+        assert!(catalog.error.is_some());
+
+        let office = DNSProvider { name: "Офис".to_string(), ..get_dns_providers().remove(0) };
+        let error = catalog.save_provider(None, office).unwrap_err();
+        assert!(error.contains("сначала исправьте его вручную"), "{}", error);
+        assert!(catalog.remove_provider("Google").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), broken);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn changed_file_is_reloaded_and_bad_edit_keeps_providers() {
+        let dir = temp_dir();
+        let path = dir.join(TOML_FILE);
+        fs::write(&path, "version = 1\n[[providers]]\nname = \"A\"\nprimary = \"10.0.0.1\"\nsecondary = \"10.0.0.2\"\n").unwrap();
+        let mut catalog = ProviderCatalog::load_from(&dir);
+        assert_eq!(catalog.providers.len(), 1);
+
+        // Время изменения файла может не отличаться в пределах одного тика ФС
+        let file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        fs::write(&path, "version = 1\n[[providers]]\nname = \"A\"\nprimary = \"bad\"\nsecondary = \"10.0.0.2\"\n").unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+        catalog.checked -= RELOAD_INTERVAL;
+
+        assert!(catalog.reload_if_changed());
+        assert!(catalog.error.as_deref().unwrap().contains("primary - не IP адрес"));
+        assert_eq!(catalog.providers[0].primary, "10.0.0.1");
+        assert!(!catalog.reload_if_changed());
+
+        // Правка из приложения не затирает неудачную ручную правку
+        let copy = DNSProvider { name: catalog.copy_name("A"), ..catalog.providers[0].clone() };
+        assert!(catalog.save_provider(None, copy).is_err());
+        assert!(fs::read_to_string(&path).unwrap().contains("primary = \"bad\""));
+        assert_eq!(catalog.providers.len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
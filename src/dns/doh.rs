@@ -0,0 +1,372 @@
+// DNS-over-HTTPS (RFC 8484): запросы GET и POST по одному HTTP/2 соединению.
+// Установку соединения (TCP + TLS) меряем отдельно от запросов по уже открытому
+// соединению - в работе браузер и ОС держат его открытым, и важна именно вторая цифра
+
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use bytes::Bytes;
+use h2::client::SendRequest;
+use http::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Method, Request, StatusCode, Uri};
+use rustls::ClientConfig;
+use tokio::task::JoinHandle;
+
+use super::speed_test::{millis, warm_question, BenchmarkConfig, LatencyStats, QueryOutcome};
+use super::tls::{self, ConnectionSetup};
+use super::wire::{build_query, RecordType};
+
+const DNS_MESSAGE: &str = "application/dns-message";
+const HTTPS_PORT: u16 = 443;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DohMethod {
+    // Запрос в base64url в параметре dns - такие ответы кэшируются по HTTP
+    Get,
+    // Запрос в теле как есть
+    Post,
+}
+
+impl DohMethod {
+    pub const ALL: [DohMethod; 2] = [DohMethod::Get, DohMethod::Post];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DohMethod::Get => "GET",
+            DohMethod::Post => "POST",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DohEndpoint {
+    host: String,
+    port: u16,
+    // Путь вместе с фиксированными параметрами шаблона, если они есть
+    path: String,
+}
+
+impl DohEndpoint {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let uri: Uri = url.parse().map_err(|e| format!("некорректный адрес DoH {}: {}", url, e))?;
+        if uri.scheme_str() != Some("https") {
+            return Err(format!("DoH работает только по https: {}", url));
+        }
+        let host = uri.host().ok_or_else(|| format!("в адресе DoH нет сервера: {}", url))?;
+
+        Ok(Self {
+            host: host.trim_start_matches('[').trim_end_matches(']').to_string(),
+            port: uri.port_u16().unwrap_or(HTTPS_PORT),
+            path: uri.path_and_query().map(|path| path.as_str().to_string()).unwrap_or_else(|| "/".to_string()),
+        })
+    }
+
+    fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    fn uri(&self, method: DohMethod, message: &[u8]) -> String {
+        let base = format!("https://{}{}", self.authority(), self.path);
+        match method {
+            DohMethod::Get => {
+                let separator = if self.path.contains('?') { '&' } else { '?' };
+                format!("{}{}dns={}", base, separator, URL_SAFE_NO_PAD.encode(message))
+            }
+            DohMethod::Post => base,
+        }
+    }
+}
+
+// Открытое HTTP/2 соединение: все запросы идут по нему отдельными потоками
+pub struct DohConnection {
+    endpoint: DohEndpoint,
+    sender: SendRequest<Bytes>,
+    pub setup: ConnectionSetup,
+    driver: JoinHandle<()>,
+}
+
+impl DohConnection {
+    pub async fn connect(endpoint: &DohEndpoint, tls: Arc<ClientConfig>) -> Result<Self, String> {
+        // Адрес сервера DoH узнаем заранее - поиск имени не входит в установку соединения
+        let address = tokio::net::lookup_host((endpoint.host.as_str(), endpoint.port))
+            .await
+            .map_err(|e| format!("{}: {}", endpoint.host, e))?
+            .next()
+            .ok_or_else(|| format!("{}: адрес не найден", endpoint.host))?;
+
+        let (stream, setup) = tls::connect(address, &endpoint.host, tls).await?;
+        if stream.get_ref().1.alpn_protocol() != Some(b"h2") {
+            return Err(format!("{} не согласовал HTTP/2", endpoint.host));
+        }
+        let (sender, connection) = h2::client::handshake(stream).await.map_err(|e| format!("HTTP/2: {}", e))?;
+
+        let driver = tokio::spawn(async move {
+            let _ = connection.await;
+        });
+
+        Ok(Self { endpoint: endpoint.clone(), sender, setup, driver })
+    }
+
+    pub async fn query(&mut self, method: DohMethod, name: &str, record_type: RecordType, timeout: Duration) -> QueryOutcome {
+        // RFC 8484 советует ID 0, чтобы одинаковые запросы попадали в HTTP кэш
+        let message = build_query(0, name, record_type);
+        let started = Instant::now();
+
+        match tokio::time::timeout(timeout, self.exchange(method, message)).await {
+            Err(_) => QueryOutcome::Timeout,
+            Ok(Err(e)) => QueryOutcome::Failed(e),
+            Ok(Ok(body)) => QueryOutcome::from_message(&body, 0, name, record_type, started.elapsed()),
+        }
+    }
+
+    async fn exchange(&mut self, method: DohMethod, message: Vec<u8>) -> Result<Vec<u8>, String> {
+        let mut sender = self.sender.clone().ready().await.map_err(|e| format!("HTTP/2: {}", e))?;
+
+        let builder = Request::builder().uri(self.endpoint.uri(method, &message)).header(ACCEPT, DNS_MESSAGE);
+        let request = match method {
+            DohMethod::Get => builder.method(Method::GET),
+            DohMethod::Post => {
+                builder.method(Method::POST).header(CONTENT_TYPE, DNS_MESSAGE).header(CONTENT_LENGTH, message.len())
+            }
+        }
+        .body(())
+        .map_err(|e| e.to_string())?;
+
+        let (response, mut body_stream) =
+            sender.send_request(request, method == DohMethod::Get).map_err(|e| format!("HTTP/2: {}", e))?;
+        if method == DohMethod::Post {
+            body_stream.send_data(Bytes::from(message), true).map_err(|e| format!("HTTP/2: {}", e))?;
+        }
+
+        let response = response.await.map_err(|e| format!("HTTP/2: {}", e))?;
+        if response.status() != StatusCode::OK {
+            return Err(format!("HTTP {}", response.status()));
+        }
+
+        let mut body = response.into_body();
+        let mut data = Vec::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk.map_err(|e| format!("HTTP/2: {}", e))?;
+            let _ = body.flow_control().release_capacity(chunk.len());
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data)
+    }
+}
+
+impl Drop for DohConnection {
+    fn drop(&mut self) {
+        self.driver.abort();
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DohResult {
+    pub url: String,
+    // None - соединение установить не удалось
+    pub setup: Option<ConnectionSetup>,
+    pub get: LatencyStats,
+    pub post: LatencyStats,
+    pub error: Option<String>,
+}
+
+impl DohResult {
+    pub fn stats(&self, method: DohMethod) -> &LatencyStats {
+        match method {
+            DohMethod::Get => &self.get,
+            DohMethod::Post => &self.post,
+        }
+    }
+}
+
+// Корневые сертификаты и ALPN h2 для настоящих серверов; собираем один раз
+pub fn web_tls() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG.get_or_init(|| tls::client_config(tls::web_roots(), &[b"h2"])).clone()
+}
+
+// Одно соединение на весь замер: по очереди GET и POST к популярным именам
+pub async fn measure(url: &str, config: &BenchmarkConfig, tls: Arc<ClientConfig>) -> DohResult {
+    let mut result = DohResult { url: url.to_string(), ..Default::default() };
+
+    let endpoint = match DohEndpoint::parse(url) {
+        Ok(endpoint) => endpoint,
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+    let mut connection = match tokio::time::timeout(config.timeout, DohConnection::connect(&endpoint, tls)).await {
+        Ok(Ok(connection)) => connection,
+        Ok(Err(e)) => {
+            result.error = Some(e);
+            return result;
+        }
+        Err(_) => {
+            result.error = Some(format!("соединение не установлено за {:?}", config.timeout));
+            return result;
+        }
+    };
+    result.setup = Some(connection.setup.clone());
+
+    let mut latencies = [Vec::new(), Vec::new()];
+    let mut timeouts = [0, 0];
+    for index in 0..config.warmup + config.samples {
+        let (name, record_type) = warm_question(index);
+        let measured = index >= config.warmup;
+
+        for (slot, method) in DohMethod::ALL.into_iter().enumerate() {
+            match connection.query(method, name, record_type, config.timeout).await {
+                QueryOutcome::Answered { latency, .. } => {
+                    if measured {
+                        latencies[slot].push(millis(latency));
+                    }
+                }
+                QueryOutcome::Invalid { reason, .. } => {
+                    result.error = Some(format!("{} {} {}: {}", method.label(), record_type.label(), name, reason));
+                }
+                QueryOutcome::Timeout => {
+                    timeouts[slot] += measured as usize;
+                    result.error = Some(format!("{} {} {}: нет ответа", method.label(), record_type.label(), name));
+                }
+                QueryOutcome::Failed(e) => result.error = Some(format!("{} {}: {}", method.label(), name, e)),
+            }
+        }
+    }
+
+    result.get = LatencyStats::from_samples(&latencies[0], config.samples, timeouts[0]);
+    result.post = LatencyStats::from_samples(&latencies[1], config.samples, timeouts[1]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::test_server::{block_on, spawn_server};
+    use crate::dns::tls::tests::self_signed;
+    use crate::dns::wire::tests::answer_for;
+    use crate::dns::wire::RCODE_NOERROR;
+    use std::net::{IpAddr, SocketAddr};
+    use std::sync::Mutex;
+
+    const ANSWER: &str = "192.0.2.7";
+
+    // Что увидел локальный сервер DoH
+    #[derive(Default)]
+    struct Seen {
+        connections: usize,
+        methods: Vec<Method>,
+    }
+
+    // Локальный сервер DoH с самоподписанным сертификатом на localhost
+    async fn spawn_doh_server() -> (SocketAddr, Arc<ClientConfig>, Arc<Mutex<Seen>>) {
+        let (server_config, client_config) = self_signed("localhost", &[b"h2"]);
+        let seen = Arc::new(Mutex::new(Seen::default()));
+
+        let server_seen = seen.clone();
+        let address = spawn_server(Some(server_config), move |_, stream| {
+            server_seen.lock().unwrap().connections += 1;
+            let seen = server_seen.clone();
+            async move {
+                let Ok(mut connection) = h2::server::handshake(stream).await else {
+                    return;
+                };
+                while let Some(Ok((request, mut respond))) = connection.accept().await {
+                    // Тело POST приходит, только пока соединение опрашивается - отвечаем в отдельной задаче
+                    let seen = seen.clone();
+                    tokio::spawn(async move {
+                        let (parts, mut body) = request.into_parts();
+                        seen.lock().unwrap().methods.push(parts.method.clone());
+
+                        let query = if parts.method == Method::GET {
+                            let encoded = parts.uri.query().unwrap().strip_prefix("dns=").unwrap().to_string();
+                            URL_SAFE_NO_PAD.decode(encoded).unwrap()
+                        } else {
+                            let mut data = Vec::new();
+                            while let Some(chunk) = body.data().await {
+                                data.extend_from_slice(&chunk.unwrap());
+                            }
+                            data
+                        };
+
+                        // Тип вопроса - предпоследние два байта запроса; на AAAA отвечаем пустым NOERROR
+                        let is_a = query[query.len() - 4..query.len() - 2] == RecordType::A.code().to_be_bytes();
+                        let addresses = if is_a { vec![ANSWER.parse().unwrap()] } else { Vec::new() };
+                        let answer = answer_for(&query, RCODE_NOERROR, &addresses);
+                        let response = http::Response::builder()
+                            .status(StatusCode::OK)
+                            .header(CONTENT_TYPE, DNS_MESSAGE)
+                            .body(())
+                            .unwrap();
+                        let mut send = respond.send_response(response, false).unwrap();
+                        send.send_data(Bytes::from(answer), true).unwrap();
+                    });
+                }
+            }
+        })
+        .await;
+
+        (address, client_config, seen)
+    }
+
+    #[test]
+    fn get_and_post_share_one_http2_connection() {
+        block_on(async {
+            let (address, tls, seen) = spawn_doh_server().await;
+            let endpoint = DohEndpoint::parse(&format!("https://localhost:{}/dns-query", address.port())).unwrap();
+            let mut connection = DohConnection::connect(&endpoint, tls).await.unwrap();
+            assert!(connection.setup.total() > 0.0);
+
+            let expected: IpAddr = ANSWER.parse().unwrap();
+            for method in [DohMethod::Get, DohMethod::Post, DohMethod::Get] {
+                match connection.query(method, "example.com", RecordType::A, Duration::from_secs(2)).await {
+                    QueryOutcome::Answered { answers, .. } => assert_eq!(answers, vec![expected]),
+                    other => panic!("{}: ожидался ответ, получено {:?}", method.label(), other),
+                }
+            }
+
+            let seen = seen.lock().unwrap();
+            assert_eq!(seen.connections, 1);
+            assert_eq!(seen.methods, vec![Method::GET, Method::POST, Method::GET]);
+        });
+    }
+
+    #[test]
+    fn measurement_reports_setup_and_both_methods() {
+        block_on(async {
+            let (address, tls, seen) = spawn_doh_server().await;
+            let config = BenchmarkConfig { samples: 3, warmup: 1, ..Default::default() };
+            let result = measure(&format!("https://localhost:{}/dns-query", address.port()), &config, tls).await;
+
+            assert_eq!(result.error, None);
+            assert!(result.setup.is_some());
+            assert_eq!((result.get.answered, result.post.answered), (3, 3));
+            // Прогрев тоже идет по тому же соединению
+            let seen = seen.lock().unwrap();
+            assert_eq!((seen.connections, seen.methods.len()), (1, 8));
+        });
+    }
+
+    #[test]
+    fn untrusted_certificate_is_rejected() {
+        block_on(async {
+            let (address, _, _) = spawn_doh_server().await;
+            let endpoint = DohEndpoint::parse(&format!("https://localhost:{}/dns-query", address.port())).unwrap();
+            let error = DohConnection::connect(&endpoint, web_tls()).await.err().unwrap();
+            assert!(error.starts_with("TLS"), "{}", error);
+        });
+    }
+
+    #[test]
+    fn get_template_keeps_existing_query_parameters() {
+        let endpoint = DohEndpoint::parse("https://[2606:4700::1111]/dns-query?ct=1").unwrap();
+        assert_eq!(endpoint.uri(DohMethod::Get, &[0xff]), "https://[2606:4700::1111]:443/dns-query?ct=1&dns=_w");
+        assert!(DohEndpoint::parse("http://dns.example/dns-query").is_err());
+    }
+}
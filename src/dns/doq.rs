@@ -0,0 +1,284 @@
+// DNS-over-QUIC (RFC 9250): каждый запрос - отдельный двунаправленный поток QUIC
+// с двухбайтовой длиной перед сообщением и ID 0. Меряем полное рукопожатие,
+// повторное подключение с 0-RTT (запрос уходит вместе с рукопожатием) и запросы
+// по уже открытому соединению
+
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::{Connection, Endpoint};
+
+use super::speed_test::{
+    millis, socket_address, warm_question, BenchmarkConfig, LatencyStats, QueryOutcome, WARM_DOMAINS,
+};
+use super::tls;
+use super::wire::{build_query, RecordType};
+
+pub const DOQ_PORT: u16 = 853;
+const DOQ_ALPN: &[u8] = b"doq";
+// Сообщение DNS не длиннее 64 КБ плюс два байта длины
+const MAX_RESPONSE: usize = 65537;
+// DOQ_NO_ERROR: соединение закрыто штатно
+const DOQ_NO_ERROR: u32 = 0;
+
+pub struct DoqClient {
+    endpoint: Endpoint,
+    address: SocketAddr,
+    auth_name: String,
+}
+
+impl DoqClient {
+    // Билеты сессии хранятся в клиентской конфигурации TLS: повторное подключение
+    // того же клиента может отправить запрос в 0-RTT
+    pub fn new(server: &str, auth_name: &str, tls: quinn::ClientConfig) -> Result<Self, String> {
+        let address = socket_address(server, DOQ_PORT)?;
+        let bind: SocketAddr = if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }
+            .parse()
+            .expect("адрес привязки корректен");
+        let mut endpoint = Endpoint::client(bind).map_err(|e| format!("QUIC: {}", e))?;
+        endpoint.set_default_client_config(tls);
+        Ok(Self { endpoint, address, auth_name: auth_name.to_string() })
+    }
+
+    // Полное рукопожатие; возвращает соединение и его длительность
+    pub async fn connect(&self) -> Result<(Connection, Duration), String> {
+        let started = Instant::now();
+        let connection = self
+            .endpoint
+            .connect(self.address, &self.auth_name)
+            .map_err(|e| format!("QUIC {}: {}", self.address, e))?
+            .await
+            .map_err(|e| format!("QUIC {}: {}", self.auth_name, e))?;
+        Ok((connection, started.elapsed()))
+    }
+
+    // Повторное подключение с запросом в 0-RTT. Возвращает время до первого ответа
+    // и принял ли сервер ранние данные; без билета сессии - обычное рукопожатие
+    pub async fn resume(&self, name: &str, record_type: RecordType, timeout: Duration) -> Result<(QueryOutcome, bool), String> {
+        let started = Instant::now();
+        let connecting =
+            self.endpoint.connect(self.address, &self.auth_name).map_err(|e| format!("QUIC {}: {}", self.address, e))?;
+
+        let (connection, accepted) = match connecting.into_0rtt() {
+            Ok((connection, accepted)) => (connection, Some(accepted)),
+            Err(connecting) => (connecting.await.map_err(|e| format!("QUIC {}: {}", self.auth_name, e))?, None),
+        };
+
+        let mut outcome = query_on(&connection, name, record_type, timeout, started).await;
+        let zero_rtt = match accepted {
+            Some(accepted) => accepted.await,
+            None => false,
+        };
+        // Сервер отверг ранние данные - повторяем запрос уже после рукопожатия
+        if !zero_rtt && matches!(outcome, QueryOutcome::Failed(_)) {
+            outcome = query_on(&connection, name, record_type, timeout, started).await;
+        }
+        close(&connection);
+        Ok((outcome, zero_rtt))
+    }
+}
+
+impl Drop for DoqClient {
+    fn drop(&mut self) {
+        self.endpoint.close(DOQ_NO_ERROR.into(), b"");
+    }
+}
+
+pub async fn query(connection: &Connection, name: &str, record_type: RecordType, timeout: Duration) -> QueryOutcome {
+    query_on(connection, name, record_type, timeout, Instant::now()).await
+}
+
+// started - откуда считать задержку: для 0-RTT это начало подключения
+async fn query_on(
+    connection: &Connection,
+    name: &str,
+    record_type: RecordType,
+    timeout: Duration,
+    started: Instant,
+) -> QueryOutcome {
+    match tokio::time::timeout(timeout, exchange(connection, build_query(0, name, record_type))).await {
+        Err(_) => QueryOutcome::Timeout,
+        Ok(Err(e)) => QueryOutcome::Failed(e),
+        Ok(Ok(frame)) => match frame.get(2..) {
+            Some(message) if frame.len() >= 2 && u16::from_be_bytes([frame[0], frame[1]]) as usize == message.len() => {
+                QueryOutcome::from_message(message, 0, name, record_type, started.elapsed())
+            }
+            _ => QueryOutcome::Invalid { latency: started.elapsed(), reason: "неверная длина ответа".to_string() },
+        },
+    }
+}
+
+async fn exchange(connection: &Connection, message: Vec<u8>) -> Result<Vec<u8>, String> {
+    let (mut send, mut receive) = connection.open_bi().await.map_err(|e| format!("QUIC: {}", e))?;
+
+    let mut frame = (message.len() as u16).to_be_bytes().to_vec();
+    frame.extend_from_slice(&message);
+    send.write_all(&frame).await.map_err(|e| format!("QUIC: {}", e))?;
+    // Клиент закрывает свою сторону потока сразу после запроса (RFC 9250, 4.2)
+    send.finish().map_err(|e| format!("QUIC: {}", e))?;
+
+    receive.read_to_end(MAX_RESPONSE).await.map_err(|e| format!("QUIC: {}", e))
+}
+
+fn close(connection: &Connection) {
+    connection.close(DOQ_NO_ERROR.into(), b"");
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DoqResult {
+    pub server: String,
+    pub auth_name: String,
+    // Полное рукопожатие QUIC + TLS 1.3, мс; None - соединение не установлено
+    pub handshake: Option<f64>,
+    // Время до первого ответа на новом соединении: рукопожатие и затем запрос
+    pub first_answer: Option<f64>,
+    // То же при повторном подключении с запросом в 0-RTT
+    pub resumed_first_answer: Option<f64>,
+    // Сервер принял ранние данные; false - запрос ушел после рукопожатия
+    pub zero_rtt: bool,
+    // Запросы по уже открытому соединению
+    pub queries: LatencyStats,
+    pub error: Option<String>,
+}
+
+// Корневые сертификаты, ALPN doq и разрешенные ранние данные
+pub fn web_tls() -> quinn::ClientConfig {
+    static CONFIG: OnceLock<quinn::ClientConfig> = OnceLock::new();
+    CONFIG.get_or_init(|| client_config(tls::web_roots())).clone()
+}
+
+pub fn client_config(roots: rustls::RootCertStore) -> quinn::ClientConfig {
+    let mut config = (*tls::client_config(roots, &[DOQ_ALPN])).clone();
+    config.enable_early_data = true;
+    let crypto = QuicClientConfig::try_from(config).expect("ring поддерживает набор шифров QUIC");
+    quinn::ClientConfig::new(Arc::new(crypto))
+}
+
+pub async fn measure(server: &str, auth_name: &str, config: &BenchmarkConfig, tls: quinn::ClientConfig) -> DoqResult {
+    let mut result = DoqResult { server: server.to_string(), auth_name: auth_name.to_string(), ..Default::default() };
+
+    let client = match DoqClient::new(server, auth_name, tls) {
+        Ok(client) => client,
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+    let (connection, handshake) = match tokio::time::timeout(config.timeout, client.connect()).await {
+        Ok(Ok(connected)) => connected,
+        Ok(Err(e)) => {
+            result.error = Some(e);
+            return result;
+        }
+        Err(_) => {
+            result.error = Some(format!("соединение не установлено за {:?}", config.timeout));
+            return result;
+        }
+    };
+    result.handshake = Some(millis(handshake));
+
+    let mut latencies = Vec::new();
+    let mut timeouts = 0;
+    for index in 0..config.warmup + config.samples {
+        let (name, record_type) = warm_question(index);
+        let measured = index >= config.warmup;
+
+        match query(&connection, name, record_type, config.timeout).await {
+            QueryOutcome::Answered { latency, .. } => {
+                if index == 0 {
+                    result.first_answer = Some(millis(handshake + latency));
+                }
+                if measured {
+                    latencies.push(millis(latency));
+                }
+            }
+            QueryOutcome::Invalid { reason, .. } => result.error = Some(format!("{} {}: {}", record_type.label(), name, reason)),
+            QueryOutcome::Timeout => {
+                timeouts += measured as usize;
+                result.error = Some(format!("{} {}: нет ответа", record_type.label(), name));
+            }
+            QueryOutcome::Failed(e) => result.error = Some(format!("{} {}: {}", record_type.label(), name, e)),
+        }
+    }
+    result.queries = LatencyStats::from_samples(&latencies, config.samples, timeouts);
+    // Билет сессии уже получен - закрываем и подключаемся заново
+    close(&connection);
+
+    match tokio::time::timeout(config.timeout * 2, client.resume(WARM_DOMAINS[0], RecordType::A, config.timeout)).await {
+        Ok(Ok((QueryOutcome::Answered { latency, .. }, zero_rtt))) => {
+            result.resumed_first_answer = Some(millis(latency));
+            result.zero_rtt = zero_rtt;
+        }
+        Ok(Ok((outcome, _))) => result.error = Some(format!("повторное подключение: {:?}", outcome)),
+        Ok(Err(e)) => result.error = Some(format!("повторное подключение: {}", e)),
+        Err(_) => result.error = Some("повторное подключение: нет ответа".to_string()),
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::test_server::{block_on, frame};
+    use crate::dns::tls::tests::self_signed_roots;
+    use crate::dns::wire::tests::answer_for;
+    use crate::dns::wire::RCODE_NOERROR;
+    use quinn::crypto::rustls::QuicServerConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const AUTH_NAME: &str = "doq.test";
+
+    // Локальный сервер DoQ: отвечает пустым NOERROR на каждый поток и считает подключения
+    fn spawn_server() -> (SocketAddr, quinn::ClientConfig, Arc<AtomicUsize>) {
+        let (mut server_tls, roots) = self_signed_roots(AUTH_NAME, &[DOQ_ALPN]);
+        // QUIC допускает только 0 или u32::MAX
+        server_tls.max_early_data_size = u32::MAX;
+        let crypto = QuicServerConfig::try_from(server_tls).unwrap();
+        let endpoint =
+            Endpoint::server(quinn::ServerConfig::with_crypto(Arc::new(crypto)), "127.0.0.1:0".parse().unwrap()).unwrap();
+        let address = endpoint.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            while let Some(incoming) = endpoint.accept().await {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let Ok(connection) = incoming.await else {
+                        return;
+                    };
+                    while let Ok((mut send, mut receive)) = connection.accept_bi().await {
+                        tokio::spawn(async move {
+                            let query = receive.read_to_end(MAX_RESPONSE).await.unwrap();
+                            send.write_all(&frame(&answer_for(&query[2..], RCODE_NOERROR, &[]))).await.unwrap();
+                            send.finish().unwrap();
+                            let _ = send.stopped().await;
+                        });
+                    }
+                });
+            }
+        });
+
+        (address, client_config(roots), connections)
+    }
+
+    #[test]
+    fn queries_reuse_the_connection_and_resumption_uses_zero_rtt() {
+        block_on(async {
+            let (address, tls, connections) = spawn_server();
+            let config = BenchmarkConfig { samples: 3, warmup: 1, ..Default::default() };
+            let result = measure(&address.to_string(), AUTH_NAME, &config, tls).await;
+
+            assert_eq!(result.error, None);
+            assert!(result.handshake.is_some() && result.first_answer.is_some());
+            assert_eq!(result.queries.answered, 3);
+            assert!(result.resumed_first_answer.is_some());
+            assert!(result.zero_rtt, "сервер не принял 0-RTT");
+            // Первое соединение на все запросы и одно повторное
+            assert_eq!(connections.load(Ordering::SeqCst), 2);
+        });
+    }
+}
@@ -0,0 +1,304 @@
+// DNS-over-TLS (RFC 7858): запросы по TLS на порт 853 с двухбайтовой длиной перед
+// каждым сообщением (как DNS по TCP). Соединение одно на весь замер; запросы можно
+// отправлять пачкой, не дожидаясь ответов, а ответы приходят в любом порядке (RFC 7766)
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use rustls::ClientConfig;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+
+use super::speed_test::{millis, socket_address, warm_question, BenchmarkConfig, LatencyStats, QueryOutcome};
+use super::tls::{self, ConnectionSetup};
+use super::wire::{build_query, random_id, RecordType};
+
+pub const DOT_PORT: u16 = 853;
+
+pub struct DotConnection {
+    address: SocketAddr,
+    auth_name: String,
+    tls: Arc<ClientConfig>,
+    // None - прошлая пачка оборвалась, следующая откроет новое соединение
+    stream: Option<TlsStream<TcpStream>>,
+    // Установка первого соединения; переподключения в замер не входят
+    pub setup: ConnectionSetup,
+}
+
+impl DotConnection {
+    // Подключаемся по адресу провайдера, а сертификат проверяем по его auth name
+    pub async fn connect(server: &str, auth_name: &str, tls: Arc<ClientConfig>) -> Result<Self, String> {
+        let address = socket_address(server, DOT_PORT)?;
+        let (stream, setup) = tls::connect(address, auth_name, tls.clone()).await?;
+        Ok(Self { address, auth_name: auth_name.to_string(), tls, stream: Some(stream), setup })
+    }
+
+    // Все вопросы уходят одной записью; ответы сопоставляются по ID.
+    // Задержка каждого - от отправки пачки до прихода его ответа
+    pub async fn pipeline(&mut self, questions: &[(&str, RecordType)], timeout: Duration) -> Vec<QueryOutcome> {
+        let mut ids = Vec::with_capacity(questions.len());
+        let mut frames = Vec::new();
+        for (name, record_type) in questions {
+            let mut id = random_id();
+            while ids.contains(&id) {
+                id = random_id();
+            }
+            ids.push(id);
+
+            let message = build_query(id, name, *record_type);
+            frames.extend_from_slice(&(message.len() as u16).to_be_bytes());
+            frames.extend_from_slice(&message);
+        }
+
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => {
+                let reconnect = tls::connect(self.address, &self.auth_name, self.tls.clone());
+                match tokio::time::timeout(timeout, reconnect).await {
+                    Ok(Ok((stream, _))) => self.stream.insert(stream),
+                    Ok(Err(e)) => return vec![QueryOutcome::Failed(e); questions.len()],
+                    Err(_) => return vec![QueryOutcome::Timeout; questions.len()],
+                }
+            }
+        };
+
+        let mut outcomes: Vec<Option<QueryOutcome>> = vec![None; questions.len()];
+        let started = Instant::now();
+        if let Err(e) = write(stream, &frames).await {
+            self.stream = None;
+            return vec![QueryOutcome::Failed(e); questions.len()];
+        }
+
+        let deadline = tokio::time::Instant::from_std(started + timeout);
+        let mut pending: HashSet<u16> = ids.iter().copied().collect();
+        while !pending.is_empty() {
+            let message = match tokio::time::timeout_at(deadline, read_message(stream)).await {
+                Ok(Ok(message)) => message,
+                Ok(Err(e)) => {
+                    fill_pending(&mut outcomes, QueryOutcome::Failed(e));
+                    break;
+                }
+                Err(_) => break,
+            };
+            let latency = started.elapsed();
+
+            // Ответ без известного ID - запоздавший ответ на прошлую пачку
+            let Some(id) = message.get(..2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]])) else {
+                continue;
+            };
+            if !pending.remove(&id) {
+                continue;
+            }
+            let index = ids.iter().position(|candidate| *candidate == id).expect("ID из отправленных");
+            let (name, record_type) = questions[index];
+            outcomes[index] = Some(QueryOutcome::from_message(&message, id, name, record_type, latency));
+        }
+
+        // Без всех ответов поток мог оборваться посреди кадра, и дальше его уже не разобрать -
+        // такое соединение закрываем, а не переиспользуем
+        if !pending.is_empty() {
+            self.stream = None;
+        }
+
+        outcomes.into_iter().map(|outcome| outcome.unwrap_or(QueryOutcome::Timeout)).collect()
+    }
+
+    pub async fn query(&mut self, name: &str, record_type: RecordType, timeout: Duration) -> QueryOutcome {
+        self.pipeline(&[(name, record_type)], timeout).await.remove(0)
+    }
+}
+
+async fn write(stream: &mut TlsStream<TcpStream>, frames: &[u8]) -> Result<(), String> {
+    stream.write_all(frames).await.map_err(|e| e.to_string())?;
+    stream.flush().await.map_err(|e| e.to_string())
+}
+
+async fn read_message(stream: &mut TlsStream<TcpStream>) -> Result<Vec<u8>, String> {
+    let length = stream.read_u16().await.map_err(|e| format!("соединение закрыто: {}", e))?;
+    let mut message = vec![0; length as usize];
+    stream.read_exact(&mut message).await.map_err(|e| format!("ответ обрезан: {}", e))?;
+    Ok(message)
+}
+
+fn fill_pending(outcomes: &mut [Option<QueryOutcome>], outcome: QueryOutcome) {
+    for slot in outcomes.iter_mut().filter(|slot| slot.is_none()) {
+        *slot = Some(outcome.clone());
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DotResult {
+    pub server: String,
+    pub auth_name: String,
+    // None - соединение установить не удалось
+    pub setup: Option<ConnectionSetup>,
+    // Запросы по одному, каждый после ответа на предыдущий
+    pub sequential: LatencyStats,
+    // Те же запросы одной пачкой
+    pub pipelined: LatencyStats,
+    pub error: Option<String>,
+}
+
+// Корневые сертификаты без ALPN: RFC 7858 его не требует, а часть серверов не знает "dot"
+pub fn web_tls() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG.get_or_init(|| tls::client_config(tls::web_roots(), &[])).clone()
+}
+
+pub async fn measure(server: &str, auth_name: &str, config: &BenchmarkConfig, tls: Arc<ClientConfig>) -> DotResult {
+    let mut result = DotResult { server: server.to_string(), auth_name: auth_name.to_string(), ..Default::default() };
+
+    let mut connection = match tokio::time::timeout(config.timeout, DotConnection::connect(server, auth_name, tls)).await {
+        Ok(Ok(connection)) => connection,
+        Ok(Err(e)) => {
+            result.error = Some(e);
+            return result;
+        }
+        Err(_) => {
+            result.error = Some(format!("соединение не установлено за {:?}", config.timeout));
+            return result;
+        }
+    };
+    result.setup = Some(connection.setup.clone());
+
+    let mut latencies = Vec::new();
+    let mut timeouts = 0;
+    for index in 0..config.warmup + config.samples {
+        let (name, record_type) = warm_question(index);
+        let outcome = connection.query(name, record_type, config.timeout).await;
+        if index >= config.warmup {
+            record(&mut result.error, outcome, &mut latencies, &mut timeouts);
+        }
+    }
+    result.sequential = LatencyStats::from_samples(&latencies, config.samples, timeouts);
+
+    let questions: Vec<_> = (0..config.samples).map(warm_question).collect();
+    let mut latencies = Vec::new();
+    let mut timeouts = 0;
+    for outcome in connection.pipeline(&questions, config.timeout).await {
+        record(&mut result.error, outcome, &mut latencies, &mut timeouts);
+    }
+    result.pipelined = LatencyStats::from_samples(&latencies, config.samples, timeouts);
+
+    result
+}
+
+fn record(error: &mut Option<String>, outcome: QueryOutcome, latencies: &mut Vec<f64>, timeouts: &mut usize) {
+    match outcome {
+        QueryOutcome::Answered { latency, .. } => latencies.push(millis(latency)),
+        QueryOutcome::Invalid { reason, .. } => *error = Some(reason),
+        QueryOutcome::Timeout => {
+            *timeouts += 1;
+            *error = Some("нет ответа".to_string());
+        }
+        QueryOutcome::Failed(e) => *error = Some(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::test_server::{block_on, frame, read_frame, spawn_server};
+    use crate::dns::tls::tests::self_signed;
+    use crate::dns::wire::tests::answer_for;
+    use crate::dns::wire::RCODE_NOERROR;
+
+    const AUTH_NAME: &str = "dns.test";
+
+    // Локальный сервер DoT: копит batch запросов и отвечает на них в обратном порядке
+    async fn spawn_dot_server(batch: usize) -> (SocketAddr, Arc<ClientConfig>) {
+        let (server_config, client_config) = self_signed(AUTH_NAME, &[]);
+        let address = spawn_server(Some(server_config), move |_, mut stream| async move {
+            let mut queries = Vec::new();
+            while let Some(query) = read_frame(&mut stream).await {
+                queries.push(query);
+                if queries.len() < batch {
+                    continue;
+                }
+
+                let frames: Vec<u8> =
+                    queries.drain(..).rev().flat_map(|query| frame(&answer_for(&query, RCODE_NOERROR, &[]))).collect();
+                stream.write_all(&frames).await.unwrap();
+            }
+        })
+        .await;
+
+        (address, client_config)
+    }
+
+    #[test]
+    fn pipelined_answers_are_matched_out_of_order() {
+        block_on(async {
+            let (address, tls) = spawn_dot_server(3).await;
+            let mut connection = DotConnection::connect(&address.to_string(), AUTH_NAME, tls).await.unwrap();
+
+            let questions = [("example.com", RecordType::A), ("google.com", RecordType::Aaaa), ("wikipedia.org", RecordType::A)];
+            let outcomes = connection.pipeline(&questions, Duration::from_secs(2)).await;
+
+            assert_eq!(outcomes.len(), 3);
+            assert!(outcomes.iter().all(|outcome| matches!(outcome, QueryOutcome::Answered { .. })), "{:?}", outcomes);
+        });
+    }
+
+    #[test]
+    fn measurement_reuses_one_connection() {
+        block_on(async {
+            let (address, tls) = spawn_dot_server(1).await;
+            let config = BenchmarkConfig { samples: 4, warmup: 1, ..Default::default() };
+            let result = measure(&address.to_string(), AUTH_NAME, &config, tls).await;
+
+            assert_eq!(result.error, None);
+            assert!(result.setup.is_some());
+            assert_eq!((result.sequential.answered, result.pipelined.answered), (4, 4));
+        });
+    }
+
+    #[test]
+    fn timed_out_frame_closes_the_connection() {
+        block_on(async {
+            // Первое соединение отдает только начало ответа и замолкает, следующие отвечают сразу
+            let (server_config, tls) = self_signed(AUTH_NAME, &[]);
+            let address = spawn_server(Some(server_config), |index, mut stream| async move {
+                while let Some(query) = read_frame(&mut stream).await {
+                    let frame = frame(&answer_for(&query, RCODE_NOERROR, &[]));
+                    let sent = if index == 0 { 4 } else { frame.len() };
+                    stream.write_all(&frame[..sent]).await.unwrap();
+                    stream.flush().await.unwrap();
+                }
+            })
+            .await;
+
+            let mut connection = DotConnection::connect(&address.to_string(), AUTH_NAME, tls).await.unwrap();
+            let timeout = Duration::from_millis(200);
+            assert_eq!(connection.query("example.com", RecordType::A, timeout).await, QueryOutcome::Timeout);
+
+            let outcome = connection.query("example.org", RecordType::A, timeout).await;
+            assert!(matches!(outcome, QueryOutcome::Answered { .. }), "{:?}", outcome);
+        });
+    }
+
+    #[test]
+    fn certificate_must_match_auth_name() {
+        block_on(async {
+            let (address, tls) = spawn_dot_server(1).await;
+            let error = DotConnection::connect(&address.to_string(), "other.test", tls).await.err().unwrap();
+            assert!(error.starts_with("TLS other.test"), "{}", error);
+        });
+    }
+
+    #[test]
+    fn silent_pipeline_times_out_every_question() {
+        block_on(async {
+            // Сервер ждет пачку из 5 запросов, а приходит 2 - ответа не будет
+            let (address, tls) = spawn_dot_server(5).await;
+            let mut connection = DotConnection::connect(&address.to_string(), AUTH_NAME, tls).await.unwrap();
+            let outcomes = connection
+                .pipeline(&[("example.com", RecordType::A), ("google.com", RecordType::A)], Duration::from_millis(200))
+                .await;
+            assert_eq!(outcomes, vec![QueryOutcome::Timeout, QueryOutcome::Timeout]);
+        });
+    }
+}
@@ -0,0 +1,149 @@
+// Типизированные ошибки DNS операций и отчет о примененных изменениях
+
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::command::CommandError;
+
+// Serialize нужен, чтобы ошибки приходили из привилегированного helper как есть
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum DnsError {
+    // Нужны права администратора (root / polkit)
+    PrivilegeMissing(String),
+    AdapterNotFound(String),
+    // PowerShell, D-Bus сервис или файл конфигурации недоступны
+    BackendUnavailable(String),
+    CommandTimeout(Duration),
+    ParseFailure(String),
+    InvalidAddress(String),
+    // Часть адаптеров настроена, часть нет - подробности в отчете
+    PartialSuccess(ApplyReport),
+    Failed(String),
+}
+
+impl fmt::Display for DnsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Что делать, подсказывает UI: на Linux это окно polkit, а не перезапуск от администратора
+            DnsError::PrivilegeMissing(detail) => write!(f, "недостаточно прав ({})", detail),
+            DnsError::AdapterNotFound(name) => write!(f, "адаптер \"{}\" не найден", name),
+            DnsError::BackendUnavailable(detail) => write!(f, "бэкенд DNS недоступен: {}", detail),
+            DnsError::CommandTimeout(timeout) => {
+                write!(f, "команда не завершилась за {:.0} с", timeout.as_secs_f64())
+            }
+            DnsError::ParseFailure(detail) => write!(f, "не удалось разобрать ответ системы: {}", detail),
+            DnsError::InvalidAddress(address) => write!(f, "некорректный адрес DNS сервера: {}", address),
+            DnsError::PartialSuccess(report) => write!(
+                f,
+                "изменения применены частично ({} из {} адаптеров)",
+                report.succeeded().count(),
+                report.changes.len()
+            ),
+            DnsError::Failed(detail) => write!(f, "{}", detail),
+        }
+    }
+}
+
+impl From<CommandError> for DnsError {
+    fn from(error: CommandError) -> Self {
+        match error {
+            CommandError::Timeout(timeout) => DnsError::CommandTimeout(timeout),
+            CommandError::Spawn(detail) | CommandError::NotRecorded(detail) => DnsError::BackendUnavailable(detail),
+            CommandError::Failed { stderr, .. } if is_access_denied(&stderr) => DnsError::PrivilegeMissing(stderr),
+            CommandError::Failed { stderr, .. } => DnsError::Failed(stderr),
+            error @ CommandError::NotSaved(_) => DnsError::Failed(error.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for DnsError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => DnsError::PrivilegeMissing(error.to_string()),
+            _ => DnsError::Failed(error.to_string()),
+        }
+    }
+}
+
+// Сообщения Windows и D-Bus об отказе в доступе (английская и русская локали)
+fn is_access_denied(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ["access is denied", "permissiondenied", "отказано в доступе", "requires elevation", "accessdenied", "not authorized"]
+        .iter()
+        .any(|marker| message.contains(marker))
+}
+
+#[cfg(target_os = "linux")]
+impl From<zbus::Error> for DnsError {
+    fn from(error: zbus::Error) -> Self {
+        match &error {
+            zbus::Error::MethodError(name, _, _)
+                if name.as_str() == "org.freedesktop.DBus.Error.AccessDenied"
+                    || name.as_str() == "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired" =>
+            {
+                DnsError::PrivilegeMissing(error.to_string())
+            }
+            zbus::Error::MethodError(name, _, _) if name.as_str() == "org.freedesktop.DBus.Error.ServiceUnknown" => {
+                DnsError::BackendUnavailable(error.to_string())
+            }
+            zbus::Error::InputOutput(_) | zbus::Error::Address(_) | zbus::Error::Handshake(_) => {
+                DnsError::BackendUnavailable(error.to_string())
+            }
+            zbus::Error::Variant(_) | zbus::Error::InvalidReply => DnsError::ParseFailure(error.to_string()),
+            _ => DnsError::Failed(error.to_string()),
+        }
+    }
+}
+
+// Что произошло с одним адаптером (интерфейсом, профилем подключения)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdapterChange {
+    pub adapter: String,
+    pub result: Result<String, DnsError>,
+}
+
+impl AdapterChange {
+    pub fn ok(adapter: impl Into<String>, change: impl Into<String>) -> Self {
+        Self { adapter: adapter.into(), result: Ok(change.into()) }
+    }
+
+    pub fn err(adapter: impl Into<String>, error: DnsError) -> Self {
+        Self { adapter: adapter.into(), result: Err(error) }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ApplyReport {
+    pub backend: String,
+    pub changes: Vec<AdapterChange>,
+}
+
+impl ApplyReport {
+    pub fn new(backend: &str, changes: Vec<AdapterChange>) -> Self {
+        Self { backend: backend.to_string(), changes }
+    }
+
+    pub fn succeeded(&self) -> impl Iterator<Item = &AdapterChange> {
+        self.changes.iter().filter(|change| change.result.is_ok())
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &AdapterChange> {
+        self.changes.iter().filter(|change| change.result.is_err())
+    }
+
+    // Все адаптеры успешно - Ok, часть - PartialSuccess, ни одного - ошибка первого адаптера
+    pub fn into_result(self) -> Result<ApplyReport, DnsError> {
+        if self.failed().next().is_none() {
+            return Ok(self);
+        }
+        if self.succeeded().next().is_some() {
+            return Err(DnsError::PartialSuccess(self));
+        }
+
+        let first_error = self.changes.into_iter().find_map(|change| change.result.err());
+        Err(first_error.unwrap_or_else(|| DnsError::Failed("неизвестная ошибка".to_string())))
+    }
+}
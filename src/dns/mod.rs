@@ -1,5 +1,20 @@
 // Модуль для DNS функций
 // Содержит функции работы с DNS, провайдерами, тестированием
 
+pub mod backend;
+pub mod catalog;
+pub mod doh;
+pub mod doq;
+pub mod dot;
+pub mod error;
+pub mod plan;
 pub mod providers;
 pub mod speed_test;
+pub mod stamp;
+pub mod tcp;
+#[cfg(test)]
+mod test_server;
+pub mod tls;
+pub mod transaction;
+pub mod wire;
+pub mod workload;
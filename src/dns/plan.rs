@@ -0,0 +1,67 @@
+// План изменения DNS: что поменяется на каждом адаптере и какими командами.
+// План строится только чтением состояния системы - ничего не применяется
+
+use std::net::IpAddr;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AdapterPlan {
+    pub adapter: String,
+    // Имя интерфейса для AdapterSelection::Names - по нему план можно откатить
+    pub interface: String,
+    // Серверы, которые адаптер использует сейчас
+    pub current: Vec<String>,
+    // Текущие серверы выдает система (DHCP, настройки сети), а не заданы вручную:
+    // откат возвращает их сбросом, а не закрепляет те же адреса статически
+    pub from_dhcp: bool,
+    pub new: Vec<String>,
+    pub to_dhcp: bool,
+    // Команды в том виде, в каком их выполнит бэкенд (для D-Bus - эквивалентные команды CLI)
+    pub commands: Vec<String>,
+}
+
+impl AdapterPlan {
+    pub fn set(adapter: impl Into<String>, current: Vec<String>, servers: &[IpAddr], commands: Vec<String>) -> Self {
+        let adapter = adapter.into();
+        Self {
+            interface: adapter.clone(),
+            adapter,
+            current,
+            from_dhcp: false,
+            new: servers.iter().map(|ip| ip.to_string()).collect(),
+            to_dhcp: false,
+            commands,
+        }
+    }
+
+    pub fn reset(adapter: impl Into<String>, current: Vec<String>, commands: Vec<String>) -> Self {
+        let adapter = adapter.into();
+        Self { interface: adapter.clone(), adapter, current, from_dhcp: false, new: Vec::new(), to_dhcp: true, commands }
+    }
+
+    pub fn with_dhcp_source(self, from_dhcp: bool) -> Self {
+        Self { from_dhcp, ..self }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChangePlan {
+    pub backend: String,
+    pub adapters: Vec<AdapterPlan>,
+    // Явно выбранные адаптеры, которых нет в системе
+    pub missing: Vec<String>,
+}
+
+impl ChangePlan {
+    pub fn new(backend: &str, adapters: Vec<AdapterPlan>, missing: Vec<String>) -> Self {
+        Self { backend: backend.to_string(), adapters, missing }
+    }
+}
+
+// Оставляем только адреса - заглушки вроде "N/A" и "Не настроен" значат, что серверов нет
+pub(crate) fn configured_servers(servers: &[String]) -> Vec<String> {
+    servers
+        .iter()
+        .filter_map(|s| s.trim().parse::<IpAddr>().ok())
+        .map(|ip| ip.to_string())
+        .collect()
+}
@@ -1,23 +1,254 @@
 // Модуль для DNS провайдеров
 
 use std::collections::HashSet;
+use std::net::IpAddr;
 
-#[derive(Clone)]
+use serde::{Deserialize, Serialize};
+
+// Так же провайдер описывается в файле каталога (см. catalog.rs)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct DNSProvider {
     pub name: String,
     pub primary: String,
     pub secondary: String,
+    #[serde(default)]
     pub description: String,
+    // Адрес DNS-over-HTTPS (RFC 8484), если провайдер его поддерживает
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doh: Option<String>,
+    // Имя для проверки сертификата DNS-over-TLS (RFC 7858) на основном адресе
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dot: Option<String>,
+    // Имя для проверки сертификата DNS-over-QUIC (RFC 9250) на основном адресе
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doq: Option<String>,
+    // IPv6 адреса тех же резолверов; резервный без основного не имеет смысла
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary_v6: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secondary_v6: Option<String>,
+    // Резолвер проверяет подписи DNSSEC
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub dnssec: bool,
+    // Провайдер заявляет, что не ведет журналы запросов
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub no_logs: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dnscrypt: Option<DnsCrypt>,
+    // SHA-256 сертификатов из штампа, в hex. Только сохраняются для экспорта в штамп -
+    // сам сертификат проверяется по корням Mozilla
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cert_hashes: Vec<String>,
+    // Основной адрес - лишь точка входа DNSCrypt или DoH из штампа: обычный DNS на 53 порту
+    // там не обещан, поэтому системным DNS такой провайдер не ставится и по UDP не замеряется
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub encrypted_only: bool,
+    // Что блокирует резолвер; None - неизвестно
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filtering: Option<Filtering>,
+    // Передает ли резолвер подсеть клиента авторитетным серверам (EDNS Client Subnet, RFC 7871)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ecs: Option<Ecs>,
+    // Ссылка на политику хранения журналов
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logging_policy: Option<String>,
+}
+
+// Протоколы, по которым можно обращаться к провайдеру
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    // Обычный DNS по UDP/TCP 53 на основном и резервном адресах
+    Plain,
+    DnsCrypt,
+    Doh,
+    Dot,
+    Doq,
+}
+
+impl Transport {
+    pub const ALL: [Transport; 5] = [Transport::Plain, Transport::DnsCrypt, Transport::Doh, Transport::Dot, Transport::Doq];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Transport::Plain => "DNS",
+            Transport::DnsCrypt => "DNSCrypt",
+            Transport::Doh => "DoH",
+            Transport::Dot => "DoT",
+            Transport::Doq => "DoQ",
+        }
+    }
+}
+
+// Категория фильтрации по возрастанию строгости
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filtering {
+    // Отвечает как есть
+    #[serde(rename = "none")]
+    Unfiltered,
+    // Вредоносные и фишинговые домены
+    Malware,
+    // Реклама и трекеры, а вместе с ними и вредоносные домены
+    Ads,
+    // Сайты для взрослых и вредоносные домены
+    Family,
+}
+
+impl Filtering {
+    pub const ALL: [Filtering; 4] = [Filtering::Unfiltered, Filtering::Malware, Filtering::Ads, Filtering::Family];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Filtering::Unfiltered => "без фильтрации",
+            Filtering::Malware => "вредоносные",
+            Filtering::Ads => "реклама",
+            Filtering::Family => "семейный",
+        }
+    }
+
+    // Подходит ли резолвер, если нужна фильтрация wanted: вредоносные домены
+    // блокируют все категории, кроме "без фильтрации", остальные - только свою
+    pub fn covers(self, wanted: Filtering) -> bool {
+        match wanted {
+            Filtering::Malware => self != Filtering::Unfiltered,
+            _ => self == wanted,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ecs {
+    // Подсеть клиента не передается - приватнее, но CDN может выбрать дальний узел
+    Off,
+    // Передается урезанная подсеть клиента (обычно /24 и /56)
+    Subnet,
+}
+
+impl Ecs {
+    pub fn label(self) -> &'static str {
+        match self {
+            Ecs::Off => "ECS выключен",
+            Ecs::Subnet => "передает подсеть (ECS)",
+        }
+    }
+}
+
+// Стандартный порт DNSCrypt - тот же, что у HTTPS
+pub const DNSCRYPT_PORT: u16 = 443;
+
+// Сервер DNSCrypt на основном адресе: сертификат резолвера подписан ключом public_key
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DnsCrypt {
+    // Имя провайдера вида 2.dnscrypt-cert.example.com
+    pub provider_name: String,
+    // Открытый ключ Ed25519 в hex
+    pub public_key: String,
+    #[serde(default = "dnscrypt_port")]
+    pub port: u16,
+}
+
+fn dnscrypt_port() -> u16 {
+    DNSCRYPT_PORT
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl DNSProvider {
+    // Все адреса для установки в систему: сначала IPv4, затем IPv6, без повторов
+    pub fn servers(&self) -> Vec<String> {
+        let mut servers: Vec<String> = Vec::new();
+        for server in [Some(&self.primary), Some(&self.secondary), self.primary_v6.as_ref(), self.secondary_v6.as_ref()] {
+            match server {
+                Some(server) if !server.trim().is_empty() && !servers.contains(server) => servers.push(server.clone()),
+                _ => {}
+            }
+        }
+        servers
+    }
+
+    pub fn transports(&self) -> Vec<Transport> {
+        Transport::ALL.into_iter().filter(|transport| self.endpoint(*transport).is_some()).collect()
+    }
+
+    // Куда подключаться по транспорту; None - провайдер его не поддерживает
+    pub fn endpoint(&self, transport: Transport) -> Option<String> {
+        let primary = |port: u16| match self.primary.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("[{}]:{}", ip, port),
+            _ => format!("{}:{}", self.primary, port),
+        };
+        match transport {
+            Transport::Plain => (!self.encrypted_only).then(|| primary(53)),
+            Transport::DnsCrypt => {
+                self.dnscrypt.as_ref().map(|dnscrypt| format!("{} ({})", primary(dnscrypt.port), dnscrypt.provider_name))
+            }
+            Transport::Doh => self.doh.clone(),
+            Transport::Dot => self.dot.as_ref().map(|name| format!("{} ({})", primary(853), name)),
+            Transport::Doq => self.doq.as_ref().map(|name| format!("{} ({})", primary(853), name)),
+        }
+    }
+}
+
+// Отбор провайдеров на вкладке провайдеров; пустой фильтр пропускает всех
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProviderFilter {
+    pub transport: Option<Transport>,
+    pub filtering: Option<Filtering>,
+    pub dnssec: bool,
+    pub no_ecs: bool,
+    pub no_logs: bool,
+}
+
+impl ProviderFilter {
+    pub fn matches(&self, provider: &DNSProvider) -> bool {
+        self.transport.is_none_or(|transport| provider.endpoint(transport).is_some())
+            && self.filtering.is_none_or(|wanted| provider.filtering.is_some_and(|filtering| filtering.covers(wanted)))
+            && (!self.dnssec || provider.dnssec)
+            && (!self.no_ecs || provider.ecs == Some(Ecs::Off))
+            && (!self.no_logs || provider.no_logs)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProviderSort {
+    // Порядок файла каталога
+    #[default]
+    Catalog,
+    Name,
+    // От "без фильтрации" к семейным, неизвестные в конце
+    Filtering,
+    // Больше транспортов - выше
+    Transports,
 }
 
-#[derive(Clone, Default)]
-pub struct SpeedTestResult {
-    pub provider: String,
-    pub primary_ping: Option<f64>,
-    pub secondary_ping: Option<f64>,
-    pub avg_ping: Option<f64>,
+impl ProviderSort {
+    pub const ALL: [ProviderSort; 4] = [ProviderSort::Catalog, ProviderSort::Name, ProviderSort::Filtering, ProviderSort::Transports];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ProviderSort::Catalog => "как в каталоге",
+            ProviderSort::Name => "по имени",
+            ProviderSort::Filtering => "по фильтрации",
+            ProviderSort::Transports => "по числу протоколов",
+        }
+    }
+
+    // Сортировка устойчивая: равные остаются в порядке каталога
+    pub fn sort(self, providers: &mut [DNSProvider]) {
+        match self {
+            ProviderSort::Catalog => {}
+            ProviderSort::Name => providers.sort_by_key(|provider| provider.name.to_lowercase()),
+            ProviderSort::Filtering => providers.sort_by_key(|provider| (provider.filtering.is_none(), provider.filtering)),
+            ProviderSort::Transports => providers.sort_by_key(|provider| std::cmp::Reverse(provider.transports().len())),
+        }
+    }
 }
 
+// Встроенный каталог - он же содержимое нового файла каталога
 pub fn get_dns_providers() -> Vec<DNSProvider> {
     vec![
         DNSProvider {
@@ -25,188 +256,243 @@ pub fn get_dns_providers() -> Vec<DNSProvider> {
             primary: "1.1.1.1".to_string(),
             secondary: "1.0.0.1".to_string(),
             description: "Быстрый и приватный DNS от Cloudflare".to_string(),
+            doh: Some("https://cloudflare-dns.com/dns-query".to_string()),
+            dot: Some("cloudflare-dns.com".to_string()),
+            doq: None,
+            primary_v6: Some("2606:4700:4700::1111".to_string()),
+            secondary_v6: Some("2606:4700:4700::1001".to_string()),
+            dnssec: true,
+            no_logs: true,
+            dnscrypt: None,
+            cert_hashes: Vec::new(),
+            encrypted_only: false,
+            filtering: Some(Filtering::Unfiltered),
+            ecs: Some(Ecs::Off),
+            logging_policy: Some("https://developers.cloudflare.com/1.1.1.1/privacy/public-dns-resolver/".to_string()),
         },
         DNSProvider {
             name: "Google".to_string(),
             primary: "8.8.8.8".to_string(),
             secondary: "8.8.4.4".to_string(),
             description: "Надежный DNS от Google".to_string(),
+            doh: Some("https://dns.google/dns-query".to_string()),
+            dot: Some("dns.google".to_string()),
+            doq: None,
+            primary_v6: Some("2001:4860:4860::8888".to_string()),
+            secondary_v6: Some("2001:4860:4860::8844".to_string()),
+            dnssec: true,
+            no_logs: false,
+            dnscrypt: None,
+            cert_hashes: Vec::new(),
+            encrypted_only: false,
+            filtering: Some(Filtering::Unfiltered),
+            ecs: Some(Ecs::Subnet),
+            logging_policy: Some("https://developers.google.com/speed/public-dns/privacy".to_string()),
         },
         DNSProvider {
             name: "Quad9".to_string(),
             primary: "9.9.9.9".to_string(),
             secondary: "149.112.112.112".to_string(),
             description: "Защита от вредоносных сайтов".to_string(),
+            doh: Some("https://dns.quad9.net/dns-query".to_string()),
+            dot: Some("dns.quad9.net".to_string()),
+            doq: None,
+            primary_v6: Some("2620:fe::fe".to_string()),
+            secondary_v6: Some("2620:fe::9".to_string()),
+            dnssec: true,
+            no_logs: true,
+            dnscrypt: None,
+            cert_hashes: Vec::new(),
+            encrypted_only: false,
+            filtering: Some(Filtering::Malware),
+            ecs: Some(Ecs::Off),
+            logging_policy: Some("https://quad9.net/privacy/policy/".to_string()),
         },
         DNSProvider {
             name: "OpenDNS".to_string(),
             primary: "208.67.222.222".to_string(),
             secondary: "208.67.220.220".to_string(),
             description: "Семейная фильтрация контента".to_string(),
+            doh: Some("https://doh.opendns.com/dns-query".to_string()),
+            dot: None,
+            doq: None,
+            primary_v6: Some("2620:119:35::35".to_string()),
+            secondary_v6: Some("2620:119:53::53".to_string()),
+            dnssec: false,
+            no_logs: false,
+            dnscrypt: None,
+            cert_hashes: Vec::new(),
+            encrypted_only: false,
+            filtering: Some(Filtering::Malware),
+            ecs: Some(Ecs::Subnet),
+            logging_policy: Some("https://www.cisco.com/c/en/us/about/legal/privacy-full.html".to_string()),
         },
         DNSProvider {
             name: "AdGuard".to_string(),
             primary: "94.140.14.14".to_string(),
             secondary: "94.140.15.15".to_string(),
             description: "Блокировка рекламы".to_string(),
+            doh: Some("https://dns.adguard-dns.com/dns-query".to_string()),
+            dot: Some("dns.adguard-dns.com".to_string()),
+            doq: Some("dns.adguard-dns.com".to_string()),
+            primary_v6: Some("2a10:50c0::ad1:ff".to_string()),
+            secondary_v6: Some("2a10:50c0::ad2:ff".to_string()),
+            dnssec: true,
+            no_logs: true,
+            dnscrypt: None,
+            cert_hashes: Vec::new(),
+            encrypted_only: false,
+            filtering: Some(Filtering::Ads),
+            ecs: None,
+            logging_policy: Some("https://adguard-dns.io/en/privacy.html".to_string()),
         },
         DNSProvider {
             name: "CleanBrowsing".to_string(),
             primary: "185.228.168.9".to_string(),
             secondary: "185.228.169.9".to_string(),
             description: "Безопасный интернет для детей".to_string(),
+            doh: Some("https://doh.cleanbrowsing.org/doh/security-filter/".to_string()),
+            dot: Some("security-filter-dns.cleanbrowsing.org".to_string()),
+            doq: None,
+            primary_v6: Some("2a0d:2a00:1::2".to_string()),
+            secondary_v6: Some("2a0d:2a00:2::2".to_string()),
+            dnssec: true,
+            no_logs: true,
+            dnscrypt: None,
+            cert_hashes: Vec::new(),
+            encrypted_only: false,
+            filtering: Some(Filtering::Malware),
+            ecs: None,
+            logging_policy: Some("https://cleanbrowsing.org/privacy".to_string()),
         },
     ]
 }
 
-pub fn ping_dns_server(ip: &str) -> Option<f64> {
-    // Используем PowerShell для измерения задержки через Test-Connection
-    let command = format!("Test-Connection -ComputerName {} -Count 1 | Select-Object -ExpandProperty ResponseTime", ip);
+// Резолверы, которые уже стоят в системе, - опорные точки для сравнения в Лаборатории.
+// sources - подпись и строки, в которых встречаются адреса ("Автопилот (DHCP): 192.168.1.1, ...");
+// одинаковые пары серверов замеряются один раз, под первой подписью
+pub fn baseline_providers(sources: &[(String, Vec<String>)]) -> Vec<DNSProvider> {
+    let mut seen = HashSet::new();
+    let mut baselines = Vec::new();
 
-    match run_powershell_command(&command) {
-        Ok(result) => {
-            // Парсим результат
-            if let Ok(ms) = result.trim().parse::<f64>() {
-                println!("PowerShell ping to {}: {:.1}ms", ip, ms);
-                Some(ms)
-            } else {
-                println!("Failed to parse PowerShell ping result for {}: {}", ip, result);
-                None
-            }
-        }
-        Err(e) => {
-            println!("PowerShell ping to {} failed: {}", ip, e);
-            None
-        }
-    }
-}
-
-pub fn get_current_dns() -> Result<String, String> {
-    // Сначала пробуем PowerShell для получения текущих DNS серверов
-    let ps_output = run_powershell_command("Get-DnsClientServerAddress | Where-Object { $_.AddressFamily -eq 2 } | Select-Object -ExpandProperty ServerAddresses");
-
-    if let Ok(dns_list) = ps_output {
-        if !dns_list.is_empty() && dns_list != "" {
-            let dns_servers: Vec<&str> = dns_list.split_whitespace().collect();
-            if !dns_servers.is_empty() {
-                // Дедупликация DNS адресов с помощью HashSet
-                let unique_servers: HashSet<&str> = dns_servers.into_iter().collect();
-                let addresses = unique_servers.into_iter().collect::<Vec<&str>>().join(", ");
-                // Проверяем, является ли это DHCP настройками
-                if is_dhcp_dns(&addresses) {
-                    return Ok(format!("Автопилот (DHCP): {}", addresses));
-                } else {
-                    return Ok(addresses);
+    for (name, texts) in sources {
+        let mut servers: Vec<IpAddr> = Vec::new();
+        for text in texts {
+            for address in text.split(|c: char| c == ',' || c.is_whitespace()).filter_map(|token| token.parse().ok()) {
+                if !servers.contains(&address) {
+                    servers.push(address);
                 }
             }
         }
-    }
-
-    // Fallback - используем ipconfig напрямую с полным путем
-    let output = run_cmd_command("ipconfig /all");
-
-    match output {
-        Ok(stdout) => {
-            // Парсим результат
-            let lines: Vec<&str> = stdout.lines().collect();
-            let mut dns_servers = Vec::new();
-            let mut is_dhcp = false;
+        let (ipv4, ipv6): (Vec<IpAddr>, Vec<IpAddr>) = servers.into_iter().partition(IpAddr::is_ipv4);
 
-            for line in lines {
-                if line.contains("DNS servers configured through DHCP") {
-                    is_dhcp = true;
-                }
-                if line.contains("DNS Servers") && !line.contains("configured through DHCP") {
-                    let parts: Vec<&str> = line.split(':').collect();
-                    if parts.len() > 1 {
-                        let ip_part = parts[1].trim();
-                        if !ip_part.is_empty() && !ip_part.contains("None") {
-                            // Разбиваем на отдельные IP адреса
-                            let ips: Vec<&str> = ip_part.split(',').collect();
-                            for ip in ips {
-                                let clean_ip = ip.trim();
-                                if !clean_ip.is_empty() && clean_ip != "0.0.0.0" {
-                                    dns_servers.push(clean_ip.to_string());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            if dns_servers.is_empty() {
-                Ok("Автопилот (DHCP): адреса не получены".to_string())
-            } else if is_dhcp {
-                Ok(format!("Автопилот (DHCP): {}", dns_servers.join(", ")))
-            } else {
-                Ok(dns_servers.join(", "))
-            }
+        // Замер идет по двум серверам; если сервер один, он же и второй.
+        // Только IPv6 резолверы замеряются как основная пара
+        let (main, extra) = if ipv4.is_empty() { (ipv6, Vec::new()) } else { (ipv4, ipv6) };
+        let Some((primary, secondary)) = server_pair(&main) else {
+            continue;
+        };
+        let ipv6 = server_pair(&extra);
+        if !seen.insert((primary, secondary, ipv6)) {
+            continue;
         }
-        Err(e) => Err(e),
+
+        baselines.push(DNSProvider {
+            name: name.clone(),
+            primary: primary.to_string(),
+            secondary: secondary.to_string(),
+            description: "Резолвер, который уже настроен в системе".to_string(),
+            doh: None,
+            dot: None,
+            doq: None,
+            primary_v6: ipv6.map(|(primary, _)| primary.to_string()),
+            secondary_v6: ipv6.map(|(_, secondary)| secondary.to_string()),
+            dnssec: false,
+            no_logs: false,
+            dnscrypt: None,
+            cert_hashes: Vec::new(),
+            encrypted_only: false,
+            filtering: None,
+            ecs: None,
+            logging_policy: None,
+        });
     }
+    baselines
 }
 
-pub fn set_dns(primary: &str, secondary: &str) -> Result<String, String> {
-    // Получаем список активных сетевых адаптеров и устанавливаем DNS для всех
-    let command = format!(
-        r#"Get-NetAdapter | Where-Object {{ $_.Status -eq 'Up' }} | ForEach-Object {{
-    Set-DnsClientServerAddress -InterfaceAlias $_.Name -ServerAddresses ('{0}','{1}')
-}}"#,
-        primary, secondary
-    );
-    run_powershell_command(&command)
+fn server_pair(servers: &[IpAddr]) -> Option<(IpAddr, IpAddr)> {
+    let primary = *servers.first()?;
+    Some((primary, servers.get(1).copied().unwrap_or(primary)))
 }
 
-pub fn reset_dns() -> Result<String, String> {
-    // Полностью сбрасываем DNS для всех активных адаптеров к DHCP
-    let command = r#"Get-NetAdapter | Where-Object { $_.Status -eq 'Up' } | ForEach-Object {
-    # Сбрасываем все DNS настройки и устанавливаем получение от DHCP
-    Set-DnsClientServerAddress -InterfaceAlias $_.Name -ResetServerAddresses
-    # Явно включаем DHCP для DNS
-    Set-NetIPInterface -InterfaceAlias $_.Name -Dhcp Enabled
-}"#;
-    run_powershell_command(command)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn is_dhcp_dns(addresses: &str) -> bool {
-    // Простая проверка на DHCP адреса
-    addresses.contains("192.168.") || addresses.contains("10.") || addresses.contains("172.")
-}
+    fn names(providers: &[DNSProvider]) -> Vec<&str> {
+        providers.iter().map(|provider| provider.name.as_str()).collect()
+    }
+
+    #[test]
+    fn filter_finds_malware_blocking_dot_providers() {
+        let builtin = get_dns_providers();
+        let matching = |filter: &ProviderFilter| -> Vec<DNSProvider> {
+            builtin.iter().filter(|provider| filter.matches(provider)).cloned().collect()
+        };
 
-fn run_powershell_command(command: &str) -> Result<String, String> {
-    use std::process::Command;
+        assert_eq!(matching(&ProviderFilter::default()).len(), builtin.len());
 
-    let output = Command::new(r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe")
-        .arg("-Command")
-        .arg(command)
-        .output()
-        .map_err(|e| format!("Failed to execute command: {}", e))?;
+        let filter =
+            ProviderFilter { transport: Some(Transport::Dot), filtering: Some(Filtering::Malware), ..Default::default() };
+        assert_eq!(names(&matching(&filter)), vec!["Quad9", "AdGuard", "CleanBrowsing"]);
 
-    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let private = ProviderFilter { dnssec: true, no_ecs: true, no_logs: true, ..Default::default() };
+        assert_eq!(names(&matching(&private)), vec!["Cloudflare", "Quad9"]);
 
-    if output.status.success() {
-        Ok(stdout)
-    } else {
-        Err(stderr)
+        let unfiltered = ProviderFilter { filtering: Some(Filtering::Unfiltered), ..Default::default() };
+        assert_eq!(names(&matching(&unfiltered)), vec!["Cloudflare", "Google"]);
+        assert_eq!(builtin[4].endpoint(Transport::Doq).as_deref(), Some("94.140.14.14:853 (dns.adguard-dns.com)"));
     }
-}
 
-fn run_cmd_command(command: &str) -> Result<String, String> {
-    use std::process::Command;
+    #[test]
+    fn providers_are_sorted_by_metadata() {
+        let mut providers = get_dns_providers();
+        providers[1].filtering = None;
+
+        ProviderSort::Filtering.sort(&mut providers);
+        assert_eq!(names(&providers), vec!["Cloudflare", "Quad9", "OpenDNS", "CleanBrowsing", "AdGuard", "Google"]);
 
-    let output = Command::new(r"C:\Windows\System32\cmd.exe")
-        .arg("/C")
-        .arg(command)
-        .output()
-        .map_err(|e| format!("Failed to execute command: {}", e))?;
+        ProviderSort::Transports.sort(&mut providers);
+        assert_eq!(names(&providers)[0], "AdGuard");
+
+        ProviderSort::Name.sort(&mut providers);
+        assert_eq!(names(&providers), vec!["AdGuard", "CleanBrowsing", "Cloudflare", "Google", "OpenDNS", "Quad9"]);
+    }
 
-    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    #[test]
+    fn baselines_are_parsed_and_deduplicated() {
+        let sources = vec![
+            ("Текущие DNS".to_string(), vec!["Автопилот (DHCP): 192.168.1.1, 8.8.8.8".to_string()]),
+            ("DNS адаптера eth0".to_string(), vec!["192.168.1.1".to_string(), "8.8.8.8".to_string()]),
+            ("DNS адаптера eth1".to_string(), vec!["192.168.1.1, fd00::1".to_string(), "8.8.8.8".to_string()]),
+            ("DNS адаптера tun0".to_string(), vec!["fd00::53".to_string()]),
+            ("DNS адаптера wlan0".to_string(), vec!["10.0.0.1".to_string()]),
+            ("DNS адаптера lo".to_string(), vec!["Не настроен".to_string()]),
+        ];
+        let baselines = baseline_providers(&sources);
 
-    if output.status.success() {
-        Ok(stdout)
-    } else {
-        Err(stderr)
+        assert_eq!(baselines.len(), 4);
+        assert_eq!(
+            (baselines[0].name.as_str(), baselines[0].primary.as_str(), baselines[0].secondary.as_str()),
+            ("Текущие DNS", "192.168.1.1", "8.8.8.8")
+        );
+        assert_eq!(baselines[0].primary_v6, None);
+        assert_eq!(
+            (baselines[1].primary_v6.as_deref(), baselines[1].secondary_v6.as_deref()),
+            (Some("fd00::1"), Some("fd00::1"))
+        );
+        assert_eq!((baselines[2].primary.as_str(), baselines[2].secondary.as_str()), ("fd00::53", "fd00::53"));
+        assert_eq!((baselines[3].primary.as_str(), baselines[3].secondary.as_str()), ("10.0.0.1", "10.0.0.1"));
+        assert_eq!(baselines[1].servers(), vec!["192.168.1.1", "8.8.8.8", "fd00::1"]);
     }
 }
@@ -1,2 +1,1043 @@
 // Модуль для тестирования скорости DNS
-// Здесь можно вынести логику тестирования скорости
+// Вместо ICMP ping отправляем настоящие A/AAAA запросы по UDP/53 и проверяем ответы:
+// сервер может не отвечать на ping, но прекрасно резолвить, и наоборот.
+// Замер идет на tokio в фоне: провайдеры опрашиваются параллельно, UI только забирает события
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::net::UdpSocket;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::{self, error::TryRecvError, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use super::doh::{self, DohResult};
+use super::doq::{self, DoqResult};
+use super::dot::{self, DotResult};
+use super::providers::DNSProvider;
+use super::tcp::{self, TcpResult};
+use super::wire::{build_query, parse_response, random_id, RecordType};
+use super::workload::{DomainResult, Workload, WorkloadResult};
+
+// Популярные имена: после прогрева они у резолвера в кэше - это замер попадания в кэш
+pub const WARM_DOMAINS: [&str; 4] = ["example.com", "google.com", "wikipedia.org", "microsoft.com"];
+// Настоящие зоны, случайный поддомен которых резолвер не может знать заранее и идет к
+// авторитетным серверам. Зоны без DNSSEC, чтобы NXDOMAIN не синтезировался из кэша NSEC (RFC 8198)
+pub const COLD_ZONES: [&str; 4] = ["google.com", "amazon.com", "microsoft.com", "yahoo.com"];
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+pub const DNS_PORT: u16 = 53;
+
+// Вопрос шага index в замерах всех протоколов: популярные имена по кругу, тип меняется с каждым
+// кругом - A и AAAA спрашиваются для каждого имени, а не A только для четных по счету
+pub fn warm_question(index: usize) -> (&'static str, RecordType) {
+    let record_type = if (index / WARM_DOMAINS.len()).is_multiple_of(2) { RecordType::A } else { RecordType::Aaaa };
+    (WARM_DOMAINS[index % WARM_DOMAINS.len()], record_type)
+}
+
+// Сколько запросов делать и сколько первых выбросить
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BenchmarkConfig {
+    pub samples: usize,
+    // Первые запросы прогревают кэш резолвера и путь до него - в статистику не идут
+    pub warmup: usize,
+    pub timeout: Duration,
+    // Сколько провайдеров замерять одновременно
+    pub parallelism: usize,
+    // Замерять ли еще и DNS-over-HTTPS
+    pub doh: bool,
+    // И DNS-over-TLS
+    pub dot: bool,
+    // И DNS-over-QUIC
+    pub doq: bool,
+    // И DNS по TCP/53 вместе с переходом на TCP после обрезанного ответа
+    pub tcp: bool,
+    // И IPv6 адреса провайдера по UDP
+    pub ipv6: bool,
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self { samples: 10, warmup: 2, timeout: QUERY_TIMEOUT, parallelism: 3, doh: true, dot: true, doq: true, tcp: true, ipv6: true }
+    }
+}
+
+impl BenchmarkConfig {
+    // Проверка связи перед сохранением своего провайдера: по запросу к каждому серверу по UDP
+    pub fn reachability() -> Self {
+        Self {
+            samples: 1,
+            warmup: 0,
+            parallelism: 1,
+            doh: false,
+            dot: false,
+            doq: false,
+            tcp: false,
+            ipv6: false,
+            ..Default::default()
+        }
+    }
+}
+
+// Статистика по задержкам корректных ответов, в миллисекундах
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LatencyStats {
+    pub sent: usize,
+    pub answered: usize,
+    pub timeouts: usize,
+    pub min: f64,
+    pub median: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub stddev: f64,
+    // Среднее изменение задержки между соседними запросами
+    pub jitter: f64,
+}
+
+impl LatencyStats {
+    // latencies - в порядке отправки, чтобы jitter считался по соседним запросам
+    pub fn from_samples(latencies: &[f64], sent: usize, timeouts: usize) -> Self {
+        let mut stats = Self { sent, answered: latencies.len(), timeouts, ..Default::default() };
+        if latencies.is_empty() {
+            return stats;
+        }
+
+        let mut sorted = latencies.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let count = sorted.len() as f64;
+
+        stats.min = sorted[0];
+        stats.max = sorted[sorted.len() - 1];
+        stats.median = percentile(&sorted, 50.0);
+        stats.p90 = percentile(&sorted, 90.0);
+        stats.p95 = percentile(&sorted, 95.0);
+        stats.mean = sorted.iter().sum::<f64>() / count;
+        stats.stddev = (sorted.iter().map(|v| (v - stats.mean).powi(2)).sum::<f64>() / count).sqrt();
+        if latencies.len() > 1 {
+            stats.jitter = latencies.windows(2).map(|pair| (pair[1] - pair[0]).abs()).sum::<f64>()
+                / (latencies.len() - 1) as f64;
+        }
+        stats
+    }
+
+    pub fn has_answers(&self) -> bool {
+        self.answered > 0
+    }
+
+    // Доля запросов без корректного ответа
+    pub fn loss_percent(&self) -> f64 {
+        if self.sent == 0 {
+            0.0
+        } else {
+            (self.sent - self.answered) as f64 * 100.0 / self.sent as f64
+        }
+    }
+}
+
+// Метод ближайшего ранга: значение, ниже или равно которому p% замеров
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// По какой статистике сортировать провайдеров в Лаборатории
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RankingStatistic {
+    Min,
+    #[default]
+    Median,
+    Mean,
+    P90,
+    P95,
+    Max,
+    Jitter,
+    Loss,
+}
+
+impl RankingStatistic {
+    pub const ALL: [RankingStatistic; 8] = [
+        RankingStatistic::Min,
+        RankingStatistic::Median,
+        RankingStatistic::Mean,
+        RankingStatistic::P90,
+        RankingStatistic::P95,
+        RankingStatistic::Max,
+        RankingStatistic::Jitter,
+        RankingStatistic::Loss,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RankingStatistic::Min => "Min",
+            RankingStatistic::Median => "Median",
+            RankingStatistic::Mean => "Mean",
+            RankingStatistic::P90 => "P90",
+            RankingStatistic::P95 => "P95",
+            RankingStatistic::Max => "Max",
+            RankingStatistic::Jitter => "Jitter",
+            RankingStatistic::Loss => "Loss %",
+        }
+    }
+
+    // None - сервер ни разу корректно не ответил, такие уходят в конец списка
+    pub fn value(self, stats: &LatencyStats) -> Option<f64> {
+        if !stats.has_answers() {
+            return None;
+        }
+        Some(match self {
+            RankingStatistic::Min => stats.min,
+            RankingStatistic::Median => stats.median,
+            RankingStatistic::Mean => stats.mean,
+            RankingStatistic::P90 => stats.p90,
+            RankingStatistic::P95 => stats.p95,
+            RankingStatistic::Max => stats.max,
+            RankingStatistic::Jitter => stats.jitter,
+            RankingStatistic::Loss => stats.loss_percent(),
+        })
+    }
+
+    pub fn format(self, stats: &LatencyStats) -> String {
+        match (self, self.value(stats)) {
+            (_, None) => "N/A".to_string(),
+            (RankingStatistic::Loss, Some(value)) => format!("{:.0}%", value),
+            (_, Some(value)) => format!("{:.1}ms", value),
+        }
+    }
+}
+
+// Ответ из кэша резолвера или с рекурсией до авторитетных серверов
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CacheState {
+    #[default]
+    Warm,
+    Cold,
+}
+
+impl CacheState {
+    pub const ALL: [CacheState; 2] = [CacheState::Warm, CacheState::Cold];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CacheState::Warm => "🔥 кэш",
+            CacheState::Cold => "❄️ без кэша",
+        }
+    }
+}
+
+// Результат одного сервера: "отвечает ли" и "как быстро резолвит" - разные вещи
+#[derive(Clone, Debug, Default)]
+pub struct ServerResult {
+    pub server: String,
+    // Сервер прислал хоть какой-то ответ на наш запрос
+    pub reachable: bool,
+    pub warm: LatencyStats,
+    pub cold: LatencyStats,
+    // Последняя ошибка, если была
+    pub error: Option<String>,
+}
+
+impl ServerResult {
+    pub fn stats(&self, cache: CacheState) -> &LatencyStats {
+        match cache {
+            CacheState::Warm => &self.warm,
+            CacheState::Cold => &self.cold,
+        }
+    }
+}
+
+// Основной и резервный серверы одного семейства адресов и их общая статистика
+#[derive(Clone, Debug, Default)]
+pub struct ServerPair {
+    pub primary: ServerResult,
+    pub secondary: ServerResult,
+    pub warm: LatencyStats,
+    pub cold: LatencyStats,
+}
+
+impl ServerPair {
+    pub fn stats(&self, cache: CacheState) -> &LatencyStats {
+        match cache {
+            CacheState::Warm => &self.warm,
+            CacheState::Cold => &self.cold,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct SpeedTestResult {
+    pub provider: String,
+    // Резолвер, уже настроенный в системе, - с ним сравниваются остальные
+    pub baseline: bool,
+    pub primary: ServerResult,
+    pub secondary: ServerResult,
+    // Замеры обоих серверов вместе - по ним провайдер попадает в рейтинг
+    pub warm: LatencyStats,
+    pub cold: LatencyStats,
+    // None - у провайдера нет DoH или замер DoH выключен
+    pub doh: Option<DohResult>,
+    pub dot: Option<DotResult>,
+    pub doq: Option<DoqResult>,
+    // None - замер TCP/53 выключен
+    pub tcp: Option<TcpResult>,
+    // Те же замеры по IPv6 адресам; None - их у провайдера нет или замер выключен.
+    // В рейтинг не идут: без IPv6 на самом компьютере провайдер незаслуженно оказался бы последним
+    pub ipv6: Option<ServerPair>,
+    // Проход по своему набору доменов, если он выбран в Лаборатории
+    pub workload: Option<WorkloadResult>,
+}
+
+impl SpeedTestResult {
+    pub fn stats(&self, cache: CacheState) -> &LatencyStats {
+        match cache {
+            CacheState::Warm => &self.warm,
+            CacheState::Cold => &self.cold,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryOutcome {
+    // Корректный ответ на наш вопрос
+    // truncated - ответ не влез в UDP (TC), полный нужно запрашивать по TCP
+    Answered { latency: Duration, answers: Vec<IpAddr>, truncated: bool },
+    // Сервер ответил, но ответ негодный (SERVFAIL, REFUSED, мусор)
+    Invalid { latency: Duration, reason: String },
+    Timeout,
+    Failed(String),
+}
+
+impl QueryOutcome {
+    // Разбор ответа, пришедшего по любому транспорту
+    pub fn from_message(message: &[u8], id: u16, name: &str, record_type: RecordType, latency: Duration) -> Self {
+        match parse_response(message, id, name, record_type) {
+            Ok(response) if response.is_valid_answer() => {
+                QueryOutcome::Answered { latency, answers: response.answers, truncated: response.truncated }
+            }
+            Ok(response) => QueryOutcome::Invalid { latency, reason: format!("RCODE {}", response.rcode) },
+            Err(reason) => QueryOutcome::Invalid { latency, reason },
+        }
+    }
+}
+
+// Один запрос по UDP; ответы с чужим ID игнорируются до истечения таймаута
+pub async fn query_udp(server: SocketAddr, name: &str, record_type: RecordType, timeout: Duration) -> QueryOutcome {
+    let bind_address: SocketAddr = if server.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }
+        .parse()
+        .expect("адрес привязки корректен");
+    let socket = match UdpSocket::bind(bind_address).await {
+        Ok(socket) => socket,
+        Err(e) => return QueryOutcome::Failed(e.to_string()),
+    };
+    if let Err(e) = socket.connect(server).await {
+        return QueryOutcome::Failed(e.to_string());
+    }
+
+    let id = random_id();
+    let query = build_query(id, name, record_type);
+    let started = Instant::now();
+    if let Err(e) = socket.send(&query).await {
+        return QueryOutcome::Failed(e.to_string());
+    }
+
+    let deadline = tokio::time::Instant::from_std(started + timeout);
+    let mut buffer = [0u8; 4096];
+    loop {
+        let length = match tokio::time::timeout_at(deadline, socket.recv(&mut buffer)).await {
+            Ok(Ok(length)) => length,
+            Err(_) => return QueryOutcome::Timeout,
+            // ICMP port unreachable приходит как ConnectionRefused - сервера на этом адресе нет
+            Ok(Err(e)) => return QueryOutcome::Failed(e.to_string()),
+        };
+        let latency = started.elapsed();
+
+        // Запоздавший ответ на прошлый запрос - ждем свой
+        if length >= 2 && buffer[..2] != id.to_be_bytes() {
+            continue;
+        }
+        return QueryOutcome::from_message(&buffer[..length], id, name, record_type, latency);
+    }
+}
+
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// Адрес без порта - стандартный порт транспорта
+pub fn socket_address(server: &str, default_port: u16) -> Result<SocketAddr, String> {
+    server
+        .parse::<SocketAddr>()
+        .or_else(|_| server.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, default_port)))
+        .map_err(|_| format!("некорректный адрес: {}", server))
+}
+
+// Задержки корректных ответов в порядке отправки и число таймаутов
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<f64>,
+    timeouts: usize,
+}
+
+// Уникальная метка, которой точно нет ни в одном кэше
+fn random_label() -> String {
+    format!("dnsm-{:016x}", RandomState::new().build_hasher().finish())
+}
+
+// Замер одного сервера: на каждом шаге один запрос популярного имени (кэш)
+// и один - случайного поддомена настоящей зоны (промах кэша); A и AAAA чередуются
+async fn measure_server(server: &str, config: &BenchmarkConfig) -> (ServerResult, Samples, Samples) {
+    let mut result = ServerResult { server: server.to_string(), ..Default::default() };
+    let mut warm = Samples::default();
+    let mut cold = Samples::default();
+
+    let address = match socket_address(server, DNS_PORT) {
+        Ok(address) => address,
+        Err(e) => {
+            result.error = Some(e);
+            return (result, warm, cold);
+        }
+    };
+
+    for index in 0..config.warmup + config.samples {
+        let (warm_name, record_type) = warm_question(index);
+        let measured = index >= config.warmup;
+        let cold_name = format!("{}.{}", random_label(), COLD_ZONES[index % COLD_ZONES.len()]);
+
+        for (name, samples) in [(warm_name.to_string(), &mut warm), (cold_name, &mut cold)] {
+            match query_udp(address, &name, record_type, config.timeout).await {
+                QueryOutcome::Answered { latency, .. } => {
+                    result.reachable = true;
+                    if measured {
+                        samples.latencies.push(millis(latency));
+                    }
+                }
+                QueryOutcome::Invalid { reason, .. } => {
+                    result.reachable = true;
+                    result.error = Some(format!("{} {}: {}", record_type.label(), name, reason));
+                }
+                QueryOutcome::Timeout => {
+                    samples.timeouts += measured as usize;
+                    result.error = Some(format!("{} {}: нет ответа", record_type.label(), name));
+                }
+                QueryOutcome::Failed(e) => result.error = Some(format!("{} {}: {}", record_type.label(), name, e)),
+            }
+        }
+    }
+
+    result.warm = LatencyStats::from_samples(&warm.latencies, config.samples, warm.timeouts);
+    result.cold = LatencyStats::from_samples(&cold.latencies, config.samples, cold.timeouts);
+    (result, warm, cold)
+}
+
+// Проход по набору: сначала config.warmup запросов из начала расписания (не учитываются),
+// затем каждое имя столько раз, каков его вес. Возвращает замеры по каждому имени набора
+async fn replay_workload(server: &str, workload: &Workload, config: &BenchmarkConfig) -> Vec<Samples> {
+    let mut samples: Vec<Samples> = workload.entries.iter().map(|_| Samples::default()).collect();
+    let Ok(address) = socket_address(server, DNS_PORT) else {
+        for (entry, samples) in workload.entries.iter().zip(&mut samples) {
+            samples.timeouts = entry.weight as usize;
+        }
+        return samples;
+    };
+
+    let schedule = workload.schedule();
+    for &index in schedule.iter().cycle().take(config.warmup) {
+        let entry = &workload.entries[index];
+        let _ = query_udp(address, &entry.name, entry.record_type, config.timeout).await;
+    }
+
+    for &index in &schedule {
+        let entry = &workload.entries[index];
+        match query_udp(address, &entry.name, entry.record_type, config.timeout).await {
+            QueryOutcome::Answered { latency, .. } => samples[index].latencies.push(millis(latency)),
+            QueryOutcome::Timeout => samples[index].timeouts += 1,
+            QueryOutcome::Invalid { .. } | QueryOutcome::Failed(_) => {}
+        }
+    }
+    samples
+}
+
+async fn measure_workload(provider: &DNSProvider, workload: &Workload, config: &BenchmarkConfig) -> WorkloadResult {
+    let primary = replay_workload(&provider.primary, workload, config).await;
+    let secondary = replay_workload(&provider.secondary, workload, config).await;
+
+    let mut all = Samples::default();
+    let domains = workload
+        .entries
+        .iter()
+        .zip(primary.into_iter().zip(secondary))
+        .map(|(entry, (mut primary, secondary))| {
+            primary.latencies.extend(secondary.latencies);
+            primary.timeouts += secondary.timeouts;
+            all.latencies.extend(&primary.latencies);
+            all.timeouts += primary.timeouts;
+
+            DomainResult {
+                name: entry.name.clone(),
+                record_type: entry.record_type,
+                weight: entry.weight,
+                stats: LatencyStats::from_samples(&primary.latencies, entry.weight as usize * 2, primary.timeouts),
+            }
+        })
+        .collect();
+
+    WorkloadResult {
+        source: workload.source.clone(),
+        aggregate: LatencyStats::from_samples(&all.latencies, workload.total_queries() * 2, all.timeouts),
+        domains,
+    }
+}
+
+async fn measure_pair(primary: &str, secondary: &str, config: &BenchmarkConfig) -> ServerPair {
+    let (primary, mut warm, mut cold) = measure_server(primary, config).await;
+    let (secondary, secondary_warm, secondary_cold) = measure_server(secondary, config).await;
+    warm.latencies.extend(secondary_warm.latencies);
+    cold.latencies.extend(secondary_cold.latencies);
+
+    let sent = config.samples * 2;
+    ServerPair {
+        primary,
+        secondary,
+        warm: LatencyStats::from_samples(&warm.latencies, sent, warm.timeouts + secondary_warm.timeouts),
+        cold: LatencyStats::from_samples(&cold.latencies, sent, cold.timeouts + secondary_cold.timeouts),
+    }
+}
+
+pub async fn test_provider(provider: &DNSProvider, config: &BenchmarkConfig, workload: Option<&Workload>) -> SpeedTestResult {
+    // Обычного DNS у сервера из штампа DNSCrypt или DoH может не быть - по UDP и TCP его не замеряем
+    let plain = !provider.encrypted_only;
+    let ipv4 = if plain {
+        measure_pair(&provider.primary, &provider.secondary, config).await
+    } else {
+        let skipped = ServerResult {
+            server: provider.primary.clone(),
+            error: Some("обычный DNS не заявлен - только зашифрованный протокол".to_string()),
+            ..Default::default()
+        };
+        ServerPair { primary: skipped.clone(), secondary: skipped, ..Default::default() }
+    };
+
+    // Без резервного IPv6 основной замеряется дважды, как у опорных резолверов с одним сервером
+    let ipv6 = match (&provider.primary_v6, config.ipv6) {
+        (Some(primary), true) => {
+            Some(measure_pair(primary, provider.secondary_v6.as_deref().unwrap_or(primary), config).await)
+        }
+        _ => None,
+    };
+
+    let doh = match (&provider.doh, config.doh) {
+        (Some(url), true) => Some(doh::measure(url, config, doh::web_tls()).await),
+        _ => None,
+    };
+    let dot = match (&provider.dot, config.dot) {
+        (Some(auth_name), true) => Some(dot::measure(&provider.primary, auth_name, config, dot::web_tls()).await),
+        _ => None,
+    };
+    let doq = match (&provider.doq, config.doq) {
+        (Some(auth_name), true) => Some(doq::measure(&provider.primary, auth_name, config, doq::web_tls()).await),
+        _ => None,
+    };
+
+    let tcp = if config.tcp && plain { Some(tcp::measure(&provider.primary, config).await) } else { None };
+
+    let workload = match workload {
+        Some(workload) if plain => Some(measure_workload(provider, workload, config).await),
+        _ => None,
+    };
+
+    SpeedTestResult {
+        provider: provider.name.clone(),
+        baseline: false,
+        primary: ipv4.primary,
+        secondary: ipv4.secondary,
+        warm: ipv4.warm,
+        cold: ipv4.cold,
+        doh,
+        dot,
+        doq,
+        tcp,
+        ipv6,
+        workload,
+    }
+}
+
+enum SpeedTestEvent {
+    // Сколько опорных резолверов нашлось - они собираются уже внутри замера
+    Baselines(usize),
+    Started(String),
+    Measured(Box<SpeedTestResult>),
+}
+
+// Фоновый замер всех провайдеров. Runtime принадлежит замеру:
+// отмена останавливает его вместе со всеми запросами, не дожидаясь таймаутов
+pub struct SpeedTestRun {
+    runtime: Option<Runtime>,
+    events: UnboundedReceiver<SpeedTestEvent>,
+    pub total: usize,
+    pub completed: usize,
+    // Провайдеры, которые замеряются прямо сейчас
+    pub running: Vec<String>,
+    // Опорные резолверы еще собираются, и total пока их не учитывает
+    baselines_pending: bool,
+}
+
+impl SpeedTestRun {
+    // baselines собирает уже настроенные в системе резолверы - они замеряются вместе с провайдерами.
+    // Сбор может обращаться к ОС, поэтому он идет в фоне, а не в потоке UI
+    pub fn start(
+        providers: Vec<DNSProvider>,
+        baselines: impl FnOnce() -> Vec<DNSProvider> + Send + 'static,
+        config: BenchmarkConfig,
+        workload: Option<Arc<Workload>>,
+    ) -> io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("dns-speed-test")
+            .enable_all()
+            .build()?;
+        let (sender, events) = mpsc::unbounded_channel();
+        let total = providers.len();
+
+        runtime.spawn(run_providers(providers, baselines, config, workload, sender));
+
+        Ok(Self { runtime: Some(runtime), events, total, completed: 0, running: Vec::new(), baselines_pending: true })
+    }
+
+    // Забирает готовые результаты, не блокируя кадр
+    pub fn poll(&mut self) -> Vec<SpeedTestResult> {
+        let mut results = Vec::new();
+        loop {
+            match self.events.try_recv() {
+                Ok(SpeedTestEvent::Baselines(count)) => {
+                    self.total += count;
+                    self.baselines_pending = false;
+                }
+                Ok(SpeedTestEvent::Started(provider)) => self.running.push(provider),
+                Ok(SpeedTestEvent::Measured(result)) => {
+                    self.running.retain(|provider| *provider != result.provider);
+                    self.completed += 1;
+                    results.push(*result);
+                }
+                Err(TryRecvError::Empty) => break,
+                // Все задачи завершились (или упали) - ждать больше нечего
+                Err(TryRecvError::Disconnected) => {
+                    self.running.clear();
+                    self.baselines_pending = false;
+                    self.completed = self.total;
+                    break;
+                }
+            }
+        }
+        results
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.baselines_pending && self.completed >= self.total
+    }
+
+    pub fn cancel(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+impl Drop for SpeedTestRun {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+// Не больше config.parallelism провайдеров одновременно; серверы одного провайдера - по очереди
+async fn run_providers(
+    providers: Vec<DNSProvider>,
+    baselines: impl FnOnce() -> Vec<DNSProvider> + Send + 'static,
+    config: BenchmarkConfig,
+    workload: Option<Arc<Workload>>,
+    sender: UnboundedSender<SpeedTestEvent>,
+) {
+    let baselines = tokio::task::spawn_blocking(baselines).await.unwrap_or_default();
+    let _ = sender.send(SpeedTestEvent::Baselines(baselines.len()));
+
+    let limit = Arc::new(Semaphore::new(config.parallelism.max(1)));
+    let mut tasks = JoinSet::new();
+
+    let targets = baselines.into_iter().map(|provider| (provider, true));
+    for (provider, baseline) in targets.chain(providers.into_iter().map(|provider| (provider, false))) {
+        let limit = limit.clone();
+        let sender = sender.clone();
+        let workload = workload.clone();
+        tasks.spawn(async move {
+            let Ok(_permit) = limit.acquire_owned().await else {
+                return;
+            };
+            let _ = sender.send(SpeedTestEvent::Started(provider.name.clone()));
+            let result = SpeedTestResult { baseline, ..test_provider(&provider, &config, workload.as_deref()).await };
+            let _ = sender.send(SpeedTestEvent::Measured(Box::new(result)));
+        });
+    }
+
+    while tasks.join_next().await.is_some() {}
+}
+
+// Лучшие по выбранной статистике сверху, провайдеры без ответов - в конце
+pub fn rank_results(results: &mut [SpeedTestResult], statistic: RankingStatistic, cache: CacheState) {
+    results.sort_by(|a, b| match (statistic.value(a.stats(cache)), statistic.value(b.stats(cache))) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+}
+
+// Опорный резолвер - самый быстрый из уже настроенных: провайдер должен обогнать лучший из них
+pub fn reference_baseline(
+    results: &[SpeedTestResult],
+    statistic: RankingStatistic,
+    cache: CacheState,
+) -> Option<&SpeedTestResult> {
+    results
+        .iter()
+        .filter(|result| result.baseline)
+        .filter_map(|result| statistic.value(result.stats(cache)).map(|value| (result, value)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(result, _)| result)
+}
+
+// На сколько процентов провайдер быстрее (> 0) или медленнее (< 0) опорного резолвера
+pub fn relative_to_baseline(
+    result: &SpeedTestResult,
+    baseline: &SpeedTestResult,
+    statistic: RankingStatistic,
+    cache: CacheState,
+) -> Option<f64> {
+    let value = statistic.value(result.stats(cache))?;
+    let reference = statistic.value(baseline.stats(cache))?;
+    // С нулем (например, без потерь) сравнивать в процентах нечего
+    if reference <= 0.0 {
+        return None;
+    }
+    Some((reference - value) * 100.0 / reference)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::test_server::block_on;
+    use crate::dns::wire::tests::{answer_for, question_name, question_type};
+    use crate::dns::wire::RCODE_NOERROR;
+    use std::net::UdpSocket as StdUdpSocket;
+    use std::thread;
+
+    fn provider(name: &str, primary: SocketAddr, secondary: SocketAddr) -> DNSProvider {
+        DNSProvider {
+            name: name.to_string(),
+            primary: primary.to_string(),
+            secondary: secondary.to_string(),
+            description: String::new(),
+            doh: None,
+            dot: None,
+            doq: None,
+            primary_v6: None,
+            secondary_v6: None,
+            dnssec: false,
+            no_logs: false,
+            dnscrypt: None,
+            cert_hashes: Vec::new(),
+            encrypted_only: false,
+            filtering: None,
+            ecs: None,
+            logging_policy: None,
+        }
+    }
+
+    // Локальный UDP сервер, который отвечает на один запрос через respond
+    fn serve_once(respond: impl FnOnce(&[u8]) -> Vec<u8> + Send + 'static) -> SocketAddr {
+        let socket = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            let (length, peer) = socket.recv_from(&mut buffer).unwrap();
+            socket.send_to(&respond(&buffer[..length]), peer).unwrap();
+        });
+        address
+    }
+
+    #[test]
+    fn valid_answer_is_timed() {
+        let address: IpAddr = "93.184.216.34".parse().unwrap();
+        let server = serve_once(move |query| answer_for(query, RCODE_NOERROR, &[address]));
+
+        match block_on(query_udp(server, WARM_DOMAINS[0], RecordType::A, QUERY_TIMEOUT)) {
+            QueryOutcome::Answered { answers, .. } => assert_eq!(answers, vec![address]),
+            other => panic!("ожидался ответ, получено {:?}", other),
+        }
+    }
+
+    #[test]
+    fn refused_answer_is_reachable_but_invalid() {
+        let server = serve_once(|query| answer_for(query, 5, &[]));
+        assert!(matches!(
+            block_on(query_udp(server, WARM_DOMAINS[0], RecordType::A, QUERY_TIMEOUT)),
+            QueryOutcome::Invalid { reason, .. } if reason == "RCODE 5"
+        ));
+    }
+
+    #[test]
+    fn statistics_use_nearest_rank_percentiles() {
+        let latencies = [12.0, 10.0, 30.0, 11.0, 14.0, 13.0, 10.0, 15.0, 12.0, 11.0];
+        let stats = LatencyStats::from_samples(&latencies, 12, 2);
+
+        assert_eq!(stats.min, 10.0);
+        assert_eq!(stats.median, 12.0);
+        assert_eq!(stats.p90, 15.0);
+        assert_eq!(stats.p95, 30.0);
+        assert_eq!(stats.max, 30.0);
+        assert!((stats.mean - 13.8).abs() < 1e-9);
+        assert!((stats.loss_percent() - 100.0 * 2.0 / 12.0).abs() < 1e-9);
+        // |10-12| + |30-10| + |11-30| + ... по соседним запросам
+        assert!((stats.jitter - 57.0 / 9.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ranking_puts_silent_providers_last() {
+        let provider = |name: &str, latencies: &[f64]| SpeedTestResult {
+            provider: name.to_string(),
+            warm: LatencyStats::from_samples(latencies, 4, 4 - latencies.len()),
+            ..Default::default()
+        };
+        let mut results = vec![
+            provider("silent", &[]),
+            provider("spiky", &[5.0, 5.0, 5.0, 90.0]),
+            provider("steady", &[20.0, 21.0, 20.0, 22.0]),
+        ];
+
+        rank_results(&mut results, RankingStatistic::Median, CacheState::Warm);
+        assert_eq!(results.iter().map(|r| r.provider.as_str()).collect::<Vec<_>>(), ["spiky", "steady", "silent"]);
+
+        rank_results(&mut results, RankingStatistic::P95, CacheState::Warm);
+        assert_eq!(results.iter().map(|r| r.provider.as_str()).collect::<Vec<_>>(), ["steady", "spiky", "silent"]);
+    }
+
+    #[test]
+    fn providers_are_compared_with_the_fastest_baseline() {
+        let result = |name: &str, baseline: bool, latencies: &[f64]| SpeedTestResult {
+            provider: name.to_string(),
+            baseline,
+            warm: LatencyStats::from_samples(latencies, latencies.len().max(1), 0),
+            ..Default::default()
+        };
+        let results = vec![
+            result("Текущие DNS", true, &[40.0]),
+            result("DNS адаптера eth0", true, &[50.0]),
+            result("офлайн", true, &[]),
+            result("fast", false, &[10.0]),
+            result("slow", false, &[60.0]),
+        ];
+
+        let baseline = reference_baseline(&results, RankingStatistic::Median, CacheState::Warm).unwrap();
+        assert_eq!(baseline.provider, "Текущие DNS");
+        let relative = |index: usize| relative_to_baseline(&results[index], baseline, RankingStatistic::Median, CacheState::Warm);
+        assert_eq!(relative(3), Some(75.0));
+        assert_eq!(relative(4), Some(-50.0));
+        assert_eq!(relative(2), None);
+        // Потерь у опорного нет - процент от нуля не считаем
+        assert_eq!(relative_to_baseline(&results[3], baseline, RankingStatistic::Loss, CacheState::Warm), None);
+    }
+
+    #[test]
+    fn silent_server_times_out() {
+        // Сокет открыт, но никто не отвечает
+        let silent = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let outcome =
+            block_on(query_udp(silent.local_addr().unwrap(), WARM_DOMAINS[0], RecordType::A, Duration::from_millis(200)));
+        assert_eq!(outcome, QueryOutcome::Timeout);
+    }
+
+    // Ждет завершения замера, но не дольше limit
+    fn wait_finished(run: &mut SpeedTestRun, limit: Duration) -> Vec<SpeedTestResult> {
+        let started = Instant::now();
+        let mut results = Vec::new();
+        while !run.is_finished() && started.elapsed() < limit {
+            results.extend(run.poll());
+            thread::sleep(Duration::from_millis(10));
+        }
+        results
+    }
+
+    #[test]
+    fn providers_are_measured_concurrently() {
+        let silent = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = silent.local_addr().unwrap();
+        let providers = (0..4).map(|i| provider(&format!("silent-{}", i), address, address)).collect();
+        let config = BenchmarkConfig { samples: 1, warmup: 0, timeout: Duration::from_millis(150), parallelism: 4, ..Default::default() };
+
+        // По очереди это 4 провайдера * 2 сервера * 2 запроса * 150ms = 2.4s
+        let started = Instant::now();
+        let mut run = SpeedTestRun::start(providers, Vec::new, config, None).unwrap();
+        let results = wait_finished(&mut run, Duration::from_secs(5));
+
+        assert!(run.is_finished());
+        assert!(started.elapsed() < Duration::from_millis(1500), "замер занял {:?}", started.elapsed());
+        assert_eq!(results.len(), 4);
+        assert!(results.iter().all(|result| result.warm.timeouts == 2 && result.cold.timeouts == 2));
+        assert!(results.iter().all(|result| !result.warm.has_answers() && !result.cold.has_answers()));
+    }
+
+    #[test]
+    fn baselines_are_collected_off_the_calling_thread() {
+        let silent = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = silent.local_addr().unwrap();
+        let config = BenchmarkConfig { samples: 1, warmup: 0, timeout: Duration::from_millis(100), ..Default::default() };
+
+        let caller = thread::current().id();
+        let baselines = move || {
+            // Чтение текущих DNS может долго ждать ОС - UI в это время не должен стоять
+            assert_ne!(thread::current().id(), caller);
+            thread::sleep(Duration::from_millis(200));
+            vec![provider("📌 Текущие DNS", address, address)]
+        };
+
+        let started = Instant::now();
+        let mut run = SpeedTestRun::start(vec![provider("silent", address, address)], baselines, config, None).unwrap();
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert!(!run.is_finished());
+
+        let results = wait_finished(&mut run, Duration::from_secs(5));
+        assert_eq!(run.total, 2);
+        assert_eq!(results.iter().filter(|result| result.baseline).count(), 1);
+    }
+
+    #[test]
+    fn cancel_stops_pending_queries_immediately() {
+        let silent = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = silent.local_addr().unwrap();
+        let providers = (0..3).map(|i| provider(&format!("silent-{}", i), address, address)).collect();
+        let config = BenchmarkConfig { timeout: Duration::from_secs(30), parallelism: 1, ..Default::default() };
+
+        let mut run = SpeedTestRun::start(providers, Vec::new, config, None).unwrap();
+        let started = Instant::now();
+        while run.running.is_empty() && started.elapsed() < Duration::from_secs(5) {
+            run.poll();
+            thread::sleep(Duration::from_millis(10));
+        }
+        // Лимит параллельности: второй провайдер ждет, пока не освободится первый
+        assert_eq!(run.running.len(), 1);
+
+        let cancelled = Instant::now();
+        run.cancel();
+        assert!(cancelled.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn cold_queries_use_unique_subdomains_of_real_zones() {
+        let socket = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+        let (names, received) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            while let Ok((length, peer)) = socket.recv_from(&mut buffer) {
+                let query = &buffer[..length];
+                let _ = names.send((question_name(query), question_type(query)));
+                socket.send_to(&answer_for(query, RCODE_NOERROR, &[]), peer).unwrap();
+            }
+        });
+
+        let config = BenchmarkConfig { samples: 7, warmup: 1, ..Default::default() };
+        let (result, warm, cold) = block_on(measure_server(&address.to_string(), &config));
+        let questions: Vec<(String, u16)> = received.try_iter().collect();
+
+        assert_eq!((warm.latencies.len(), cold.latencies.len()), (7, 7));
+        assert_eq!((result.warm.answered, result.cold.answered), (7, 7));
+
+        let (cold_questions, warm_questions): (Vec<_>, Vec<_>) = questions.iter().partition(|(name, _)| name.starts_with("dnsm-"));
+        assert_eq!(warm_questions.len(), 8);
+        // Каждое популярное имя спрошено и как A, и как AAAA
+        for domain in WARM_DOMAINS {
+            for record_type in [RecordType::A, RecordType::Aaaa] {
+                assert!(warm_questions.contains(&&(domain.to_string(), record_type.code())), "{} {}", record_type.label(), domain);
+            }
+        }
+        let cold_names: Vec<&String> = cold_questions.iter().map(|(name, _)| name).collect();
+        assert_eq!(cold_names.len(), 8);
+        assert!(cold_names.iter().all(|name| COLD_ZONES.iter().any(|zone| name.ends_with(&format!(".{}", zone)))));
+        let unique: std::collections::HashSet<_> = cold_names.iter().collect();
+        assert_eq!(unique.len(), cold_names.len());
+    }
+
+    #[test]
+    fn ipv6_servers_are_measured_separately() {
+        let answering = |bind: &str| {
+            let socket = StdUdpSocket::bind(bind).unwrap();
+            let address = socket.local_addr().unwrap();
+            thread::spawn(move || {
+                let mut buffer = [0u8; 512];
+                while let Ok((length, peer)) = socket.recv_from(&mut buffer) {
+                    socket.send_to(&answer_for(&buffer[..length], RCODE_NOERROR, &[]), peer).unwrap();
+                }
+            });
+            address
+        };
+        let ipv4 = answering("127.0.0.1:0");
+        let ipv6 = answering("[::1]:0");
+        let provider = DNSProvider { primary_v6: Some(ipv6.to_string()), ..provider("local", ipv4, ipv4) };
+        let config = BenchmarkConfig { samples: 2, warmup: 0, doh: false, dot: false, doq: false, tcp: false, ..Default::default() };
+
+        let result = block_on(test_provider(&provider, &config, None));
+        let measured = result.ipv6.unwrap();
+        // Без резервного IPv6 основной замеряется за оба
+        assert_eq!((measured.primary.server.as_str(), measured.secondary.server.as_str()), (ipv6.to_string().as_str(), ipv6.to_string().as_str()));
+        assert_eq!((measured.warm.answered, measured.cold.answered), (4, 4));
+        assert_eq!(result.warm.answered, 4);
+
+        let disabled = block_on(test_provider(&provider, &BenchmarkConfig { ipv6: false, ..config }, None));
+        assert!(disabled.ipv6.is_none());
+
+        // Адрес из штампа DNSCrypt или DoH по обычному DNS не опрашивается, даже если отвечает
+        let encrypted = DNSProvider { encrypted_only: true, primary_v6: None, ..provider };
+        let skipped = block_on(test_provider(&encrypted, &BenchmarkConfig { tcp: true, ..config }, None));
+        assert!(!skipped.primary.reachable && skipped.primary.error.is_some());
+        assert_eq!(skipped.warm.answered, 0);
+        assert!(skipped.tcp.is_none());
+    }
+
+    #[test]
+    fn workload_is_replayed_per_domain() {
+        let socket = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap();
+        let (names, received) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            while let Ok((length, peer)) = socket.recv_from(&mut buffer) {
+                let query = &buffer[..length];
+                let name = question_name(query);
+                // На второе имя набора не отвечаем
+                if name != "slow.example.com" {
+                    socket.send_to(&answer_for(query, RCODE_NOERROR, &[]), peer).unwrap();
+                }
+                let _ = names.send(name);
+            }
+        });
+
+        let workload = Workload::parse("traffic.csv", "app.example.com,A,3\nslow.example.com,AAAA,1\n").unwrap();
+        let config = BenchmarkConfig { warmup: 1, timeout: Duration::from_millis(150), ..Default::default() };
+        let result = block_on(measure_workload(&provider("local", address, address), &workload, &config));
+        let names: Vec<String> = received.try_iter().collect();
+
+        // Разогрев - по одному запросу на сервер, затем 4 запроса набора
+        assert_eq!(names.len(), 2 * (1 + 4));
+        assert_eq!(result.source, "traffic.csv");
+        assert_eq!((result.domains[0].stats.sent, result.domains[0].stats.answered), (6, 6));
+        assert_eq!((result.domains[1].stats.sent, result.domains[1].stats.timeouts), (2, 2));
+        assert_eq!((result.aggregate.sent, result.aggregate.answered), (8, 6));
+    }
+}
@@ -0,0 +1,398 @@
+// DNS Stamps (https://dnscrypt.info/stamps-specifications): "sdns://" и base64url без '='
+// от двоичной записи - протокол, 8 байт свойств и поля с длиной впереди.
+// Штамп описывает один сервер по одному протоколу, поэтому провайдер экспортируется
+// несколькими штампами - по одному на каждый транспорт
+
+use std::net::{IpAddr, SocketAddr};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use http::Uri;
+
+use super::providers::{DNSProvider, DnsCrypt, Filtering, Transport, DNSCRYPT_PORT};
+use super::speed_test::DNS_PORT;
+
+pub const SCHEME: &str = "sdns://";
+
+const HTTPS_PORT: u16 = 443;
+const TLS_PORT: u16 = 853;
+
+// Биты свойств сервера, little-endian
+const PROP_DNSSEC: u64 = 1;
+const PROP_NO_LOGS: u64 = 1 << 1;
+const PROP_NO_FILTER: u64 = 1 << 2;
+
+// Первый байт штампа
+fn protocol_id(transport: Transport) -> u8 {
+    match transport {
+        Transport::Plain => 0x00,
+        Transport::DnsCrypt => 0x01,
+        Transport::Doh => 0x02,
+        Transport::Dot => 0x03,
+        Transport::Doq => 0x04,
+    }
+}
+
+fn transport_of(id: u8) -> Result<Transport, String> {
+    Transport::ALL.into_iter().find(|transport| protocol_id(*transport) == id).ok_or_else(|| match id {
+        0x05 | 0x81 | 0x85 => format!("штампы Oblivious DoH и релеев (0x{:02x}) не поддерживаются", id),
+        _ => format!("неизвестный протокол штампа: 0x{:02x}", id),
+    })
+}
+
+// Штамп -> провайдер. У штампа нет имени: берем имя сервера из штампа
+pub fn parse(stamp: &str) -> Result<DNSProvider, String> {
+    let encoded = stamp.trim().strip_prefix(SCHEME).ok_or_else(|| format!("штамп должен начинаться с {}", SCHEME))?;
+    let data = URL_SAFE_NO_PAD
+        .decode(encoded.trim_end_matches('='))
+        .map_err(|e| format!("штамп не в base64url: {}", e))?;
+
+    let mut reader = Reader { data: &data, position: 0 };
+    let kind = transport_of(reader.byte()?)?;
+    let props = u64::from_le_bytes(reader.take(8)?.try_into().expect("ровно 8 байт"));
+    let address = reader.string()?;
+
+    let mut provider = DNSProvider {
+        name: String::new(),
+        primary: String::new(),
+        secondary: String::new(),
+        description: format!("Импортирован из DNS Stamp ({})", kind.label()),
+        doh: None,
+        dot: None,
+        doq: None,
+        primary_v6: None,
+        secondary_v6: None,
+        dnssec: props & PROP_DNSSEC != 0,
+        no_logs: props & PROP_NO_LOGS != 0,
+        dnscrypt: None,
+        cert_hashes: Vec::new(),
+        encrypted_only: false,
+        // Штамп говорит только, фильтрует ли резолвер, но не что именно
+        filtering: (props & PROP_NO_FILTER != 0).then_some(Filtering::Unfiltered),
+        ecs: None,
+        logging_policy: None,
+    };
+
+    let ip = match kind {
+        Transport::Plain => {
+            let ip = server_address(&address, DNS_PORT, kind)?;
+            provider.name = format!("DNS {}", ip);
+            ip
+        }
+        Transport::DnsCrypt => {
+            let (ip, port) = split_address(&address, DNSCRYPT_PORT)?;
+            let public_key = reader.field()?;
+            if public_key.len() != 32 {
+                return Err(format!("открытый ключ DNSCrypt - {} байт вместо 32", public_key.len()));
+            }
+            let public_key = encode_hex(public_key);
+            let provider_name = reader.string()?;
+            provider.name = provider_name.trim_start_matches("2.dnscrypt-cert.").to_string();
+            provider.dnscrypt = Some(DnsCrypt { provider_name, public_key, port });
+            provider.encrypted_only = true;
+            ip
+        }
+        Transport::Doh => {
+            provider.cert_hashes = reader.hashes()?;
+            let host = reader.string()?;
+            let path = reader.string()?;
+            let (name, port) = split_host(&host, HTTPS_PORT)?;
+            let ip = server_address(&address, port, kind)?;
+            provider.name = name.to_string();
+            provider.doh = Some(format!("https://{}{}", host, path));
+            provider.encrypted_only = true;
+            ip
+        }
+        Transport::Dot | Transport::Doq => {
+            provider.cert_hashes = reader.hashes()?;
+            let host = reader.string()?;
+            let (name, port) = split_host(&host, TLS_PORT)?;
+            if port != TLS_PORT {
+                return Err(format!("{}: порт {} не поддерживается, только {}", kind.label(), port, TLS_PORT));
+            }
+            let ip = server_address(&address, TLS_PORT, kind)?;
+            provider.name = name.to_string();
+            if kind == Transport::Dot {
+                provider.dot = Some(name.to_string());
+            } else {
+                provider.doq = Some(name.to_string());
+            }
+            ip
+        }
+    };
+    // Дальше могут идти адреса bootstrap резолверов - нам они не нужны
+
+    // Сервер в штампе один - он же и резервный, как у опорных резолверов с одним адресом.
+    // У DNSCrypt и DoH это адрес зашифрованного протокола, а не обычного DNS - см. encrypted_only
+    provider.primary = ip.to_string();
+    provider.secondary = ip.to_string();
+    Ok(provider)
+}
+
+// Провайдер -> штамп нужного протокола на основном адресе
+pub fn export(provider: &DNSProvider, kind: Transport) -> Result<String, String> {
+    let ip: IpAddr = provider.primary.parse().map_err(|_| format!("основной DNS - не IP адрес: {}", provider.primary))?;
+    let missing = || format!("у провайдера {} нет {}", provider.name, kind.label());
+
+    let unfiltered = provider.filtering == Some(Filtering::Unfiltered);
+    let mut props = 0;
+    for (flag, bit) in [(provider.dnssec, PROP_DNSSEC), (provider.no_logs, PROP_NO_LOGS), (unfiltered, PROP_NO_FILTER)] {
+        if flag {
+            props |= bit;
+        }
+    }
+    let mut data = vec![protocol_id(kind)];
+    data.extend_from_slice(&props.to_le_bytes());
+
+    match kind {
+        Transport::Plain if provider.encrypted_only => return Err(missing()),
+        Transport::Plain => push_field(&mut data, join_address(ip, None).as_bytes())?,
+        Transport::DnsCrypt => {
+            let dnscrypt = provider.dnscrypt.as_ref().ok_or_else(missing)?;
+            let port = (dnscrypt.port != DNSCRYPT_PORT).then_some(dnscrypt.port);
+            push_field(&mut data, join_address(ip, port).as_bytes())?;
+            push_field(&mut data, &decode_hex(&dnscrypt.public_key)?)?;
+            push_field(&mut data, dnscrypt.provider_name.as_bytes())?;
+        }
+        Transport::Doh => {
+            let url = provider.doh.as_ref().ok_or_else(missing)?;
+            let uri: Uri = url.parse().map_err(|e| format!("некорректный адрес DoH {}: {}", url, e))?;
+            let authority = uri.authority().ok_or_else(|| format!("в адресе DoH нет сервера: {}", url))?;
+            push_field(&mut data, join_address(ip, authority.port_u16()).as_bytes())?;
+            push_hashes(&mut data, &provider.cert_hashes)?;
+            push_field(&mut data, authority.as_str().as_bytes())?;
+            push_field(&mut data, uri.path_and_query().map(|path| path.as_str()).unwrap_or("/").as_bytes())?;
+        }
+        Transport::Dot | Transport::Doq => {
+            let name = if kind == Transport::Dot { &provider.dot } else { &provider.doq };
+            let name = name.as_ref().ok_or_else(missing)?;
+            push_field(&mut data, join_address(ip, None).as_bytes())?;
+            push_hashes(&mut data, &provider.cert_hashes)?;
+            push_field(&mut data, name.as_bytes())?;
+        }
+    }
+
+    Ok(format!("{}{}", SCHEME, URL_SAFE_NO_PAD.encode(data)))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self.data.get(self.position..self.position + length).ok_or("штамп обрезан")?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    // LP: байт длины, затем само поле
+    fn field(&mut self) -> Result<&'a [u8], String> {
+        let length = self.byte()? as usize;
+        self.take(length)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        String::from_utf8(self.field()?.to_vec()).map_err(|_| "поле штампа не в UTF-8".to_string())
+    }
+
+    // VLP: у всех полей, кроме последнего, в байте длины выставлен старший бит.
+    // Без хэшей список состоит из одного пустого поля
+    fn hashes(&mut self) -> Result<Vec<String>, String> {
+        let mut hashes = Vec::new();
+        loop {
+            let length = self.byte()?;
+            match self.take((length & 0x7f) as usize)? {
+                [] => {}
+                hash if hash.len() == 32 => hashes.push(encode_hex(hash)),
+                hash => return Err(format!("хэш сертификата - {} байт вместо 32", hash.len())),
+            }
+            if length & 0x80 == 0 {
+                return Ok(hashes);
+            }
+        }
+    }
+}
+
+fn push_field(data: &mut Vec<u8>, field: &[u8]) -> Result<(), String> {
+    let length = u8::try_from(field.len()).map_err(|_| format!("поле штампа длиннее 255 байт: {}", field.len()))?;
+    data.push(length);
+    data.extend_from_slice(field);
+    Ok(())
+}
+
+fn push_hashes(data: &mut Vec<u8>, hashes: &[String]) -> Result<(), String> {
+    if hashes.is_empty() {
+        data.push(0);
+        return Ok(());
+    }
+    for (index, hash) in hashes.iter().enumerate() {
+        let hash = decode_hex(hash).ok().filter(|hash| hash.len() == 32).ok_or_else(|| format!("хэш сертификата - не SHA-256 в hex: {}", hash))?;
+        let more = if index + 1 < hashes.len() { 0x80 } else { 0 };
+        data.push(hash.len() as u8 | more);
+        data.extend_from_slice(&hash);
+    }
+    Ok(())
+}
+
+// "1.2.3.4", "1.2.3.4:5353", "[2001:db8::1]" или "[2001:db8::1]:5353"
+fn split_address(address: &str, default_port: u16) -> Result<(IpAddr, u16), String> {
+    if address.is_empty() {
+        return Err("в штампе нет IP адреса сервера - добавьте провайдера вручную".to_string());
+    }
+    if let Ok(socket) = address.parse::<SocketAddr>() {
+        return Ok((socket.ip(), socket.port()));
+    }
+    let ip = address.trim_start_matches('[').trim_end_matches(']');
+    ip.parse().map(|ip| (ip, default_port)).map_err(|_| format!("некорректный адрес в штампе: {}", address))
+}
+
+// Адрес, на котором сервер работает на стандартном порту протокола
+fn server_address(address: &str, port: u16, kind: Transport) -> Result<IpAddr, String> {
+    match split_address(address, port)? {
+        (ip, actual) if actual == port => Ok(ip),
+        (_, actual) => Err(format!("{}: порт {} не поддерживается, только {}", kind.label(), actual, port)),
+    }
+}
+
+fn join_address(ip: IpAddr, port: Option<u16>) -> String {
+    match (ip, port) {
+        (IpAddr::V4(ip), None) => ip.to_string(),
+        (IpAddr::V6(ip), None) => format!("[{}]", ip),
+        (ip, Some(port)) => SocketAddr::new(ip, port).to_string(),
+    }
+}
+
+// Имя сервера и порт из "dns.example" или "dns.example:8443"
+fn split_host(host: &str, default_port: u16) -> Result<(&str, u16), String> {
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.contains(':') => {
+            Ok((name, port.parse().map_err(|_| format!("некорректный порт в имени сервера: {}", host))?))
+        }
+        _ if host.is_empty() => Err("в штампе нет имени сервера".to_string()),
+        _ => Ok((host, default_port)),
+    }
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn decode_hex(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.replace(':', "");
+    if !text.len().is_multiple_of(2) || !text.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("ключ должен быть в hex: {}", text));
+    }
+    Ok((0..text.len()).step_by(2).map(|index| u8::from_str_radix(&text[index..index + 2], 16).expect("проверено выше")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Штамп из публичного списка dnscrypt-proxy (public-resolvers.md)
+    const CLOUDFLARE_DOH: &str = "sdns://AgcAAAAAAAAABzEuMC4wLjEAEmRucy5jbG91ZGZsYXJlLmNvbQovZG5zLXF1ZXJ5";
+
+    fn stamp(kind: Transport, props: u64, fields: &[&[u8]]) -> String {
+        let mut data = vec![protocol_id(kind)];
+        data.extend_from_slice(&props.to_le_bytes());
+        for field in fields {
+            data.push(field.len() as u8);
+            data.extend_from_slice(field);
+        }
+        format!("{}{}", SCHEME, URL_SAFE_NO_PAD.encode(data))
+    }
+
+    #[test]
+    fn published_doh_stamp_is_imported() {
+        let provider = parse(CLOUDFLARE_DOH).unwrap();
+
+        assert_eq!(provider.name, "dns.cloudflare.com");
+        assert_eq!((provider.primary.as_str(), provider.secondary.as_str()), ("1.0.0.1", "1.0.0.1"));
+        assert_eq!(provider.doh.as_deref(), Some("https://dns.cloudflare.com/dns-query"));
+        assert!(provider.dnssec && provider.no_logs);
+        assert_eq!(provider.filtering, Some(Filtering::Unfiltered));
+        assert_eq!(export(&provider, Transport::Doh).unwrap(), CLOUDFLARE_DOH);
+
+        // Адрес из штампа DoH обещает только HTTPS - системным DNS по нему не ставим
+        assert!(provider.encrypted_only);
+        assert_eq!(provider.transports(), vec![Transport::Doh]);
+        assert!(export(&provider, Transport::Plain).is_err());
+    }
+
+    #[test]
+    fn every_stamp_type_round_trips() {
+        let key = [0xd1u8; 32];
+        let hashless: &[u8] = &[];
+        // Один хэш сертификата - то же поле с длиной впереди, без бита продолжения
+        let hash = [0x11u8; 32];
+        let stamps = [
+            (Transport::Plain, stamp(Transport::Plain, PROP_DNSSEC, &[b"9.9.9.9"])),
+            (Transport::Plain, stamp(Transport::Plain, 0, &[b"[2620:fe::fe]"])),
+            (
+                Transport::DnsCrypt,
+                stamp(Transport::DnsCrypt, PROP_NO_LOGS, &[b"185.228.168.168:8443", &key, b"cleanbrowsing.org"]),
+            ),
+            (Transport::DnsCrypt, stamp(Transport::DnsCrypt, 0, &[b"94.140.14.14", &key, b"2.dnscrypt-cert.example.org"])),
+            (
+                Transport::Doh,
+                stamp(Transport::Doh, PROP_DNSSEC | PROP_NO_FILTER, &[b"[2606:4700::1111]:8443", hashless, b"doh.example:8443", b"/q?x=1"]),
+            ),
+            (Transport::Dot, stamp(Transport::Dot, PROP_NO_FILTER, &[b"8.8.8.8", hashless, b"dns.google"])),
+            (Transport::Doq, stamp(Transport::Doq, PROP_DNSSEC | PROP_NO_LOGS, &[b"94.140.14.14", hashless, b"dns.adguard-dns.com"])),
+            (Transport::Doh, stamp(Transport::Doh, 0, &[b"1.1.1.1", &hash, b"doh.example", b"/dns-query"])),
+            (Transport::Dot, stamp(Transport::Dot, 0, &[b"1.1.1.1", &hash, b"dot.example"])),
+        ];
+
+        for (kind, original) in stamps {
+            let provider = parse(&original).unwrap_or_else(|e| panic!("{:?} {}: {}", kind, original, e));
+            assert!(provider.transports().contains(&kind), "{:?}", provider);
+            assert_eq!(export(&provider, kind).unwrap(), original, "{:?}", provider);
+        }
+    }
+
+    #[test]
+    fn imported_fields_are_filled() {
+        let key = [0xabu8; 32];
+        let dnscrypt = parse(&stamp(Transport::DnsCrypt, 0, &[b"185.228.168.168:8443", &key, b"2.dnscrypt-cert.cleanbrowsing.org"])).unwrap();
+        assert_eq!(dnscrypt.name, "cleanbrowsing.org");
+        assert_eq!(
+            dnscrypt.dnscrypt,
+            Some(DnsCrypt { provider_name: "2.dnscrypt-cert.cleanbrowsing.org".to_string(), public_key: "ab".repeat(32), port: 8443 })
+        );
+        // Сервер DNSCrypt на 8443 не обещает обычного DNS на 53
+        assert_eq!(dnscrypt.transports(), vec![Transport::DnsCrypt]);
+
+        // Хэши сертификатов (VLP) сохраняются в hex
+        let mut data = vec![protocol_id(Transport::Dot)];
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.extend_from_slice(b"\x071.1.1.1\xa0");
+        data.extend_from_slice(&[0xaa; 32]);
+        data.push(0x20);
+        data.extend_from_slice(&[0xbb; 32]);
+        data.extend_from_slice(b"\x0fone.one.one.one");
+        let original = format!("{}{}", SCHEME, URL_SAFE_NO_PAD.encode(data));
+        let dot = parse(&original).unwrap();
+        assert_eq!((dot.primary.as_str(), dot.dot.as_deref()), ("1.1.1.1", Some("one.one.one.one")));
+        assert_eq!(dot.cert_hashes, vec!["aa".repeat(32), "bb".repeat(32)]);
+        assert_eq!(dot.transports(), vec![Transport::Plain, Transport::Dot]);
+        assert_eq!(export(&dot, Transport::Dot).unwrap(), original);
+    }
+
+    #[test]
+    fn broken_stamps_are_rejected() {
+        assert!(parse("https://dns.example").unwrap_err().starts_with("штамп должен"));
+        assert!(parse("sdns://!!!").unwrap_err().starts_with("штамп не в base64url"));
+        assert_eq!(parse(&CLOUDFLARE_DOH[..43]).unwrap_err(), "штамп обрезан");
+        assert!(parse(&stamp(Transport::Plain, 0, &[b"9.9.9.9:5353"])).unwrap_err().contains("порт 5353"));
+        assert!(parse(&stamp(Transport::Doh, 0, &[b"", b"", b"doh.example", b"/dns-query"])).unwrap_err().contains("нет IP"));
+        assert!(parse(&stamp(Transport::DnsCrypt, 0, &[b"1.2.3.4", b"short", b"x"])).unwrap_err().contains("32"));
+        assert!(parse(&stamp(Transport::Dot, 0, &[b"1.1.1.1", b"short", b"dns.example"])).unwrap_err().contains("хэш"));
+        assert!(parse("sdns://BQcAAAAAAAAAAA").unwrap_err().contains("Oblivious"));
+    }
+}
@@ -0,0 +1,298 @@
+// DNS по TCP/53 (RFC 7766): то же сообщение, что по UDP, но с двухбайтовой длиной впереди.
+// Клиент уходит на TCP, когда ответ не влез в UDP и пришел с флагом TC (RFC 1035, 4.2.1) -
+// так бывает с DNSSEC и большими TXT. Часть резолверов держит TCP плохо или не держит вовсе
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use super::speed_test::{
+    millis, query_udp, socket_address, warm_question, BenchmarkConfig, LatencyStats, QueryOutcome, DNS_PORT,
+};
+use super::wire::{build_query, random_id, RecordType};
+
+// Вопрос с заведомо большим ответом: TXT google.com не влезает в 512 байт UDP без EDNS
+pub const TRUNCATION_QUESTION: (&str, RecordType) = ("google.com", RecordType::Txt);
+
+pub struct TcpConnection {
+    address: SocketAddr,
+    // None - прошлый запрос оборвался, следующий откроет новое соединение
+    stream: Option<TcpStream>,
+    // Время установки соединения, в миллисекундах
+    pub connect: f64,
+}
+
+impl TcpConnection {
+    pub async fn connect(address: SocketAddr) -> Result<Self, String> {
+        let started = Instant::now();
+        let stream = open_stream(address).await?;
+        Ok(Self { address, stream: Some(stream), connect: millis(started.elapsed()) })
+    }
+
+    // Ответы с чужим ID пропускаются до истечения таймаута, как и по UDP
+    pub async fn query(&mut self, name: &str, record_type: RecordType, timeout: Duration) -> QueryOutcome {
+        let id = random_id();
+        let message = build_query(id, name, record_type);
+        let mut frame = (message.len() as u16).to_be_bytes().to_vec();
+        frame.extend_from_slice(&message);
+
+        // Переподключение в задержку запроса не входит
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => match tokio::time::timeout(timeout, open_stream(self.address)).await {
+                Ok(Ok(stream)) => self.stream.insert(stream),
+                Ok(Err(e)) => return QueryOutcome::Failed(e),
+                Err(_) => return QueryOutcome::Timeout,
+            },
+        };
+
+        let started = Instant::now();
+        if let Err(e) = stream.write_all(&frame).await {
+            self.stream = None;
+            return QueryOutcome::Failed(e.to_string());
+        }
+
+        let deadline = tokio::time::Instant::from_std(started + timeout);
+        loop {
+            // Таймаут может прервать чтение посреди кадра, и дальше поток уже не разобрать -
+            // после любой ошибки соединение закрывается
+            let message = match tokio::time::timeout_at(deadline, read_message(stream)).await {
+                Ok(Ok(message)) => message,
+                Ok(Err(e)) => {
+                    self.stream = None;
+                    return QueryOutcome::Failed(e);
+                }
+                Err(_) => {
+                    self.stream = None;
+                    return QueryOutcome::Timeout;
+                }
+            };
+            if message.len() >= 2 && message[..2] != id.to_be_bytes() {
+                continue;
+            }
+            return QueryOutcome::from_message(&message, id, name, record_type, started.elapsed());
+        }
+    }
+}
+
+async fn open_stream(address: SocketAddr) -> Result<TcpStream, String> {
+    let stream = TcpStream::connect(address).await.map_err(|e| format!("TCP {}: {}", address, e))?;
+    let _ = stream.set_nodelay(true);
+    Ok(stream)
+}
+
+async fn read_message(stream: &mut TcpStream) -> Result<Vec<u8>, String> {
+    let length = stream.read_u16().await.map_err(|e| format!("соединение закрыто: {}", e))?;
+    let mut message = vec![0; length as usize];
+    stream.read_exact(&mut message).await.map_err(|e| format!("ответ обрезан: {}", e))?;
+    Ok(message)
+}
+
+// Переход с UDP на TCP после обрезанного ответа
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FallbackResult {
+    // Сервер поставил TC; false - ответ влез в UDP и переход проверить не удалось
+    pub truncated: bool,
+    // От запроса по UDP до полного ответа по новому TCP соединению, в мс;
+    // None - по TCP ответа не получили
+    pub total: Option<f64>,
+    pub error: Option<String>,
+}
+
+impl FallbackResult {
+    pub fn works(&self) -> bool {
+        self.truncated && self.total.is_some()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TcpResult {
+    pub server: String,
+    // None - соединение установить не удалось
+    pub connect: Option<f64>,
+    // Запросы по одному соединению, каждый после ответа на предыдущий
+    pub queries: LatencyStats,
+    pub fallback: Option<FallbackResult>,
+    pub error: Option<String>,
+}
+
+// Как клиент без EDNS: UDP, при TC - тот же вопрос по новому TCP соединению
+pub async fn fallback(address: SocketAddr, timeout: Duration) -> FallbackResult {
+    let (name, record_type) = TRUNCATION_QUESTION;
+    let started = Instant::now();
+
+    match query_udp(address, name, record_type, timeout).await {
+        QueryOutcome::Answered { truncated: true, .. } => {}
+        QueryOutcome::Answered { .. } => return FallbackResult::default(),
+        QueryOutcome::Invalid { reason, .. } => return FallbackResult { error: Some(reason), ..Default::default() },
+        QueryOutcome::Timeout => return FallbackResult { error: Some("нет ответа по UDP".to_string()), ..Default::default() },
+        QueryOutcome::Failed(e) => return FallbackResult { error: Some(e), ..Default::default() },
+    }
+
+    let remaining = timeout.saturating_sub(started.elapsed());
+    let outcome = match tokio::time::timeout(remaining, TcpConnection::connect(address)).await {
+        Ok(Ok(mut connection)) => connection.query(name, record_type, timeout.saturating_sub(started.elapsed())).await,
+        Ok(Err(e)) => QueryOutcome::Failed(e),
+        Err(_) => QueryOutcome::Timeout,
+    };
+
+    match outcome {
+        QueryOutcome::Answered { truncated: false, .. } => {
+            FallbackResult { truncated: true, total: Some(millis(started.elapsed())), error: None }
+        }
+        QueryOutcome::Answered { .. } => {
+            FallbackResult { truncated: true, total: None, error: Some("по TCP ответ тоже обрезан".to_string()) }
+        }
+        QueryOutcome::Invalid { reason, .. } => FallbackResult { truncated: true, total: None, error: Some(reason) },
+        QueryOutcome::Timeout => {
+            FallbackResult { truncated: true, total: None, error: Some("нет ответа по TCP".to_string()) }
+        }
+        QueryOutcome::Failed(e) => FallbackResult { truncated: true, total: None, error: Some(e) },
+    }
+}
+
+pub async fn measure(server: &str, config: &BenchmarkConfig) -> TcpResult {
+    let mut result = TcpResult { server: server.to_string(), ..Default::default() };
+
+    let address = match socket_address(server, DNS_PORT) {
+        Ok(address) => address,
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+
+    let mut connection = match tokio::time::timeout(config.timeout, TcpConnection::connect(address)).await {
+        Ok(Ok(connection)) => connection,
+        Ok(Err(e)) => {
+            result.error = Some(e);
+            return result;
+        }
+        Err(_) => {
+            result.error = Some(format!("соединение не установлено за {:?}", config.timeout));
+            return result;
+        }
+    };
+    result.connect = Some(connection.connect);
+
+    let mut latencies = Vec::new();
+    let mut timeouts = 0;
+    for index in 0..config.warmup + config.samples {
+        let (name, record_type) = warm_question(index);
+        let outcome = connection.query(name, record_type, config.timeout).await;
+        if index < config.warmup {
+            continue;
+        }
+        match outcome {
+            QueryOutcome::Answered { latency, .. } => latencies.push(millis(latency)),
+            QueryOutcome::Invalid { reason, .. } => result.error = Some(reason),
+            QueryOutcome::Timeout => {
+                timeouts += 1;
+                result.error = Some("нет ответа".to_string());
+            }
+            QueryOutcome::Failed(e) => result.error = Some(e),
+        }
+    }
+    result.queries = LatencyStats::from_samples(&latencies, config.samples, timeouts);
+    result.fallback = Some(fallback(address, config.timeout).await);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::test_server::{block_on, frame, read_frame, spawn_server};
+    use crate::dns::wire::tests::{answer_for, question_name};
+    use crate::dns::wire::RCODE_NOERROR;
+    use tokio::net::UdpSocket;
+
+    // Локальный резолвер на одном порту: по UDP на TXT отвечает с TC, по TCP - полным ответом.
+    // tcp = false - TCP не слушается вовсе, как у резолвера, который его не поддерживает
+    async fn spawn_resolver(tcp: bool) -> SocketAddr {
+        let address = if tcp {
+            spawn_server(None, |_, mut stream| async move {
+                while let Some(query) = read_frame(&mut stream).await {
+                    stream.write_all(&frame(&answer_for(&query, RCODE_NOERROR, &[]))).await.unwrap();
+                }
+            })
+            .await
+        } else {
+            "127.0.0.1:0".parse().unwrap()
+        };
+        let udp = UdpSocket::bind(address).await.unwrap();
+        let address = udp.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 512];
+            while let Ok((length, peer)) = udp.recv_from(&mut buffer).await {
+                let query = &buffer[..length];
+                let mut answer = answer_for(query, RCODE_NOERROR, &[]);
+                if question_name(query) == TRUNCATION_QUESTION.0 {
+                    answer[2] |= 0x02;
+                }
+                udp.send_to(&answer, peer).await.unwrap();
+            }
+        });
+
+        address
+    }
+
+    #[test]
+    fn truncated_udp_answer_is_retried_over_tcp() {
+        block_on(async {
+            let address = spawn_resolver(true).await;
+            let config = BenchmarkConfig { samples: 3, warmup: 1, ..Default::default() };
+            let result = measure(&address.to_string(), &config).await;
+
+            assert_eq!(result.error, None);
+            assert!(result.connect.is_some());
+            assert_eq!(result.queries.answered, 3);
+            let fallback = result.fallback.unwrap();
+            assert!(fallback.works(), "{:?}", fallback);
+        });
+    }
+
+    #[test]
+    fn timed_out_frame_does_not_desync_the_next_query() {
+        block_on(async {
+            // Первый ответ приходит половиной кадра: длина и пара байт, остальное - уже после таймаута
+            let address = spawn_server(None, |index, mut stream| async move {
+                while let Some(query) = read_frame(&mut stream).await {
+                    let frame = frame(&answer_for(&query, RCODE_NOERROR, &[]));
+                    if index == 0 {
+                        let _ = stream.write_all(&frame[..4]).await;
+                        tokio::time::sleep(Duration::from_millis(300)).await;
+                        let _ = stream.write_all(&frame[4..]).await;
+                    } else {
+                        stream.write_all(&frame).await.unwrap();
+                    }
+                }
+            })
+            .await;
+
+            let mut connection = TcpConnection::connect(address).await.unwrap();
+            let timeout = Duration::from_millis(100);
+            assert!(matches!(connection.query("example.com", RecordType::A, timeout).await, QueryOutcome::Timeout));
+
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            let outcome = connection.query("example.org", RecordType::A, timeout).await;
+            assert!(matches!(outcome, QueryOutcome::Answered { .. }), "{:?}", outcome);
+        });
+    }
+
+    #[test]
+    fn fallback_fails_without_tcp() {
+        block_on(async {
+            // Порт TCP, на котором никто не слушает: UDP ответит с TC, а повторить негде
+            let address = spawn_resolver(false).await;
+            let fallback = fallback(address, Duration::from_secs(2)).await;
+
+            assert!(fallback.truncated);
+            assert!(!fallback.works());
+            assert!(fallback.error.unwrap().starts_with("TCP "));
+        });
+    }
+}
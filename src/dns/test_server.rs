@@ -0,0 +1,68 @@
+// Общее для тестов протоколов: рантайм и локальный сервер на TCP или TLS
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use rustls::ServerConfig;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+// Два рабочих потока: локальный сервер отвечает, пока тест ждет ответа
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_multi_thread().worker_threads(2).enable_all().build().unwrap().block_on(future)
+}
+
+// Соединение с клиентом - TCP или TLS поверх него
+pub(crate) trait ServerStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> ServerStream for T {}
+
+// Слушает localhost (по TLS, если передан tls) и отдает каждое соединение handler вместе с его номером с нуля
+pub(crate) async fn spawn_server<H, F>(tls: Option<Arc<ServerConfig>>, handler: H) -> SocketAddr
+where
+    H: Fn(usize, Box<dyn ServerStream>) -> F + Send + Sync + 'static,
+    F: Future<Output = ()> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let acceptor = tls.map(TlsAcceptor::from);
+    let handler = Arc::new(handler);
+
+    tokio::spawn(async move {
+        let mut index = 0;
+        while let Ok((tcp, _)) = listener.accept().await {
+            let acceptor = acceptor.clone();
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                let stream: Box<dyn ServerStream> = match acceptor {
+                    // Клиент, не доверяющий сертификату, обрывает рукопожатие
+                    Some(acceptor) => match acceptor.accept(tcp).await {
+                        Ok(stream) => Box::new(stream),
+                        Err(_) => return,
+                    },
+                    None => Box::new(tcp),
+                };
+                handler(index, stream).await;
+            });
+            index += 1;
+        }
+    });
+
+    address
+}
+
+// Сообщение с двухбайтовой длиной впереди, как в DNS по TCP и DoT; None - клиент закрыл соединение
+pub(crate) async fn read_frame(stream: &mut (impl AsyncRead + Unpin + ?Sized)) -> Option<Vec<u8>> {
+    let length = stream.read_u16().await.ok()?;
+    let mut message = vec![0; length as usize];
+    stream.read_exact(&mut message).await.ok()?;
+    Some(message)
+}
+
+pub(crate) fn frame(message: &[u8]) -> Vec<u8> {
+    let mut frame = (message.len() as u16).to_be_bytes().to_vec();
+    frame.extend_from_slice(message);
+    frame
+}
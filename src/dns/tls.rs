@@ -0,0 +1,100 @@
+// Общие настройки TLS для зашифрованных транспортов DNS (DoH, DoT, DoQ)
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, RootCertStore};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::TlsConnector;
+
+use super::speed_test::millis;
+
+// Стадии установки соединения, в миллисекундах
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConnectionSetup {
+    pub tcp_connect: f64,
+    // Включая согласование протокола через ALPN
+    pub tls_handshake: f64,
+}
+
+impl ConnectionSetup {
+    pub fn total(&self) -> f64 {
+        self.tcp_connect + self.tls_handshake
+    }
+}
+
+// Корневые сертификаты Mozilla - не зависим от хранилища системы
+pub fn web_roots() -> RootCertStore {
+    RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() }
+}
+
+// alpn - протокол, который согласуем в рукопожатии (h2 для DoH, doq для DoQ)
+pub fn client_config(roots: RootCertStore, alpn: &[&[u8]]) -> Arc<ClientConfig> {
+    let mut config = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .expect("ring поддерживает TLS 1.2 и 1.3")
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+    Arc::new(config)
+}
+
+pub fn server_name(host: &str) -> Result<ServerName<'static>, String> {
+    ServerName::try_from(host.to_string()).map_err(|e| format!("некорректное имя сервера {}: {}", host, e))
+}
+
+// TCP и TLS по отдельности, чтобы было видно, что из них дороже.
+// Сертификат проверяется по name, а не по адресу, к которому подключаемся
+pub async fn connect(
+    address: SocketAddr,
+    name: &str,
+    config: Arc<ClientConfig>,
+) -> Result<(TlsStream<TcpStream>, ConnectionSetup), String> {
+    let server_name = server_name(name)?;
+
+    let started = Instant::now();
+    let tcp = TcpStream::connect(address).await.map_err(|e| format!("TCP {}: {}", address, e))?;
+    let _ = tcp.set_nodelay(true);
+    let tcp_connect = started.elapsed();
+
+    let started = Instant::now();
+    let stream = TlsConnector::from(config).connect(server_name, tcp).await.map_err(|e| format!("TLS {}: {}", name, e))?;
+    let tls_handshake = started.elapsed();
+
+    Ok((stream, ConnectionSetup { tcp_connect: millis(tcp_connect), tls_handshake: millis(tls_handshake) }))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+    use rustls::ServerConfig;
+
+    // Самоподписанный сертификат на name: настройки локального сервера и клиента, который ему доверяет
+    pub(crate) fn self_signed(name: &str, alpn: &[&[u8]]) -> (Arc<ServerConfig>, Arc<ClientConfig>) {
+        let (server, roots) = self_signed_roots(name, alpn);
+        (Arc::new(server), client_config(roots, alpn))
+    }
+
+    // То же, но корни отдельно - для клиентов со своими настройками поверх TLS
+    pub(crate) fn self_signed_roots(name: &str, alpn: &[&[u8]]) -> (ServerConfig, RootCertStore) {
+        let certified = rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap();
+        let certificate: CertificateDer<'static> = certified.cert.der().clone();
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()));
+
+        let mut server = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![certificate.clone()], key)
+            .unwrap();
+        server.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(certificate).unwrap();
+        (server, roots)
+    }
+}
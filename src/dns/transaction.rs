@@ -0,0 +1,229 @@
+// Применение DNS с автоматическим откатом: снимок прежних серверов берется из плана,
+// после применения проверяем резолвинг и связь, и если проверка не прошла
+// или пользователь не подтвердил изменения до конца отсчета - возвращаем снимок
+
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::backend::DnsBackend;
+use super::error::{AdapterChange, ApplyReport, DnsError};
+use super::plan::ChangePlan;
+use crate::network::adapters::AdapterSelection;
+
+// Сколько ждать подтверждения, как в окне "Сохранить параметры дисплея?"
+pub const CONFIRM_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Имена для проверки - хотя бы одно должно разрешиться и ответить по TCP
+const PROBE_HOSTS: [&str; 2] = ["example.com:443", "cloudflare.com:443"];
+const PROBE_CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProbeState {
+    Running,
+    // Время разрешения имени и подключения
+    Passed(Duration),
+    Failed(String),
+}
+
+// Почему изменения откатываются
+#[derive(Clone, Debug, PartialEq)]
+pub enum RevertReason {
+    ProbeFailed(String),
+    NotConfirmed,
+    // Пользователь нажал "Откатить"
+    Requested,
+}
+
+pub struct Transaction {
+    // Состояние адаптеров до применения
+    pub snapshot: ChangePlan,
+    pub probe: ProbeState,
+    deadline: Instant,
+    probe_result: Option<Receiver<Result<Duration, String>>>,
+}
+
+impl Transaction {
+    // Проверка идет в отдельном потоке, чтобы отсчет в UI не замирал
+    pub fn start(snapshot: ChangePlan) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(connectivity_probe());
+        });
+        Self::with_probe(snapshot, receiver, Instant::now() + CONFIRM_TIMEOUT)
+    }
+
+    pub fn with_probe(snapshot: ChangePlan, probe_result: Receiver<Result<Duration, String>>, deadline: Instant) -> Self {
+        Self { snapshot, probe: ProbeState::Running, deadline, probe_result: Some(probe_result) }
+    }
+
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.deadline.saturating_duration_since(now)
+    }
+
+    // Забирает результат проверки и решает, пора ли откатывать
+    pub fn poll(&mut self, now: Instant) -> Option<RevertReason> {
+        if let Some(receiver) = &self.probe_result {
+            match receiver.try_recv() {
+                Ok(Ok(elapsed)) => self.probe = ProbeState::Passed(elapsed),
+                Ok(Err(e)) => self.probe = ProbeState::Failed(e),
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => {
+                    self.probe = ProbeState::Failed("проверка прервалась".to_string());
+                }
+            }
+            if self.probe != ProbeState::Running {
+                self.probe_result = None;
+            }
+        }
+
+        match &self.probe {
+            ProbeState::Failed(e) => Some(RevertReason::ProbeFailed(e.clone())),
+            _ if now >= self.deadline => Some(RevertReason::NotConfirmed),
+            _ => None,
+        }
+    }
+}
+
+// Разрешаем имя через системный резолвер (то есть через новые DNS) и подключаемся к нему
+pub fn connectivity_probe() -> Result<Duration, String> {
+    let started = Instant::now();
+    let mut errors = Vec::new();
+
+    for host in PROBE_HOSTS {
+        let addresses: Vec<SocketAddr> = match host.to_socket_addrs() {
+            Ok(addresses) => addresses.collect(),
+            Err(e) => {
+                errors.push(format!("{}: {}", host, e));
+                continue;
+            }
+        };
+
+        for address in &addresses {
+            match TcpStream::connect_timeout(address, PROBE_CONNECT_TIMEOUT) {
+                Ok(_) => return Ok(started.elapsed()),
+                Err(e) => errors.push(format!("{}: {}", address, e)),
+            }
+        }
+    }
+
+    Err(errors.join("; "))
+}
+
+// Возвращает каждый адаптер к снимку: серверы от DHCP или пустой список - сброс,
+// иначе прежние серверы, заданные вручную
+pub fn revert(backend: &dyn DnsBackend, snapshot: &ChangePlan) -> Result<ApplyReport, DnsError> {
+    let mut changes = Vec::new();
+
+    for adapter in &snapshot.adapters {
+        let selection = AdapterSelection::Names(vec![adapter.interface.clone()]);
+        let result = match adapter.current.as_slice() {
+            _ if adapter.from_dhcp => backend.reset_dns(&selection),
+            [] => backend.reset_dns(&selection),
+            servers => backend.set_dns(&selection, servers),
+        };
+
+        match result {
+            Ok(report) | Err(DnsError::PartialSuccess(report)) => changes.extend(report.changes),
+            Err(e) => changes.push(AdapterChange::err(&adapter.adapter, e)),
+        }
+    }
+
+    ApplyReport::new(backend.name(), changes).into_result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::plan::AdapterPlan;
+    use std::sync::Mutex;
+
+    // Запоминает вызовы вместо изменения системы
+    #[derive(Default)]
+    struct RecordingBackend {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl DnsBackend for RecordingBackend {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+
+        fn get_current_dns(&self) -> Result<String, DnsError> {
+            Ok(String::new())
+        }
+
+        fn set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ApplyReport, DnsError> {
+            self.calls.lock().unwrap().push(format!("set {:?} {}", selection, servers.join(" ")));
+            Ok(ApplyReport::new(self.name(), vec![AdapterChange::ok("a", "set")]))
+        }
+
+        fn reset_dns(&self, selection: &AdapterSelection) -> Result<ApplyReport, DnsError> {
+            self.calls.lock().unwrap().push(format!("reset {:?}", selection));
+            Ok(ApplyReport::new(self.name(), vec![AdapterChange::ok("a", "reset")]))
+        }
+
+        fn plan_set_dns(&self, _selection: &AdapterSelection, _servers: &[String]) -> Result<ChangePlan, DnsError> {
+            Ok(ChangePlan::default())
+        }
+
+        fn plan_reset_dns(&self, _selection: &AdapterSelection) -> Result<ChangePlan, DnsError> {
+            Ok(ChangePlan::default())
+        }
+
+        fn flush_cache(&self) -> Result<ApplyReport, DnsError> {
+            Ok(ApplyReport::default())
+        }
+    }
+
+    #[test]
+    fn revert_restores_previous_servers_or_dhcp() {
+        let snapshot = ChangePlan::new(
+            "recording",
+            vec![
+                AdapterPlan::set("eth0", vec!["192.168.1.1".to_string(), "fd00::1".to_string()], &[], Vec::new()),
+                AdapterPlan { interface: "wlan0".to_string(), ..AdapterPlan::set("Home (wlan0)", Vec::new(), &[], Vec::new()) },
+            ],
+            Vec::new(),
+        );
+
+        let backend = RecordingBackend::default();
+        let report = revert(&backend, &snapshot).unwrap();
+
+        assert_eq!(report.changes.len(), 2);
+        assert_eq!(
+            *backend.calls.lock().unwrap(),
+            vec!["set Names([\"eth0\"]) 192.168.1.1 fd00::1", "reset Names([\"wlan0\"])"]
+        );
+    }
+
+    #[test]
+    fn dhcp_servers_are_reverted_by_reset_not_pinned() {
+        let dhcp = AdapterPlan::set("Ethernet", vec!["192.168.1.1".to_string()], &[], Vec::new()).with_dhcp_source(true);
+        let snapshot = ChangePlan::new("recording", vec![dhcp], Vec::new());
+
+        let backend = RecordingBackend::default();
+        revert(&backend, &snapshot).unwrap();
+
+        assert_eq!(*backend.calls.lock().unwrap(), vec!["reset Names([\"Ethernet\"])"]);
+    }
+
+    #[test]
+    fn failed_probe_reverts_immediately_and_silence_reverts_at_deadline() {
+        let now = Instant::now();
+
+        let (sender, receiver) = mpsc::channel();
+        let mut failing = Transaction::with_probe(ChangePlan::default(), receiver, now + CONFIRM_TIMEOUT);
+        assert_eq!(failing.poll(now), None);
+        sender.send(Err("nxdomain".to_string())).unwrap();
+        assert_eq!(failing.poll(now), Some(RevertReason::ProbeFailed("nxdomain".to_string())));
+
+        let (sender, receiver) = mpsc::channel();
+        let mut passing = Transaction::with_probe(ChangePlan::default(), receiver, now + CONFIRM_TIMEOUT);
+        sender.send(Ok(Duration::from_millis(40))).unwrap();
+        assert_eq!(passing.poll(now), None);
+        assert_eq!(passing.probe, ProbeState::Passed(Duration::from_millis(40)));
+        assert_eq!(passing.poll(now + CONFIRM_TIMEOUT), Some(RevertReason::NotConfirmed));
+    }
+}
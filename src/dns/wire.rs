@@ -0,0 +1,289 @@
+// Формат DNS сообщений (RFC 1035): сборка запроса и проверка ответа.
+// Один и тот же формат идет по UDP/TCP, DoH, DoT и DoQ - отличается только транспорт
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const HEADER_LEN: usize = 12;
+const CLASS_IN: u16 = 1;
+const TYPE_CNAME: u16 = 5;
+
+// Флаги заголовка
+const FLAG_QR: u16 = 0x8000;
+const FLAG_TC: u16 = 0x0200;
+const FLAG_RD: u16 = 0x0100;
+const OPCODE_MASK: u16 = 0x7800;
+const RCODE_MASK: u16 = 0x000F;
+
+pub const RCODE_NOERROR: u8 = 0;
+pub const RCODE_NXDOMAIN: u8 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RecordType {
+    A,
+    Aaaa,
+    Ns,
+    Cname,
+    Mx,
+    Txt,
+    Https,
+}
+
+impl RecordType {
+    pub const ALL: [RecordType; 7] = [
+        RecordType::A,
+        RecordType::Aaaa,
+        RecordType::Ns,
+        RecordType::Cname,
+        RecordType::Mx,
+        RecordType::Txt,
+        RecordType::Https,
+    ];
+
+    pub fn code(self) -> u16 {
+        match self {
+            RecordType::A => 1,
+            RecordType::Aaaa => 28,
+            RecordType::Ns => 2,
+            RecordType::Cname => TYPE_CNAME,
+            RecordType::Mx => 15,
+            RecordType::Txt => 16,
+            RecordType::Https => 65,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RecordType::A => "A",
+            RecordType::Aaaa => "AAAA",
+            RecordType::Ns => "NS",
+            RecordType::Cname => "CNAME",
+            RecordType::Mx => "MX",
+            RecordType::Txt => "TXT",
+            RecordType::Https => "HTTPS",
+        }
+    }
+
+    pub fn parse(label: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|record_type| record_type.label().eq_ignore_ascii_case(label.trim()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub rcode: u8,
+    pub truncated: bool,
+    // Адреса запрошенного типа (CNAME по пути пропускаются); для остальных типов пусто
+    pub answers: Vec<IpAddr>,
+}
+
+impl Response {
+    // NXDOMAIN и пустой NOERROR - тоже корректные ответы резолвера
+    pub fn is_valid_answer(&self) -> bool {
+        self.rcode == RCODE_NOERROR || self.rcode == RCODE_NXDOMAIN
+    }
+}
+
+// Случайный ID, чтобы не принять чужой или запоздавший ответ за свой
+pub fn random_id() -> u16 {
+    RandomState::new().build_hasher().finish() as u16
+}
+
+pub fn build_query(id: u16, name: &str, record_type: RecordType) -> Vec<u8> {
+    let mut message = Vec::with_capacity(HEADER_LEN + name.len() + 6);
+    message.extend_from_slice(&id.to_be_bytes());
+    message.extend_from_slice(&FLAG_RD.to_be_bytes());
+    // Один вопрос, ответов и дополнительных записей нет
+    message.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+
+    for label in name.trim_end_matches('.').split('.').filter(|label| !label.is_empty()) {
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+
+    message.extend_from_slice(&record_type.code().to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    message
+}
+
+// Проверяет, что это ответ именно на наш вопрос, и достает адреса
+pub fn parse_response(message: &[u8], id: u16, name: &str, record_type: RecordType) -> Result<Response, String> {
+    if message.len() < HEADER_LEN {
+        return Err(format!("ответ короче заголовка ({} байт)", message.len()));
+    }
+
+    let response_id = read_u16(message, 0)?;
+    if response_id != id {
+        return Err(format!("чужой ID ответа {} (ждали {})", response_id, id));
+    }
+
+    let flags = read_u16(message, 2)?;
+    if flags & FLAG_QR == 0 {
+        return Err("пришел запрос, а не ответ".to_string());
+    }
+    if flags & OPCODE_MASK != 0 {
+        return Err("неожиданный opcode".to_string());
+    }
+
+    let question_count = read_u16(message, 4)?;
+    let answer_count = read_u16(message, 6)?;
+    if question_count != 1 {
+        return Err(format!("в ответе {} вопросов вместо одного", question_count));
+    }
+
+    let (question_name, mut position) = read_name(message, HEADER_LEN)?;
+    let question_type = read_u16(message, position)?;
+    position += 4;
+    if !question_name.eq_ignore_ascii_case(name.trim_end_matches('.')) || question_type != record_type.code() {
+        return Err(format!("ответ на другой вопрос: {} тип {}", question_name, question_type));
+    }
+
+    let rcode = (flags & RCODE_MASK) as u8;
+    let truncated = flags & FLAG_TC != 0;
+    // Обрезанный ответ (TC) может кончаться посреди записи - его ответы не разбираем,
+    // клиент все равно должен повторить запрос по TCP
+    if truncated {
+        return Ok(Response { rcode, truncated, answers: Vec::new() });
+    }
+
+    let mut answers = Vec::new();
+    for _ in 0..answer_count {
+        let (_, after_name) = read_name(message, position)?;
+        let answer_type = read_u16(message, after_name)?;
+        let data_len = read_u16(message, after_name + 8)? as usize;
+        let data_start = after_name + 10;
+        let data = message
+            .get(data_start..data_start + data_len)
+            .ok_or("запись ответа обрезана")?;
+
+        match (answer_type, data.len()) {
+            (1, 4) if record_type == RecordType::A => {
+                answers.push(IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3])));
+            }
+            (28, 16) if record_type == RecordType::Aaaa => {
+                let octets: [u8; 16] = data.try_into().map_err(|_| "неверная длина AAAA")?;
+                answers.push(IpAddr::V6(Ipv6Addr::from(octets)));
+            }
+            (TYPE_CNAME, _) => {}
+            (1, _) | (28, _) => return Err(format!("запись типа {} неверной длины {}", answer_type, data.len())),
+            _ => {}
+        }
+
+        position = data_start + data_len;
+    }
+
+    Ok(Response { rcode, truncated, answers })
+}
+
+fn read_u16(message: &[u8], position: usize) -> Result<u16, String> {
+    message
+        .get(position..position + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| "ответ обрезан".to_string())
+}
+
+// Имя с поддержкой сжатия (указатели 0xC0); возвращает позицию сразу после имени в исходном месте
+fn read_name(message: &[u8], start: usize) -> Result<(String, usize), String> {
+    let mut labels = Vec::new();
+    let mut position = start;
+    let mut end = None;
+
+    // Защита от зацикленных указателей
+    for _ in 0..128 {
+        let length = *message.get(position).ok_or("имя обрезано")? as usize;
+        match length {
+            0 => {
+                return Ok((labels.join("."), end.unwrap_or(position + 1)));
+            }
+            l if l & 0xC0 == 0xC0 => {
+                let low = *message.get(position + 1).ok_or("указатель обрезан")? as usize;
+                end.get_or_insert(position + 2);
+                position = ((l & 0x3F) << 8) | low;
+            }
+            l if l <= 63 => {
+                let label = message.get(position + 1..position + 1 + l).ok_or("метка обрезана")?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                position += 1 + l;
+            }
+            _ => return Err("неверная длина метки".to_string()),
+        }
+    }
+
+    Err("слишком длинная цепочка указателей в имени".to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Ответ сервера на запрос: заголовок с QR, вопрос из запроса и записи с указателем на имя
+    pub(crate) fn answer_for(query: &[u8], rcode: u8, addresses: &[IpAddr]) -> Vec<u8> {
+        let mut response = query.to_vec();
+        response[2] |= 0x80;
+        response[3] = (response[3] & 0xF0) | rcode;
+        response[6..8].copy_from_slice(&(addresses.len() as u16).to_be_bytes());
+
+        for address in addresses {
+            let (record_type, data) = match address {
+                IpAddr::V4(v4) => (1u16, v4.octets().to_vec()),
+                IpAddr::V6(v6) => (28u16, v6.octets().to_vec()),
+            };
+            response.extend_from_slice(&[0xC0, HEADER_LEN as u8]);
+            response.extend_from_slice(&record_type.to_be_bytes());
+            response.extend_from_slice(&CLASS_IN.to_be_bytes());
+            response.extend_from_slice(&300u32.to_be_bytes());
+            response.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            response.extend_from_slice(&data);
+        }
+        response
+    }
+
+    // Имя из вопроса запроса - чтобы тестовый сервер видел, что у него спросили
+    pub(crate) fn question_name(query: &[u8]) -> String {
+        read_name(query, HEADER_LEN).unwrap().0
+    }
+
+    // Код типа записи из вопроса запроса
+    pub(crate) fn question_type(query: &[u8]) -> u16 {
+        let end = read_name(query, HEADER_LEN).unwrap().1;
+        u16::from_be_bytes([query[end], query[end + 1]])
+    }
+
+    #[test]
+    fn query_round_trips_through_response_parser() {
+        let query = build_query(0x1234, "Example.COM.", RecordType::A);
+        let address: IpAddr = "93.184.216.34".parse().unwrap();
+        let response = parse_response(&answer_for(&query, RCODE_NOERROR, &[address]), 0x1234, "example.com", RecordType::A)
+            .unwrap();
+
+        assert_eq!(response.rcode, RCODE_NOERROR);
+        assert!(!response.truncated);
+        assert_eq!(response.answers, vec![address]);
+    }
+
+    #[test]
+    fn foreign_or_malformed_responses_are_rejected() {
+        let query = build_query(7, "example.com", RecordType::Aaaa);
+        let response = answer_for(&query, RCODE_NOERROR, &[]);
+
+        assert!(parse_response(&response, 8, "example.com", RecordType::Aaaa).is_err());
+        assert!(parse_response(&response, 7, "example.org", RecordType::Aaaa).is_err());
+        assert!(parse_response(&query, 7, "example.com", RecordType::Aaaa).is_err());
+        assert!(parse_response(&response[..20], 7, "example.com", RecordType::Aaaa).is_err());
+    }
+
+    #[test]
+    fn truncated_response_skips_cut_off_records() {
+        let query = build_query(9, "example.com", RecordType::A);
+        let mut response = answer_for(&query, RCODE_NOERROR, &["93.184.216.34".parse().unwrap()]);
+        response[2] |= 0x02;
+        // Запись оборвана на середине, как бывает в UDP ответе с TC
+        response.truncate(response.len() - 2);
+
+        let response = parse_response(&response, 9, "example.com", RecordType::A).unwrap();
+        assert!(response.truncated);
+        assert!(response.answers.is_empty());
+    }
+}
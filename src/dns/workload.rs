@@ -0,0 +1,164 @@
+// Свой набор доменов для замера скорости вместо встроенных популярных имен.
+// Файл - простой список (одно имя на строку) или CSV "домен,тип,вес":
+// тип по умолчанию A, вес - сколько раз имя запрашивается за один проход
+
+use std::fs;
+use std::path::Path;
+
+use super::speed_test::LatencyStats;
+use super::wire::RecordType;
+
+// Защита от опечатки вроде веса 10000 - замер растянулся бы на часы
+pub const MAX_WEIGHT: u32 = 100;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkloadEntry {
+    pub name: String,
+    pub record_type: RecordType,
+    pub weight: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Workload {
+    // Имя файла, из которого загружен набор
+    pub source: String,
+    pub entries: Vec<WorkloadEntry>,
+}
+
+impl Workload {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let source = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        Self::parse(&source, &text)
+    }
+
+    pub fn parse(source: &str, text: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            // Заголовок CSV, если он есть, - первая значимая строка
+            if entries.is_empty() && ["domain", "name", "домен"].iter().any(|header| fields[0].eq_ignore_ascii_case(header)) {
+                continue;
+            }
+
+            entries.push(parse_entry(&fields).map_err(|e| format!("строка {}: {}", index + 1, e))?);
+        }
+
+        if entries.is_empty() {
+            return Err(format!("{}: в файле нет ни одного домена", source));
+        }
+        Ok(Self { source: source.to_string(), entries })
+    }
+
+    pub fn total_queries(&self) -> usize {
+        self.entries.iter().map(|entry| entry.weight as usize).sum()
+    }
+
+    // Порядок запросов за проход: по кругу, пока у имени не кончится вес,
+    // чтобы повторы одного имени не шли подряд
+    pub fn schedule(&self) -> Vec<usize> {
+        let mut schedule = Vec::with_capacity(self.total_queries());
+        for round in 0..self.entries.iter().map(|entry| entry.weight).max().unwrap_or(0) {
+            schedule.extend(self.entries.iter().enumerate().filter(|(_, entry)| entry.weight > round).map(|(index, _)| index));
+        }
+        schedule
+    }
+}
+
+fn parse_entry(fields: &[&str]) -> Result<WorkloadEntry, String> {
+    if fields.len() > 3 {
+        return Err(format!("ожидалось не больше трех полей (домен,тип,вес), а их {}", fields.len()));
+    }
+
+    let name = fields[0].trim_end_matches('.').to_ascii_lowercase();
+    validate_name(&name)?;
+
+    let record_type = match fields.get(1).filter(|field| !field.is_empty()) {
+        Some(field) => RecordType::parse(field).ok_or_else(|| format!("неизвестный тип записи {}", field))?,
+        None => RecordType::A,
+    };
+
+    let weight = match fields.get(2).filter(|field| !field.is_empty()) {
+        Some(field) => field.parse::<u32>().map_err(|_| format!("вес должен быть целым числом: {}", field))?,
+        None => 1,
+    };
+    if !(1..=MAX_WEIGHT).contains(&weight) {
+        return Err(format!("вес {} вне диапазона 1..={}", weight, MAX_WEIGHT));
+    }
+
+    Ok(WorkloadEntry { name, record_type, weight })
+}
+
+fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > 253 {
+        return Err(format!("некорректная длина имени: {:?}", name));
+    }
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!("некорректная метка в имени {}", name));
+        }
+        if !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("недопустимые символы в имени {}", name));
+        }
+    }
+    Ok(())
+}
+
+// Задержка по каждому имени набора - оба сервера провайдера вместе
+#[derive(Clone, Debug)]
+pub struct DomainResult {
+    pub name: String,
+    pub record_type: RecordType,
+    pub weight: u32,
+    pub stats: LatencyStats,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct WorkloadResult {
+    pub source: String,
+    // Все запросы набора; веса учтены числом повторов
+    pub aggregate: LatencyStats,
+    pub domains: Vec<DomainResult>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_list_and_csv_are_parsed() {
+        let plain = Workload::parse("list.txt", "# наши SaaS\nApp.Example.com.\n\ncdn.example.net\n").unwrap();
+        assert_eq!(
+            plain.entries,
+            vec![
+                WorkloadEntry { name: "app.example.com".to_string(), record_type: RecordType::A, weight: 1 },
+                WorkloadEntry { name: "cdn.example.net".to_string(), record_type: RecordType::A, weight: 1 },
+            ]
+        );
+
+        let csv = Workload::parse("traffic.csv", "domain,type,weight\napi.example.com,AAAA,5\nmail.example.com,mx,\n").unwrap();
+        assert_eq!(csv.entries[0], WorkloadEntry { name: "api.example.com".to_string(), record_type: RecordType::Aaaa, weight: 5 });
+        assert_eq!(csv.entries[1].record_type, RecordType::Mx);
+        assert_eq!(csv.total_queries(), 6);
+    }
+
+    #[test]
+    fn errors_point_to_the_line() {
+        assert_eq!(Workload::parse("w.csv", "a.example.com\nb.example.com,SRVX\n").unwrap_err(), "строка 2: неизвестный тип записи SRVX");
+        assert_eq!(Workload::parse("w.csv", "a.example.com,A,0").unwrap_err(), "строка 1: вес 0 вне диапазона 1..=100");
+        assert!(Workload::parse("w.csv", "bad name.example.com").unwrap_err().starts_with("строка 1"));
+        assert!(Workload::parse("w.csv", "# только комментарий\n").is_err());
+    }
+
+    #[test]
+    fn schedule_spreads_repeats_by_weight() {
+        let workload = Workload::parse("w.csv", "a.example,A,3\nb.example,A,1\nc.example,A,2\n").unwrap();
+        assert_eq!(workload.schedule(), vec![0, 1, 2, 0, 2, 0]);
+    }
+}
@@ -0,0 +1,159 @@
+// Клиентская сторона helper: бэкенд для GUI без прав root.
+// Чтение текущих DNS идет напрямую, а изменения уходят helper через сокет
+
+use std::env;
+use std::io::{self, BufReader};
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::server::HELPER_FLAG;
+use super::{exchange, HelperRequest, HelperResponse};
+use crate::dns::backend::DnsBackend;
+use crate::dns::error::{ApplyReport, DnsError};
+use crate::dns::plan::ChangePlan;
+use crate::network::adapters::AdapterSelection;
+
+// Сколько ждать ответа helper: в первый запрос входит ввод пароля в окне polkit
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Коды выхода pkexec: окно авторизации закрыто или доступ запрещен
+const PKEXEC_DISMISSED: i32 = 126;
+const PKEXEC_NOT_AUTHORIZED: i32 = 127;
+
+struct Connection {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    // Процесс pkexec держим, чтобы по коду выхода отличить отказ в авторизации
+    child: Child,
+}
+
+#[derive(Default)]
+pub struct HelperClient {
+    connection: Mutex<Option<Connection>>,
+}
+
+impl HelperClient {
+    // helper поднимается при первом изменении DNS, а не при старте GUI
+    pub fn request(&self, request: &HelperRequest) -> HelperResponse {
+        let mut guard = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.is_none() {
+            *guard = Some(launch()?);
+        }
+
+        let connection = guard.as_mut().expect("подключение только что создано");
+        match exchange(&mut connection.reader, &mut connection.writer, request) {
+            Ok(response) => response,
+            Err(e) => {
+                // helper завершился - в следующий раз запустим новый
+                let connection = guard.take().expect("подключение есть");
+                Err(exit_error(connection.child, e))
+            }
+        }
+    }
+}
+
+// Канал к helper - socketpair, второй конец которого pkexec передает helper как stdin.
+// Сокета с именем нет: подменить helper или подсунуть root чужой путь нельзя
+fn launch() -> Result<Connection, DnsError> {
+    let (writer, helper_end) = UnixStream::pair()?;
+    writer.set_read_timeout(Some(LAUNCH_TIMEOUT))?;
+
+    let exe = env::current_exe()?;
+    let child = Command::new("pkexec")
+        .arg(exe)
+        .arg(HELPER_FLAG)
+        .stdin(Stdio::from(OwnedFd::from(helper_end)))
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| DnsError::BackendUnavailable(format!("pkexec: {}", e)))?;
+
+    let reader = BufReader::new(writer.try_clone()?);
+    Ok(Connection { reader, writer, child })
+}
+
+// helper закрыл сокет или не ответил: по коду выхода pkexec понятно, была ли авторизация
+fn exit_error(mut child: Child, error: io::Error) -> DnsError {
+    if matches!(error.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) {
+        let _ = child.kill();
+        let _ = child.wait();
+        return DnsError::CommandTimeout(LAUNCH_TIMEOUT);
+    }
+
+    match child.wait().map(|status| (status, status.code())) {
+        Ok((_, Some(PKEXEC_DISMISSED | PKEXEC_NOT_AUTHORIZED))) => {
+            DnsError::PrivilegeMissing("pkexec: авторизация отклонена".to_string())
+        }
+        Ok((status, _)) => DnsError::BackendUnavailable(format!("helper завершился ({}): {}", status, error)),
+        Err(e) => DnsError::BackendUnavailable(format!("helper: {}", e)),
+    }
+}
+
+pub struct HelperBackend {
+    // Бэкенд той же ОС, но без прав - только для чтения
+    local: Box<dyn DnsBackend>,
+    client: HelperClient,
+}
+
+impl HelperBackend {
+    pub fn new(local: Box<dyn DnsBackend>, client: HelperClient) -> Self {
+        Self { local, client }
+    }
+}
+
+impl DnsBackend for HelperBackend {
+    fn name(&self) -> &'static str {
+        self.local.name()
+    }
+
+    fn get_current_dns(&self) -> Result<String, DnsError> {
+        self.local.get_current_dns()
+    }
+
+    fn adapter_dns(&self) -> Result<Vec<(String, Vec<String>)>, DnsError> {
+        self.local.adapter_dns()
+    }
+
+    fn set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ApplyReport, DnsError> {
+        self.client.request(&HelperRequest::SetDns {
+            selection: selection.clone(),
+            servers: servers.to_vec(),
+        })
+    }
+
+    fn reset_dns(&self, selection: &AdapterSelection) -> Result<ApplyReport, DnsError> {
+        self.client.request(&HelperRequest::ResetDns { selection: selection.clone() })
+    }
+
+    // План только читает состояние - helper для него не нужен
+    fn plan_set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ChangePlan, DnsError> {
+        self.local.plan_set_dns(selection, servers)
+    }
+
+    fn plan_reset_dns(&self, selection: &AdapterSelection) -> Result<ChangePlan, DnsError> {
+        self.local.plan_reset_dns(selection)
+    }
+
+    fn flush_cache(&self) -> Result<ApplyReport, DnsError> {
+        self.client.request(&HelperRequest::FlushCache)
+    }
+}
+
+// Без root изменения DNS отправляем helper; под root, при воспроизведении записи
+// или без pkexec оставляем бэкенд как есть
+pub fn wrap_unprivileged(backend: Box<dyn DnsBackend>) -> Box<dyn DnsBackend> {
+    let is_root = unsafe { libc::geteuid() } == 0;
+    if is_root || env::var_os("DNS_MANAGER_REPLAY").is_some() || !pkexec_available() {
+        return backend;
+    }
+
+    Box::new(HelperBackend::new(backend, HelperClient::default()))
+}
+
+fn pkexec_available() -> bool {
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| dir.join("pkexec").is_file()))
+        .unwrap_or(false)
+}
@@ -0,0 +1,163 @@
+// Привилегированный помощник для изменения DNS
+// GUI работает от обычного пользователя и отправляет helper типизированные запросы
+// через локальный сокет; helper запускается через pkexec и умеет только set/reset/flush.
+// Протокол - одна JSON строка на запрос и одна на ответ
+
+pub mod client;
+pub mod server;
+
+use std::io::{self, BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dns::backend::DnsBackend;
+use crate::dns::error::{ApplyReport, DnsError};
+use crate::network::adapters::AdapterSelection;
+
+// Все, что разрешено просить у helper - других операций протокол не описывает
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum HelperRequest {
+    SetDns { selection: AdapterSelection, servers: Vec<String> },
+    ResetDns { selection: AdapterSelection },
+    FlushCache,
+}
+
+pub type HelperResponse = Result<ApplyReport, DnsError>;
+
+fn handle_request(backend: &dyn DnsBackend, request: HelperRequest) -> HelperResponse {
+    match request {
+        HelperRequest::SetDns { selection, servers } => backend.set_dns(&selection, &servers),
+        HelperRequest::ResetDns { selection } => backend.reset_dns(&selection),
+        HelperRequest::FlushCache => backend.flush_cache(),
+    }
+}
+
+// Обслуживает одно подключение до его закрытия клиентом
+pub fn serve_connection(reader: impl BufRead, mut writer: impl Write, backend: &dyn DnsBackend) -> io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // Неизвестная операция или мусор не выполняются - клиент получает ошибку разбора
+        let response = match serde_json::from_str::<HelperRequest>(&line) {
+            Ok(request) => handle_request(backend, request),
+            Err(e) => Err(DnsError::ParseFailure(format!("запрос к helper: {}", e))),
+        };
+        write_message(&mut writer, &response)?;
+    }
+
+    Ok(())
+}
+
+// Отправляет запрос и ждет ответ на том же подключении
+pub fn exchange(reader: &mut impl BufRead, writer: &mut impl Write, request: &HelperRequest) -> io::Result<HelperResponse> {
+    write_message(writer, request)?;
+
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "helper закрыл соединение"));
+    }
+
+    serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(writer: &mut impl Write, message: &impl Serialize) -> io::Result<()> {
+    let mut line = serde_json::to_string(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::error::AdapterChange;
+    use crate::dns::plan::ChangePlan;
+    use std::io::BufReader;
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+
+    // Бэкенд-заглушка: описывает вызов вместо изменения системы
+    struct EchoBackend;
+
+    impl DnsBackend for EchoBackend {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn get_current_dns(&self) -> Result<String, DnsError> {
+            Ok(String::new())
+        }
+
+        fn set_dns(&self, selection: &AdapterSelection, servers: &[String]) -> Result<ApplyReport, DnsError> {
+            let change = AdapterChange::ok(format!("{:?}", selection), servers.join(" "));
+            Ok(ApplyReport::new(self.name(), vec![change]))
+        }
+
+        fn reset_dns(&self, _selection: &AdapterSelection) -> Result<ApplyReport, DnsError> {
+            Err(DnsError::PrivilegeMissing("нет root".to_string()))
+        }
+
+        fn plan_set_dns(&self, _selection: &AdapterSelection, _servers: &[String]) -> Result<ChangePlan, DnsError> {
+            Ok(ChangePlan::default())
+        }
+
+        fn plan_reset_dns(&self, _selection: &AdapterSelection) -> Result<ChangePlan, DnsError> {
+            Ok(ChangePlan::default())
+        }
+
+        fn flush_cache(&self) -> Result<ApplyReport, DnsError> {
+            Ok(ApplyReport::new(self.name(), vec![AdapterChange::ok("кэш", "очищен")]))
+        }
+    }
+
+    fn spawn_helper() -> (BufReader<UnixStream>, UnixStream, thread::JoinHandle<()>) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let handle = thread::spawn(move || {
+            let reader = BufReader::new(server.try_clone().unwrap());
+            serve_connection(reader, server, &EchoBackend).unwrap();
+        });
+        (BufReader::new(client.try_clone().unwrap()), client, handle)
+    }
+
+    #[test]
+    fn requests_and_errors_cross_the_socket() {
+        let (mut reader, mut writer, handle) = spawn_helper();
+
+        let set = HelperRequest::SetDns {
+            selection: AdapterSelection::Names(vec!["eth0".to_string()]),
+            servers: vec!["1.1.1.1".to_string(), "2606:4700:4700::1111".to_string()],
+        };
+        let report = exchange(&mut reader, &mut writer, &set).unwrap().unwrap();
+        assert_eq!(report.backend, "echo");
+        assert_eq!(report.changes[0].adapter, "Names([\"eth0\"])");
+        assert_eq!(report.changes[0].result.as_ref().unwrap(), "1.1.1.1 2606:4700:4700::1111");
+
+        let reset = HelperRequest::ResetDns { selection: AdapterSelection::AllUp };
+        assert!(matches!(
+            exchange(&mut reader, &mut writer, &reset).unwrap(),
+            Err(DnsError::PrivilegeMissing(_))
+        ));
+
+        assert!(exchange(&mut reader, &mut writer, &HelperRequest::FlushCache).unwrap().is_ok());
+
+        drop(writer);
+        drop(reader);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn unknown_operation_is_rejected() {
+        let (mut reader, mut writer, _handle) = spawn_helper();
+
+        writer.write_all(b"{\"op\":\"run\",\"command\":\"rm -rf /\"}\n").unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+
+        let response: HelperResponse = serde_json::from_str(&line).unwrap();
+        assert!(matches!(response, Err(DnsError::ParseFailure(_))));
+    }
+}
@@ -0,0 +1,124 @@
+// Серверная сторона helper: запускается через pkexec с правами root и обслуживает
+// GUI через сокет, переданный как stdin. Путей в файловой системе helper не касается -
+// root не должен ничего создавать, удалять или chown по пути, который выбрал клиент
+
+use std::io::{self, BufReader};
+use std::mem;
+use std::os::fd::AsFd;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+
+use super::{serve_connection, write_message, HelperResponse};
+use crate::command;
+use crate::dns::backend::detect_backend;
+use crate::dns::error::DnsError;
+
+// Аргумент командной строки, по которому бинарник работает как helper, а не как GUI
+pub const HELPER_FLAG: &str = "--dns-helper";
+
+// Если бинарник запущен как helper - обслуживает GUI и возвращает код выхода
+pub fn run_from_args() -> Option<i32> {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some(HELPER_FLAG) {
+        return None;
+    }
+
+    if let Some(extra) = args.next() {
+        eprintln!("dns-manager helper: лишний аргумент {:?}, канал к GUI - только stdin", extra);
+        return Some(2);
+    }
+
+    match run(allowed_uid()) {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("dns-manager helper: {}", e);
+            Some(1)
+        }
+    }
+}
+
+// pkexec и sudo сообщают, какой пользователь нас запустил
+fn allowed_uid() -> u32 {
+    ["PKEXEC_UID", "SUDO_UID"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok()?.parse().ok())
+        .unwrap_or_else(|| unsafe { libc::getuid() })
+}
+
+pub fn run(allowed_uid: u32) -> Result<(), DnsError> {
+    // GUI передает конец socketpair как stdin; если это не сокет, проверка peer uid не пройдет
+    let stream = UnixStream::from(io::stdin().as_fd().try_clone_to_owned()?);
+    serve(stream, allowed_uid)
+}
+
+// Обслуживает одно подключение: до проверки вызывающего бэкенд даже не создается
+pub fn serve(stream: UnixStream, allowed_uid: u32) -> Result<(), DnsError> {
+    if let Err(e) = authorize(&stream, allowed_uid) {
+        let mut writer = &stream;
+        let response: HelperResponse = Err(e.clone());
+        let _ = write_message(&mut writer, &response);
+        return Err(e);
+    }
+
+    let backend = detect_backend(command::default_runner().map_err(DnsError::BackendUnavailable)?);
+    let reader = BufReader::new(stream.try_clone()?);
+    serve_connection(reader, &stream, backend.as_ref()).map_err(DnsError::from)
+}
+
+// Обслуживаем только пользователя, запустившего helper
+pub fn authorize(stream: &UnixStream, allowed_uid: u32) -> Result<(), DnsError> {
+    let uid = peer_uid(stream)?;
+    if uid == allowed_uid {
+        Ok(())
+    } else {
+        Err(DnsError::PrivilegeMissing(format!("uid {} не может управлять DNS через этот helper", uid)))
+    }
+}
+
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut credentials = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut length = mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    // SO_PEERCRED отдает учетные данные процесса на другом конце сокета
+    let rc = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+
+    if rc == 0 {
+        Ok(credentials.uid)
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caller_with_other_uid_is_rejected() {
+        let (client, _server) = UnixStream::pair().unwrap();
+        let own_uid = unsafe { libc::getuid() };
+
+        assert!(authorize(&client, own_uid).is_ok());
+        assert!(matches!(authorize(&client, own_uid + 1), Err(DnsError::PrivilegeMissing(_))));
+    }
+
+    #[test]
+    fn rejected_caller_gets_an_error_before_any_request() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let own_uid = unsafe { libc::getuid() };
+
+        assert!(matches!(serve(server, own_uid + 1), Err(DnsError::PrivilegeMissing(_))));
+        let mut line = String::new();
+        io::BufRead::read_line(&mut BufReader::new(client), &mut line).unwrap();
+        let response: HelperResponse = serde_json::from_str(&line).unwrap();
+        assert!(matches!(response, Err(DnsError::PrivilegeMissing(_))));
+    }
+}
@@ -2,8 +2,17 @@
 mod ui;
 mod network;
 mod dns;
+mod command;
+#[cfg(target_os = "linux")]
+mod helper;
 
 fn main() -> Result<(), eframe::Error> {
+    // Тот же бинарник, запущенный через pkexec, работает как привилегированный helper
+    #[cfg(target_os = "linux")]
+    if let Some(code) = helper::server::run_from_args() {
+        std::process::exit(code);
+    }
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([400.0, 500.0])
@@ -19,19 +28,63 @@ fn main() -> Result<(), eframe::Error> {
 }
 
 // Импортируем типы из модулей
-use dns::providers::{DNSProvider, SpeedTestResult};
-use network::adapters::NetworkAdapter;
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Arc;
+
+use command::CommandRunner;
+use dns::backend::DnsBackend;
+use dns::error::{ApplyReport, DnsError};
+use dns::plan::ChangePlan;
+use dns::transaction::{RevertReason, Transaction};
+use dns::catalog::ProviderCatalog;
+use dns::providers::{DNSProvider, ProviderFilter, ProviderSort};
+use dns::stamp;
+use dns::speed_test::{BenchmarkConfig, CacheState, RankingStatistic, SpeedTestResult, SpeedTestRun};
+use dns::workload::Workload;
+use ui::components::{error_status, outcome_status, PendingChange, PlannedAction, ProviderCheck};
+use network::adapters::{AdapterSelection, NetworkAdapter, NetworkConnection};
 
-#[derive(Default)]
 struct DNSManager {
     status: String,
     current_dns: String,
     speed_results: Vec<SpeedTestResult>,
+    // Провайдеры из файла каталога в папке настроек
+    catalog: ProviderCatalog,
+    benchmark_config: BenchmarkConfig,
+    ranking_statistic: RankingStatistic,
+    ranking_cache: CacheState,
+    // Свой набор доменов для замера; None - встроенные популярные имена
+    workload: Option<Arc<Workload>>,
+    workload_path: String,
+    // Форма редактора своего провайдера
+    custom_name: String,
+    custom_description: String,
     custom_primary: String,
     custom_secondary: String,
+    custom_primary_v6: String,
+    custom_secondary_v6: String,
+    // Какой провайдер каталога редактируется; None - добавляется новый
+    custom_original: Option<String>,
+    // Проверка связи, после которой провайдер сохраняется
+    custom_check: Option<ProviderCheck>,
+    // Вставленный DNS Stamp (sdns://) для импорта
+    stamp_input: String,
+    // Отбор и порядок на вкладке провайдеров
+    provider_filter: ProviderFilter,
+    provider_sort: ProviderSort,
     selected_tab: usize,
-    is_speed_testing: bool,
+    // Идущий в фоне замер скорости
+    speed_test: Option<SpeedTestRun>,
     network_adapters: Vec<NetworkAdapter>,
+    network_connections: Vec<NetworkConnection>,
+    // На какие адаптеры применяются установка и сброс DNS
+    adapter_selection: AdapterSelection,
+    // План, открытый в диалоге подтверждения
+    pending_change: Option<PendingChange>,
+    // Примененные DNS, которые ждут проверки связи и подтверждения
+    transaction: Option<Transaction>,
+    backend: Arc<dyn DnsBackend>,
+    runner: Arc<dyn CommandRunner>,
 }
 
 impl DNSManager {
@@ -66,116 +119,436 @@ impl DNSManager {
         cc.egui_ctx.set_style(style);
 
         // Получаем информацию о сетевых адаптерах при запуске
-        let network_adapters = network::adapters::get_network_adapters();
+        // Все команды ОС идут через один раннер (его можно подменить записью)
+        let (runner, runner_error) = match command::default_runner() {
+            Ok(runner) => (runner, None),
+            // Раннер без записей: любая команда завершится ошибкой, настоящие не выполнятся
+            Err(e) => (Arc::new(command::replay::ReplayRunner::new(Vec::new())) as Arc<dyn CommandRunner>, Some(e)),
+        };
+
+        // Выбираем бэкенд DNS под текущую ОС
+        let backend = dns::backend::detect_backend(runner.clone());
+        // GUI без root меняет DNS через helper, запущенный через pkexec
+        #[cfg(target_os = "linux")]
+        let backend = helper::client::wrap_unprivileged(backend);
+
+        let network_adapters = network::adapters::get_network_adapters(runner.as_ref(), backend.as_ref());
+        let network_connections = network::adapters::get_network_connections();
 
         Self {
-            status: "🚀 Ready for space launch!".to_string(),
+            status: match runner_error {
+                Some(e) => format!("⛔ {}. Команды ОС отключены", e),
+                None => format!("🚀 Ready for space launch! ({})", backend.name()),
+            },
             current_dns: String::new(),
             speed_results: Vec::new(),
+            catalog: ProviderCatalog::load(),
+            benchmark_config: BenchmarkConfig::default(),
+            ranking_statistic: RankingStatistic::default(),
+            ranking_cache: CacheState::default(),
+            workload: None,
+            workload_path: String::new(),
+            custom_name: String::new(),
+            custom_description: String::new(),
             custom_primary: String::new(),
             custom_secondary: String::new(),
+            custom_primary_v6: String::new(),
+            custom_secondary_v6: String::new(),
+            custom_original: None,
+            custom_check: None,
+            stamp_input: String::new(),
+            provider_filter: ProviderFilter::default(),
+            provider_sort: ProviderSort::default(),
             selected_tab: 0,
-            is_speed_testing: false,
+            speed_test: None,
             network_adapters,
+            network_connections,
+            adapter_selection: AdapterSelection::default(),
+            pending_change: None,
+            transaction: None,
+            backend: Arc::from(backend),
+            runner,
         }
     }
 
 
 
     // Вспомогательные функции-обертки для модулей
-    fn get_current_dns() -> Result<String, String> {
-        dns::providers::get_current_dns()
+    fn get_current_dns(&self) -> Result<String, DnsError> {
+        self.backend.get_current_dns()
     }
 
-    fn set_dns(primary: &str, secondary: &str) -> Result<String, String> {
-        dns::providers::set_dns(primary, secondary)
+    fn set_dns(&self, servers: &[String]) -> Result<ApplyReport, DnsError> {
+        self.backend.set_dns(&self.adapter_selection, servers)
     }
 
-    fn reset_dns() -> Result<String, String> {
-        dns::providers::reset_dns()
+    fn reset_dns(&self) -> Result<ApplyReport, DnsError> {
+        self.backend.reset_dns(&self.adapter_selection)
     }
 
-    fn ping_dns_server(ip: &str) -> Option<f64> {
-        dns::providers::ping_dns_server(ip)
+    fn flush_dns_cache(&self) -> Result<ApplyReport, DnsError> {
+        self.backend.flush_cache()
     }
 
-    fn get_dns_providers() -> Vec<DNSProvider> {
-        dns::providers::get_dns_providers()
+    // Изменения DNS сначала показываются планом в диалоге подтверждения
+    fn confirm_set_dns(&mut self, servers: Vec<String>, success: &str, failure: &str) {
+        let plan = self.backend.plan_set_dns(&self.adapter_selection, &servers);
+        let action = PlannedAction::Set { servers };
+        self.open_plan(plan, action, success, failure);
     }
 
-    fn get_network_adapters() -> Vec<NetworkAdapter> {
-        network::adapters::get_network_adapters()
+    fn confirm_reset_dns(&mut self, success: &str, failure: &str) {
+        let plan = self.backend.plan_reset_dns(&self.adapter_selection);
+        self.open_plan(plan, PlannedAction::Reset, success, failure);
     }
 
-    fn start_speed_test(&mut self) {
-        if !self.is_speed_testing {
-            self.is_speed_testing = true;
-            self.status = "🧪 Запуск тестирования скорости DNS...".to_string();
-            self.speed_results.clear();
+    fn open_plan(&mut self, plan: Result<ChangePlan, DnsError>, action: PlannedAction, success: &str, failure: &str) {
+        match plan {
+            Ok(plan) => {
+                self.status = "🧭 Проверьте план изменений".to_string();
+                self.pending_change = Some(PendingChange {
+                    plan,
+                    action,
+                    success: success.to_string(),
+                    failure: failure.to_string(),
+                });
+            }
+            Err(e) => self.status = error_status(failure, &e),
         }
     }
 
-    fn update_speed_test(&mut self) -> bool {
-        if !self.is_speed_testing {
-            return false;
+    fn apply_pending_change(&mut self) {
+        let Some(pending) = self.pending_change.take() else {
+            return;
+        };
+
+        let PlannedAction::Set { servers } = &pending.action else {
+            // Возврат к DHCP - безопасное направление, его не откатываем
+            self.status = outcome_status(self.reset_dns(), &pending.success, &pending.failure);
+            return;
+        };
+
+        let result = self.set_dns(servers);
+        let applied = matches!(result, Ok(_) | Err(DnsError::PartialSuccess(_)));
+        self.status = outcome_status(result, &pending.success, &pending.failure);
+
+        if applied {
+            // Старый кэш скрыл бы проблему с новыми серверами
+            let _ = self.flush_dns_cache();
+            self.transaction = Some(Transaction::start(pending.plan));
         }
+    }
 
-        let providers = Self::get_dns_providers();
-        let current_count = self.speed_results.len();
+    fn keep_changes(&mut self) {
+        self.transaction = None;
+        self.status = "✅ Новые DNS сохранены".to_string();
+    }
 
-        if current_count < providers.len() {
-            // Тестируем следующий провайдер
-            let provider = &providers[current_count];
-            self.status = format!("🧪 Тестирование {}... ({}/{})", provider.name, current_count + 1, providers.len());
+    fn revert_transaction(&mut self, reason: RevertReason) {
+        let Some(transaction) = self.transaction.take() else {
+            return;
+        };
+
+        let success = match reason {
+            RevertReason::ProbeFailed(e) => format!("↩️ Связь пропала ({}), прежние DNS возвращены", e),
+            RevertReason::NotConfirmed => "↩️ Изменения не подтверждены, прежние DNS возвращены".to_string(),
+            RevertReason::Requested => "↩️ Прежние DNS возвращены".to_string(),
+        };
+        let result = dns::transaction::revert(self.backend.as_ref(), &transaction.snapshot);
+        self.status = outcome_status(result, &success, "💥 Откат не удался:");
+    }
 
-            let primary_ping = Self::ping_dns_server(&provider.primary);
-            let secondary_ping = Self::ping_dns_server(&provider.secondary);
+    fn get_network_adapters(&self) -> Vec<NetworkAdapter> {
+        network::adapters::get_network_adapters(self.runner.as_ref(), self.backend.as_ref())
+    }
 
-            let mut result = SpeedTestResult {
-                provider: provider.name.clone(),
-                primary_ping,
-                secondary_ping,
-                avg_ping: None,
-            };
+    fn get_network_connections() -> Vec<NetworkConnection> {
+        network::adapters::get_network_connections()
+    }
 
-            // Вычисляем среднее значение
-            let mut pings = Vec::new();
-            if let Some(p) = result.primary_ping { pings.push(p); }
-            if let Some(p) = result.secondary_ping { pings.push(p); }
+    fn start_speed_test(&mut self) {
+        if self.speed_test.is_some() {
+            return;
+        }
 
-            if !pings.is_empty() {
-                result.avg_ping = Some(pings.iter().sum::<f64>() / pings.len() as f64);
+        // Уже настроенные резолверы - опорные точки: стоит ли вообще переключаться.
+        // Текущие DNS бэкенд читает из ОС (процесс, D-Bus, helper), поэтому их собирает сам замер
+        let backend = self.backend.clone();
+        let adapter_sources: Vec<(String, Vec<String>)> = self
+            .network_adapters
+            .iter()
+            .map(|adapter| (format!("📌 DNS адаптера {}", adapter.name), adapter.dns_servers.clone()))
+            .collect();
+        let baselines = move || {
+            let mut sources = Vec::new();
+            if let Ok(current) = backend.get_current_dns() {
+                sources.push(("📌 Текущие DNS".to_string(), vec![current]));
+            }
+            sources.extend(adapter_sources);
+            dns::providers::baseline_providers(&sources)
+        };
+
+        match SpeedTestRun::start(self.catalog.providers.clone(), baselines, self.benchmark_config, self.workload.clone()) {
+            Ok(run) => {
+                self.status = "🧪 Запуск тестирования скорости DNS...".to_string();
+                self.speed_results.clear();
+                self.speed_test = Some(run);
             }
+            Err(e) => self.status = format!("❌ Не удалось запустить тестирование: {}", e),
+        }
+    }
 
-            self.speed_results.push(result);
-            return false; // Продолжаем тестирование
-        } else {
-            // Тестирование завершено
-            self.is_speed_testing = false;
-
-            // Сортируем по средней задержке
-            self.speed_results.sort_by(|a, b| {
-                match (a.avg_ping, b.avg_ping) {
-                    (Some(a_ping), Some(b_ping)) => a_ping.partial_cmp(&b_ping).unwrap_or(std::cmp::Ordering::Equal),
-                    (Some(_), None) => std::cmp::Ordering::Less,
-                    (None, Some(_)) => std::cmp::Ordering::Greater,
-                    (None, None) => std::cmp::Ordering::Equal,
-                }
-            });
+    // Забирает результаты фонового замера; UI при этом не ждет сеть
+    fn update_speed_test(&mut self) {
+        let Some(run) = &mut self.speed_test else {
+            return;
+        };
+
+        let results = run.poll();
+        if !results.is_empty() {
+            self.speed_results.extend(results);
+            // Сортируем по выбранной в Лаборатории статистике
+            dns::speed_test::rank_results(&mut self.speed_results, self.ranking_statistic, self.ranking_cache);
+        }
 
+        if run.is_finished() {
+            self.speed_test = None;
             self.status = format!("✅ Тестирование завершено! Получено {} результатов.", self.speed_results.len());
-            return true; // Тестирование завершено
+        } else {
+            self.status = format!(
+                "🧪 Тестирование {}... ({}/{})",
+                run.running.join(", "),
+                run.completed,
+                run.total
+            );
         }
     }
 
+    fn load_workload(&mut self) {
+        match Workload::load(std::path::Path::new(self.workload_path.trim())) {
+            Ok(workload) => {
+                self.status = format!("📂 Набор {} загружен: {} имен", workload.source, workload.entries.len());
+                self.workload = Some(Arc::new(workload));
+            }
+            Err(e) => self.status = format!("❌ Не удалось загрузить набор доменов: {}", e),
+        }
+    }
+
+    // Каталог правят руками в редакторе - подхватываем изменения без перезапуска
+    fn reload_catalog(&mut self) {
+        if !self.catalog.reload_if_changed() {
+            return;
+        }
+        self.status = match &self.catalog.error {
+            Some(e) => format!("⚠️ Каталог провайдеров не принят: {}", e),
+            None => format!("📚 Каталог провайдеров обновлен: {} провайдеров", self.catalog.providers.len()),
+        };
+    }
+
+    // Заполняет форму редактора: copy - дубликат под свободным именем
+    fn edit_provider(&mut self, provider: &DNSProvider, copy: bool) {
+        self.custom_name = if copy { self.catalog.copy_name(&provider.name) } else { provider.name.clone() };
+        self.custom_description = provider.description.clone();
+        self.custom_primary = provider.primary.clone();
+        self.custom_secondary = provider.secondary.clone();
+        self.custom_primary_v6 = provider.primary_v6.clone().unwrap_or_default();
+        self.custom_secondary_v6 = provider.secondary_v6.clone().unwrap_or_default();
+        self.custom_original = if copy { None } else { Some(provider.name.clone()) };
+    }
+
+    fn clear_provider_form(&mut self) {
+        self.custom_name.clear();
+        self.custom_description.clear();
+        self.custom_primary.clear();
+        self.custom_secondary.clear();
+        self.custom_primary_v6.clear();
+        self.custom_secondary_v6.clear();
+        self.custom_original = None;
+    }
+
+    // Провайдер из формы; транспорты и свойства редактируемой записи сохраняются как были
+    fn provider_from_form(&self) -> Result<DNSProvider, String> {
+        let name = self.custom_name.trim();
+        if name.is_empty() {
+            return Err("укажите имя провайдера".to_string());
+        }
+        let mut servers = Vec::new();
+        for (field, value) in [("Основной", &self.custom_primary), ("Резервный", &self.custom_secondary)] {
+            let address: IpAddr = value.trim().parse().map_err(|_| format!("{} DNS - не IP адрес: {:?}", field, value.trim()))?;
+            servers.push(address.to_string());
+        }
+        // IPv6 необязателен, но если указан - только IPv6 адрес
+        let mut ipv6 = Vec::new();
+        for (field, value) in [("Основной IPv6", &self.custom_primary_v6), ("Резервный IPv6", &self.custom_secondary_v6)] {
+            let value = value.trim();
+            if value.is_empty() {
+                ipv6.push(None);
+                continue;
+            }
+            let address: Ipv6Addr = value.parse().map_err(|_| format!("{} DNS - не IPv6 адрес: {:?}", field, value))?;
+            ipv6.push(Some(address.to_string()));
+        }
+        if ipv6[0].is_none() && ipv6[1].is_some() {
+            return Err("резервный IPv6 DNS указан без основного".to_string());
+        }
+
+        let original =
+            self.custom_original.as_ref().and_then(|original| self.catalog.providers.iter().find(|provider| &provider.name == original));
+        Ok(DNSProvider {
+            name: name.to_string(),
+            primary: servers[0].clone(),
+            secondary: servers[1].clone(),
+            description: self.custom_description.trim().to_string(),
+            doh: original.and_then(|provider| provider.doh.clone()),
+            dot: original.and_then(|provider| provider.dot.clone()),
+            doq: original.and_then(|provider| provider.doq.clone()),
+            primary_v6: ipv6[0].take(),
+            secondary_v6: ipv6[1].take(),
+            dnssec: original.is_some_and(|provider| provider.dnssec),
+            no_logs: original.is_some_and(|provider| provider.no_logs),
+            dnscrypt: original.and_then(|provider| provider.dnscrypt.clone()),
+            cert_hashes: original.map(|provider| provider.cert_hashes.clone()).unwrap_or_default(),
+            // Форма сохраняется только после ответа по обычному DNS
+            encrypted_only: false,
+            filtering: original.and_then(|provider| provider.filtering),
+            ecs: original.and_then(|provider| provider.ecs),
+            logging_policy: original.and_then(|provider| provider.logging_policy.clone()),
+        })
+    }
+
+    // Сохраняем только после того, как оба сервера ответили. IPv6 адреса не проверяем:
+    // у самого компьютера может не быть IPv6, и тогда рабочий провайдер не сохранился бы
+    fn check_and_save_provider(&mut self) {
+        if self.custom_check.is_some() {
+            return;
+        }
+        let provider = match self.provider_from_form() {
+            Ok(provider) => provider,
+            Err(e) => {
+                self.status = format!("❌ {}", e);
+                return;
+            }
+        };
+
+        match SpeedTestRun::start(vec![provider.clone()], Vec::new, BenchmarkConfig::reachability(), None) {
+            Ok(run) => {
+                self.status = format!("📡 Проверяем связь с {}...", provider.name);
+                self.custom_check = Some(ProviderCheck { provider, original: self.custom_original.clone(), run });
+            }
+            Err(e) => self.status = format!("❌ Не удалось запустить проверку: {}", e),
+        }
+    }
+
+    fn update_provider_check(&mut self) {
+        let Some(check) = &mut self.custom_check else {
+            return;
+        };
+        let results = check.run.poll();
+        if !check.run.is_finished() {
+            return;
+        }
+        let Some(check) = self.custom_check.take() else {
+            return;
+        };
+
+        let silent: Vec<String> = results
+            .iter()
+            .flat_map(|result| [&result.primary, &result.secondary])
+            .filter(|server| !server.reachable)
+            .map(|server| match &server.error {
+                Some(e) => format!("{} ({})", server.server, e),
+                None => server.server.clone(),
+            })
+            .collect();
+        if results.is_empty() || !silent.is_empty() {
+            self.status = format!("❌ Не сохранено - сервер не отвечает: {}", silent.join(", "));
+            return;
+        }
+
+        let name = check.provider.name.clone();
+        match self.catalog.save_provider(check.original.as_deref(), check.provider) {
+            Ok(()) => {
+                self.status = format!("💾 Провайдер {} сохранен в каталог", name);
+                self.clear_provider_form();
+            }
+            Err(e) => self.status = format!("❌ Не сохранено: {}", e),
+        }
+    }
+
+    fn delete_provider(&mut self, name: &str) {
+        self.status = match self.catalog.remove_provider(name) {
+            Ok(()) => format!("🗑 Провайдер {} удален из каталога", name),
+            Err(e) => format!("❌ Не удалось удалить {}: {}", name, e),
+        };
+        if self.custom_original.as_deref() == Some(name) {
+            self.clear_provider_form();
+        }
+    }
+
+    // Штамп описывает сервер целиком, поэтому сохраняется сразу, без проверки связи:
+    // сервер только с DoH или DNSCrypt не ответил бы на проверку по UDP/53
+    fn import_stamp(&mut self) {
+        let mut provider = match stamp::parse(&self.stamp_input) {
+            Ok(provider) => provider,
+            Err(e) => {
+                self.status = format!("❌ Штамп не принят: {}", e);
+                return;
+            }
+        };
+        if self.catalog.providers.iter().any(|existing| existing.name.eq_ignore_ascii_case(&provider.name)) {
+            provider.name = self.catalog.copy_name(&provider.name);
+        }
+
+        let name = provider.name.clone();
+        match self.catalog.save_provider(None, provider) {
+            Ok(()) => {
+                self.status = format!("📥 Провайдер {} импортирован из штампа", name);
+                self.stamp_input.clear();
+            }
+            Err(e) => self.status = format!("❌ Не сохранено: {}", e),
+        }
+    }
+
+    // Штампы всех транспортов провайдера, по одному на строку
+    fn export_stamps(&mut self, provider: &DNSProvider) -> Option<String> {
+        let stamps: Result<Vec<String>, String> =
+            provider.transports().into_iter().map(|transport| stamp::export(provider, transport)).collect();
+        match stamps {
+            Ok(stamps) => {
+                let kinds: Vec<&str> = provider.transports().into_iter().map(|transport| transport.label()).collect();
+                self.status = format!("📋 Штампы {} скопированы: {}", provider.name, kinds.join(", "));
+                Some(stamps.join("\n"))
+            }
+            Err(e) => {
+                self.status = format!("❌ Штамп не собран: {}", e);
+                None
+            }
+        }
+    }
+
+    fn cancel_speed_test(&mut self) {
+        if let Some(run) = self.speed_test.take() {
+            let (completed, total) = (run.completed, run.total);
+            run.cancel();
+            self.status = format!("⛔ Тестирование отменено ({} из {} провайдеров).", completed, total);
+        }
+    }
 }
 
 impl eframe::App for DNSManager {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.reload_catalog();
+        if self.custom_check.is_some() {
+            self.update_provider_check();
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        ctx.request_repaint_after(std::time::Duration::from_secs(2));
+
         // Обновляем тестирование скорости, если оно активно
-        if self.is_speed_testing {
+        if self.speed_test.is_some() {
             self.update_speed_test();
-            ctx.request_repaint(); // Запрашиваем перерисовку для обновления UI
+            // Результаты приходят из фона - проверяем их без перерисовки на каждом кадре
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
@@ -215,6 +588,16 @@ impl eframe::App for DNSManager {
                 _ => self.show_main_tab(ui, ctx),
             }
         });
+
+        ui::components::show_plan_dialog(self, ctx);
+
+        if let Some(transaction) = &mut self.transaction {
+            if let Some(reason) = transaction.poll(std::time::Instant::now()) {
+                self.revert_transaction(reason);
+            }
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+        ui::components::show_transaction_dialog(self, ctx);
     }
 }
 
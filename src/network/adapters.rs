@@ -1,6 +1,10 @@
 // Модуль для работы с сетевыми адаптерами
 
-use serde_json;
+use serde::{Deserialize, Serialize};
+
+use crate::command::{run_checked, CommandRunner, CommandSpec};
+use crate::dns::backend::DnsBackend;
+use crate::dns::error::DnsError;
 
 #[derive(Clone, Default)]
 pub struct NetworkAdapter {
@@ -9,113 +13,324 @@ pub struct NetworkAdapter {
     pub mac_address: String,
     pub ip_addresses: Vec<String>,
     pub dns_servers: Vec<String>,
+    // DNS выданы DHCP, а не заданы вручную (пока известно только на Windows)
+    pub dns_from_dhcp: bool,
+    // Заполняются только на Linux (netlink), на Windows остаются пустыми
+    pub index: u32,
+    pub mtu: u32,
+    pub flags: Vec<String>,
+    pub adapter_type: AdapterType,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AdapterType {
+    Ethernet,
+    WiFi,
+    // Hyper-V, мосты, veth и прочие программные адаптеры
+    Virtual,
+    Vpn,
+    Loopback,
+    #[default]
+    Other,
+}
+
+impl AdapterType {
+    pub const ALL: [AdapterType; 6] = [
+        AdapterType::Ethernet,
+        AdapterType::WiFi,
+        AdapterType::Virtual,
+        AdapterType::Vpn,
+        AdapterType::Loopback,
+        AdapterType::Other,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AdapterType::Ethernet => "🔌 Ethernet",
+            AdapterType::WiFi => "📶 Wi-Fi",
+            AdapterType::Virtual => "🧊 Виртуальный",
+            AdapterType::Vpn => "🔐 VPN",
+            AdapterType::Loopback => "🔁 Loopback",
+            AdapterType::Other => "❔ Другой",
+        }
+    }
+}
+
+// Какие адаптеры затрагивает установка или сброс DNS
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum AdapterSelection {
+    // Прежнее поведение: все адаптеры в статусе Up
+    #[default]
+    AllUp,
+    Names(Vec<String>),
+    // Активные адаптеры заданного типа и/или с именем по шаблону (* и ?, без учета регистра)
+    Filter {
+        adapter_type: Option<AdapterType>,
+        name_pattern: String,
+    },
+}
+
+impl AdapterSelection {
+    pub fn matches(&self, adapter: &NetworkAdapter) -> bool {
+        match self {
+            AdapterSelection::AllUp => is_up(adapter) && adapter.adapter_type != AdapterType::Loopback,
+            AdapterSelection::Names(names) => names.contains(&adapter.name),
+            AdapterSelection::Filter { adapter_type, name_pattern } => {
+                is_up(adapter)
+                    && adapter_type.is_none_or(|t| t == adapter.adapter_type)
+                    && (name_pattern.is_empty() || wildcard_match(name_pattern, &adapter.name))
+            }
+        }
+    }
+
+    // Подходящие адаптеры и явно запрошенные имена, которых нет в системе
+    pub fn resolve<'a>(&self, adapters: &'a [NetworkAdapter]) -> (Vec<&'a NetworkAdapter>, Vec<String>) {
+        let selected: Vec<&NetworkAdapter> = adapters.iter().filter(|a| self.matches(a)).collect();
+
+        let missing = match self {
+            AdapterSelection::Names(names) => names
+                .iter()
+                .filter(|name| !adapters.iter().any(|a| a.name == **name))
+                .cloned()
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        (selected, missing)
+    }
+}
+
+fn is_up(adapter: &NetworkAdapter) -> bool {
+    adapter.status == "Up"
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+
+    // Классический жадный алгоритм с откатом к последней звездочке
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
 }
 
-pub fn get_network_adapters() -> Vec<NetworkAdapter> {
+// Профиль подключения NetworkManager (на Windows и без NM список пуст)
+#[derive(Clone, Default)]
+pub struct NetworkConnection {
+    pub id: String,
+    pub uuid: String,
+    pub connection_type: String,
+    pub device: String,
+    pub active: bool,
+}
+
+pub fn get_network_connections() -> Vec<NetworkConnection> {
+    #[cfg(target_os = "linux")]
+    if let Some(nm) = crate::dns::backend::network_manager::NetworkManagerBackend::system() {
+        return nm.connections().unwrap_or_default();
+    }
+
+    Vec::new()
+}
+
+// netlink не знает DNS адаптеров - их дает бэкенд (resolved или NetworkManager).
+// Без них у адаптеров не было бы своих опорных резолверов в замере скорости
+#[cfg(target_os = "linux")]
+pub fn get_network_adapters(_runner: &dyn CommandRunner, backend: &dyn DnsBackend) -> Vec<NetworkAdapter> {
+    let mut adapters =
+        super::netlink::get_network_adapters().unwrap_or_else(|e| error_adapter(&format!("Ошибка netlink: {}", e)));
+    let servers = backend.adapter_dns().unwrap_or_default();
+    for adapter in &mut adapters {
+        if let Some((_, dns)) = servers.iter().find(|(name, _)| *name == adapter.name) {
+            adapter.dns_servers = dns.clone();
+        }
+    }
+    adapters
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_network_adapters(runner: &dyn CommandRunner, _backend: &dyn DnsBackend) -> Vec<NetworkAdapter> {
+    get_powershell_adapters(runner)
+}
+
+// На Linux используется только в тестах на записанных фикстурах
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+fn get_powershell_adapters(runner: &dyn CommandRunner) -> Vec<NetworkAdapter> {
+    query_powershell_adapters(runner).unwrap_or_else(|e| error_adapter(&e.to_string()))
+}
+
+// Адаптеры через Get-NetAdapter - используется и вкладкой "Сеть", и бэкендом PowerShell.
+// Адреса и DNS обоих семейств, IPv4 первыми (AddressFamily: 2 - IPv4, 23 - IPv6).
+// Заданные вручную DNS лежат в реестре в NameServer интерфейса; пусто в обоих семействах - DNS от DHCP
+pub fn query_powershell_adapters(runner: &dyn CommandRunner) -> Result<Vec<NetworkAdapter>, DnsError> {
     let command = r#"Get-NetAdapter | Where-Object { $_.Status -eq 'Up' } | ForEach-Object {
     $adapter = $_
-    $ip_info = Get-NetIPAddress -InterfaceAlias $adapter.Name -AddressFamily IPv4 | Select-Object -First 1
-    $dns_info = Get-DnsClientServerAddress -InterfaceAlias $adapter.Name -AddressFamily IPv4
+    $ip_info = @(Get-NetIPAddress -InterfaceAlias $adapter.Name -ErrorAction SilentlyContinue | Sort-Object AddressFamily | ForEach-Object { $_.IPAddress })
+    $dns_info = @(Get-DnsClientServerAddress -InterfaceAlias $adapter.Name | Sort-Object AddressFamily | ForEach-Object { $_.ServerAddresses })
+    $static_dns = @('Tcpip', 'Tcpip6') | ForEach-Object { (Get-ItemProperty "HKLM:\SYSTEM\CurrentControlSet\Services\$_\Parameters\Interfaces\$($adapter.InterfaceGuid)" -ErrorAction SilentlyContinue).NameServer } | Where-Object { $_ }
 
     [PSCustomObject]@{
         Name = $adapter.Name
         Status = $adapter.Status.ToString()
         MacAddress = $adapter.MacAddress
-        IPAddress = if ($ip_info) { $ip_info.IPAddress } else { "N/A" }
-        DNSServers = if ($dns_info.ServerAddresses) { $dns_info.ServerAddresses -join ", " } else { "N/A" }
+        IPAddress = if ($ip_info) { $ip_info -join ", " } else { "N/A" }
+        DNSServers = if ($dns_info) { $dns_info -join ", " } else { "N/A" }
+        DhcpDNS = -not $static_dns
+        InterfaceDescription = $adapter.InterfaceDescription
+        MediaType = $adapter.MediaType
+        Virtual = [bool]$adapter.Virtual
     }
 } | ConvertTo-Json"#;
 
-    match run_powershell_command(command) {
-        Ok(json_result) => {
-            // Парсим JSON результат
-            match serde_json::from_str::<Vec<serde_json::Value>>(&json_result) {
-                Ok(adapters_json) => {
-                    let mut adapters = Vec::new();
-                    for adapter_json in adapters_json {
-                        if let (Some(name), Some(status), Some(mac), Some(ip), Some(dns)) = (
-                            adapter_json.get("Name").and_then(|v| v.as_str()),
-                            adapter_json.get("Status").and_then(|v| v.as_str()),
-                            adapter_json.get("MacAddress").and_then(|v| v.as_str()),
-                            adapter_json.get("IPAddress").and_then(|v| v.as_str()),
-                            adapter_json.get("DNSServers").and_then(|v| v.as_str()),
-                        ) {
-                            let ip_addresses = if ip != "N/A" {
-                                vec![ip.to_string()]
-                            } else {
-                                vec!["Не назначен".to_string()]
-                            };
-
-                            let dns_servers = if dns != "N/A" {
-                                dns.split(", ").map(|s| s.to_string()).collect()
-                            } else {
-                                vec!["Не настроен".to_string()]
-                            };
-
-                            adapters.push(NetworkAdapter {
-                                name: name.to_string(),
-                                status: status.to_string(),
-                                mac_address: mac.to_string(),
-                                ip_addresses,
-                                dns_servers,
-                            });
-                        }
-                    }
-                    adapters
-                }
-                Err(_) => vec![NetworkAdapter {
-                    name: "Ошибка получения данных".to_string(),
-                    status: "N/A".to_string(),
-                    mac_address: "N/A".to_string(),
-                    ip_addresses: vec!["N/A".to_string()],
-                    dns_servers: vec!["N/A".to_string()],
-                }],
-            }
-        }
-        Err(_) => vec![NetworkAdapter {
-            name: "Ошибка выполнения команды".to_string(),
-            status: "N/A".to_string(),
-            mac_address: "N/A".to_string(),
-            ip_addresses: vec!["N/A".to_string()],
-            dns_servers: vec!["N/A".to_string()],
-        }],
-    }
+    let json_result = run_checked(runner, &CommandSpec::powershell(command))?;
+    parse_network_adapters(&json_result)
 }
 
-fn run_powershell_command(command: &str) -> Result<String, String> {
-    use std::process::Command;
+fn error_adapter(message: &str) -> Vec<NetworkAdapter> {
+    vec![NetworkAdapter {
+        name: message.to_string(),
+        status: "N/A".to_string(),
+        mac_address: "N/A".to_string(),
+        ip_addresses: vec!["N/A".to_string()],
+        dns_servers: vec!["N/A".to_string()],
+        ..Default::default()
+    }]
+}
 
-    // Если команда содержит ipconfig или netsh - используем cmd с полным путем
-    if command.contains("ipconfig") || command.contains("netsh") {
-        let output = Command::new(r"C:\Windows\System32\cmd.exe")
-            .arg("/C")
-            .arg(command)
-            .output()
-            .map_err(|e| format!("Failed to execute command: {}", e))?;
+fn parse_network_adapters(json_result: &str) -> Result<Vec<NetworkAdapter>, DnsError> {
+    // ConvertTo-Json отдает объект вместо массива, если адаптер всего один
+    let adapters_json = match serde_json::from_str::<serde_json::Value>(json_result) {
+        Ok(serde_json::Value::Array(items)) => items,
+        Ok(item @ serde_json::Value::Object(_)) => vec![item],
+        Ok(_) => return Err(DnsError::ParseFailure("Get-NetAdapter вернул не JSON объект".to_string())),
+        Err(e) => return Err(DnsError::ParseFailure(format!("Get-NetAdapter: {}", e))),
+    };
 
-        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let mut adapters = Vec::new();
+    for adapter_json in adapters_json {
+        if let (Some(name), Some(status), Some(mac), Some(ip), Some(dns)) = (
+            adapter_json.get("Name").and_then(|v| v.as_str()),
+            adapter_json.get("Status").and_then(|v| v.as_str()),
+            adapter_json.get("MacAddress").and_then(|v| v.as_str()),
+            adapter_json.get("IPAddress").and_then(|v| v.as_str()),
+            adapter_json.get("DNSServers").and_then(|v| v.as_str()),
+        ) {
+            let ip_addresses = if ip != "N/A" {
+                ip.split(", ").map(|s| s.to_string()).collect()
+            } else {
+                vec!["Не назначен".to_string()]
+            };
 
-        if output.status.success() {
-            Ok(stdout)
-        } else {
-            Err(stderr)
+            let dns_servers = if dns != "N/A" {
+                dns.split(", ").map(|s| s.to_string()).collect()
+            } else {
+                vec!["Не настроен".to_string()]
+            };
+
+            let adapter_type = classify_windows_adapter(
+                adapter_json.get("InterfaceDescription").and_then(|v| v.as_str()).unwrap_or(""),
+                adapter_json.get("MediaType").and_then(|v| v.as_str()).unwrap_or(""),
+                adapter_json.get("Virtual").and_then(|v| v.as_bool()).unwrap_or(false),
+            );
+
+            adapters.push(NetworkAdapter {
+                name: name.to_string(),
+                status: status.to_string(),
+                mac_address: mac.to_string(),
+                ip_addresses,
+                dns_servers,
+                dns_from_dhcp: adapter_json.get("DhcpDNS").and_then(|v| v.as_bool()).unwrap_or(false),
+                adapter_type,
+                ..Default::default()
+            });
         }
+    }
+    Ok(adapters)
+}
+
+fn classify_windows_adapter(description: &str, media_type: &str, is_virtual: bool) -> AdapterType {
+    let description = description.to_lowercase();
+
+    if ["vpn", "tap-", "wireguard", "wintun", "openvpn", "tunnel"]
+        .iter()
+        .any(|marker| description.contains(marker))
+    {
+        AdapterType::Vpn
+    } else if description.contains("loopback") {
+        AdapterType::Loopback
+    } else if is_virtual || description.contains("hyper-v") || description.contains("virtual") {
+        AdapterType::Virtual
+    } else if media_type.contains("802.11") || media_type.to_lowercase().contains("wireless") {
+        AdapterType::WiFi
+    } else if media_type.contains("802.3") {
+        AdapterType::Ethernet
     } else {
-        // Для PowerShell команд используем PowerShell
-        let output = Command::new(r"C:\Windows\System32\WindowsPowerShell\v1.0\powershell.exe")
-            .arg("-Command")
-            .arg(command)
-            .output()
-            .map_err(|e| format!("Failed to execute command: {}", e))?;
-
-        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
-
-        if output.status.success() {
-            Ok(stdout)
-        } else {
-            Err(stderr)
-        }
+        AdapterType::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::replay::ReplayRunner;
+
+    fn fixture(name: &str) -> ReplayRunner {
+        let path = format!("{}/fixtures/commands/{}", env!("CARGO_MANIFEST_DIR"), name);
+        ReplayRunner::from_file(path).unwrap()
+    }
+
+    #[test]
+    fn parses_adapter_list() {
+        let adapters = get_powershell_adapters(&fixture("get_net_adapter_two.json"));
+
+        assert_eq!(adapters.len(), 2);
+        assert_eq!(adapters[0].name, "Ethernet");
+        assert_eq!(adapters[0].ip_addresses, vec!["192.168.1.42", "2001:db8:1::42"]);
+        assert_eq!(adapters[0].dns_servers, vec!["192.168.1.1", "8.8.8.8", "2001:4860:4860::8888"]);
+        assert_eq!(adapters[1].ip_addresses, vec!["Не назначен"]);
+        assert_eq!(adapters[1].dns_servers, vec!["Не настроен"]);
+    }
+
+    #[test]
+    fn parses_single_adapter_object() {
+        let adapters = get_powershell_adapters(&fixture("get_net_adapter_single.json"));
+
+        assert_eq!(adapters.len(), 1);
+        assert_eq!(adapters[0].mac_address, "00-15-5D-01-02-03");
+    }
+
+    #[test]
+    fn selection_filters_by_type_and_pattern() {
+        let adapters = get_powershell_adapters(&fixture("get_net_adapter_two.json"));
+        assert_eq!(adapters[0].adapter_type, AdapterType::Ethernet);
+        assert_eq!(adapters[1].adapter_type, AdapterType::Virtual);
+
+        let physical = AdapterSelection::Filter { adapter_type: Some(AdapterType::Ethernet), name_pattern: String::new() };
+        let (selected, missing) = physical.resolve(&adapters);
+        assert_eq!(selected.len(), 1);
+        assert_eq!(selected[0].name, "Ethernet");
+        assert!(missing.is_empty());
+
+        let hyper_v = AdapterSelection::Filter { adapter_type: None, name_pattern: "vethernet*".to_string() };
+        assert_eq!(hyper_v.resolve(&adapters).0[0].name, "vEthernet (Default Switch)");
+
+        let explicit = AdapterSelection::Names(vec!["Ethernet".to_string(), "Wi-Fi".to_string()]);
+        assert_eq!(explicit.resolve(&adapters).1, vec!["Wi-Fi"]);
     }
 }
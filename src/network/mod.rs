@@ -3,3 +3,5 @@
 
 pub mod adapters;
 pub mod dns_utils;
+#[cfg(target_os = "linux")]
+pub mod netlink;
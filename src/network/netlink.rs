@@ -0,0 +1,231 @@
+// Получение сетевых адаптеров на Linux напрямую через rtnetlink, без запуска процессов
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::Path;
+
+use futures::TryStreamExt;
+use netlink_packet_route::address::{AddressAttribute, AddressMessage};
+use netlink_packet_route::link::{InfoKind, LinkAttribute, LinkFlag, LinkInfo, LinkLayerType, LinkMessage, State};
+
+use super::adapters::{AdapterType, NetworkAdapter};
+
+pub fn get_network_adapters() -> Result<Vec<NetworkAdapter>, String> {
+    // rtnetlink асинхронный - поднимаем на время запроса однопоточный рантайм в отдельном потоке:
+    // функцию зовут и из задач tokio, а block_on внутри работающего рантайма паникует
+    std::thread::spawn(|| {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .map_err(|e| format!("Не удалось создать tokio runtime: {}", e))?;
+
+        runtime.block_on(dump_adapters())
+    })
+    .join()
+    .map_err(|_| "поток запроса netlink завершился паникой".to_string())?
+}
+
+async fn dump_adapters() -> Result<Vec<NetworkAdapter>, String> {
+    let (connection, handle, _) =
+        rtnetlink::new_connection().map_err(|e| format!("Не удалось открыть netlink сокет: {}", e))?;
+    tokio::spawn(connection);
+
+    let links: Vec<LinkMessage> = handle
+        .link()
+        .get()
+        .execute()
+        .try_collect()
+        .await
+        .map_err(|e| format!("RTM_GETLINK: {}", e))?;
+
+    let addresses: Vec<AddressMessage> = handle
+        .address()
+        .get()
+        .execute()
+        .try_collect()
+        .await
+        .map_err(|e| format!("RTM_GETADDR: {}", e))?;
+
+    // Адреса по индексу интерфейса, в порядке выдачи ядром
+    let mut addresses_by_link: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for message in &addresses {
+        if let Some(ip) = address_of(message) {
+            addresses_by_link
+                .entry(message.header.index)
+                .or_default()
+                .push(format!("{}/{}", ip, message.header.prefix_len));
+        }
+    }
+
+    let mut adapters: Vec<NetworkAdapter> = links
+        .iter()
+        .map(|link| {
+            let addresses = addresses_by_link.remove(&link.header.index).unwrap_or_default();
+            adapter_from_link(link, addresses)
+        })
+        .collect();
+
+    adapters.sort_by_key(|adapter| adapter.index);
+    Ok(adapters)
+}
+
+fn adapter_from_link(link: &LinkMessage, ip_addresses: Vec<String>) -> NetworkAdapter {
+    let mut adapter = NetworkAdapter {
+        index: link.header.index,
+        flags: link.header.flags.iter().map(flag_name).collect(),
+        ip_addresses,
+        ..Default::default()
+    };
+
+    let mut state = None;
+    for attribute in &link.attributes {
+        match attribute {
+            LinkAttribute::IfName(name) => adapter.name = name.clone(),
+            LinkAttribute::Mtu(mtu) => adapter.mtu = *mtu,
+            LinkAttribute::Address(mac) => adapter.mac_address = format_mac(mac),
+            LinkAttribute::OperState(oper_state) => state = Some(*oper_state),
+            _ => {}
+        }
+    }
+
+    adapter.status = link_status(state, &link.header.flags).to_string();
+    adapter.adapter_type = classify_link(link, &adapter.name);
+    adapter
+}
+
+// Статусы в терминах Get-NetAdapter, чтобы выбор адаптеров и UI одинаково работали на обеих ОС
+fn link_status(state: Option<State>, flags: &[LinkFlag]) -> &'static str {
+    if !flags.contains(&LinkFlag::Up) {
+        return "Disabled";
+    }
+
+    match state {
+        // WireGuard, tun и loopback не сообщают состояние канала - им достаточно IFF_UP
+        Some(State::Up | State::Unknown) | None => "Up",
+        Some(State::Dormant) => "Dormant",
+        Some(State::Testing) => "Testing",
+        Some(State::NotPresent) => "Not Present",
+        // Down, LowerLayerDown и состояния, которых нет в netlink-packet-route
+        Some(_) => "Disconnected",
+    }
+}
+
+// Флаги в том виде, в каком их показывает `ip link`
+fn flag_name(flag: &LinkFlag) -> String {
+    let name = match flag {
+        LinkFlag::Up => "UP",
+        LinkFlag::Broadcast => "BROADCAST",
+        LinkFlag::Debug => "DEBUG",
+        LinkFlag::Loopback => "LOOPBACK",
+        LinkFlag::Pointopoint => "POINTOPOINT",
+        LinkFlag::Notrailers => "NOTRAILERS",
+        LinkFlag::Running => "RUNNING",
+        LinkFlag::Noarp => "NOARP",
+        LinkFlag::Promisc => "PROMISC",
+        LinkFlag::Allmulti => "ALLMULTI",
+        LinkFlag::Controller => "MASTER",
+        LinkFlag::Port => "SLAVE",
+        LinkFlag::Multicast => "MULTICAST",
+        LinkFlag::Portsel => "PORTSEL",
+        LinkFlag::Automedia => "AUTOMEDIA",
+        LinkFlag::Dynamic => "DYNAMIC",
+        LinkFlag::LowerUp => "LOWER_UP",
+        LinkFlag::Dormant => "DORMANT",
+        LinkFlag::Echo => "ECHO",
+        other => return format!("0x{:x}", u32::from(*other)),
+    };
+    name.to_string()
+}
+
+fn address_of(message: &AddressMessage) -> Option<IpAddr> {
+    // Для IPv4 адрес интерфейса лежит в IFA_LOCAL (IFA_ADDRESS - адрес пира на point-to-point)
+    let local = message.attributes.iter().find_map(|attribute| match attribute {
+        AddressAttribute::Local(ip) => Some(*ip),
+        _ => None,
+    });
+    let address = message.attributes.iter().find_map(|attribute| match attribute {
+        AddressAttribute::Address(ip) => Some(*ip),
+        _ => None,
+    });
+    local.or(address)
+}
+
+fn classify_link(link: &LinkMessage, name: &str) -> AdapterType {
+    if link.header.flags.contains(&LinkFlag::Loopback) {
+        return AdapterType::Loopback;
+    }
+
+    let kind = link.attributes.iter().find_map(|attribute| match attribute {
+        LinkAttribute::LinkInfo(infos) => infos.iter().find_map(|info| match info {
+            LinkInfo::Kind(kind) => Some(kind.clone()),
+            _ => None,
+        }),
+        _ => None,
+    });
+
+    match kind {
+        Some(InfoKind::Wireguard | InfoKind::Tun | InfoKind::IpTun | InfoKind::SitTun | InfoKind::GreTun
+            | InfoKind::GreTun6 | InfoKind::Vti | InfoKind::Xfrm) => AdapterType::Vpn,
+        Some(_) => AdapterType::Virtual,
+        // Wi-Fi на уровне netlink выглядит как Ethernet - отличаем по каталогу wireless в sysfs
+        None if Path::new("/sys/class/net").join(name).join("wireless").exists() => AdapterType::WiFi,
+        None if link.header.link_layer_type == LinkLayerType::Ether => AdapterType::Ethernet,
+        None if link.header.link_layer_type == LinkLayerType::None => AdapterType::Vpn,
+        None => AdapterType::Other,
+    }
+}
+
+fn format_mac(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::adapters::AdapterSelection;
+
+    fn link(index: u32, name: &str, flags: Vec<LinkFlag>, state: State, kind: Option<InfoKind>) -> LinkMessage {
+        let mut link = LinkMessage::default();
+        link.header.index = index;
+        link.header.flags = flags;
+        link.attributes = vec![LinkAttribute::IfName(name.to_string()), LinkAttribute::Mtu(1420), LinkAttribute::OperState(state)];
+        if let Some(kind) = kind {
+            link.attributes.push(LinkAttribute::LinkInfo(vec![LinkInfo::Kind(kind)]));
+        }
+        link
+    }
+
+    #[test]
+    fn link_attributes_map_to_adapter_fields() {
+        let flags = vec![LinkFlag::Up, LinkFlag::Pointopoint, LinkFlag::Noarp, LinkFlag::LowerUp];
+        let wireguard = adapter_from_link(
+            &link(7, "wg0", flags, State::Unknown, Some(InfoKind::Wireguard)),
+            vec!["10.8.0.2/32".to_string()],
+        );
+        assert_eq!((wireguard.index, wireguard.name.as_str(), wireguard.mtu), (7, "wg0", 1420));
+        assert_eq!(wireguard.status, "Up");
+        assert_eq!(wireguard.flags, vec!["UP", "POINTOPOINT", "NOARP", "LOWER_UP"]);
+        assert_eq!(wireguard.adapter_type, AdapterType::Vpn);
+
+        let unplugged = adapter_from_link(&link(2, "eth0", vec![LinkFlag::Up, LinkFlag::Broadcast], State::LowerLayerDown, None), Vec::new());
+        assert_eq!(unplugged.status, "Disconnected");
+        let disabled = adapter_from_link(&link(3, "eth1", vec![LinkFlag::Broadcast], State::Down, None), Vec::new());
+        assert_eq!(disabled.status, "Disabled");
+
+        // VPN без состояния канала попадает в "все активные", отключенные адаптеры - нет
+        let adapters = [wireguard, unplugged, disabled];
+        let (selected, _) = AdapterSelection::AllUp.resolve(&adapters);
+        assert_eq!(selected.iter().map(|a| a.name.as_str()).collect::<Vec<_>>(), vec!["wg0"]);
+    }
+
+    #[test]
+    fn adapters_can_be_listed_from_a_tokio_task() {
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let adapters = runtime.block_on(async { get_network_adapters() }).unwrap();
+        assert!(adapters.iter().any(|adapter| adapter.adapter_type == AdapterType::Loopback && adapter.status == "Up"));
+    }
+}
@@ -1,2 +1,201 @@
 // Модуль для общих UI компонентов
 // Здесь можно добавить общие компоненты интерфейса
+
+use egui::{Context, RichText};
+
+use crate::dns::error::{AdapterChange, ApplyReport, DnsError};
+use crate::dns::plan::ChangePlan;
+use crate::dns::providers::DNSProvider;
+use crate::dns::speed_test::SpeedTestRun;
+use crate::dns::transaction::{ProbeState, RevertReason};
+use crate::network::adapters::AdapterSelection;
+use crate::DNSManager;
+
+// Что выполнить после подтверждения плана
+pub enum PlannedAction {
+    Set { servers: Vec<String> },
+    Reset,
+}
+
+// Изменение DNS, которое ждет подтверждения в диалоге с планом
+pub struct PendingChange {
+    pub plan: ChangePlan,
+    pub action: PlannedAction,
+    pub success: String,
+    pub failure: String,
+}
+
+// Свой провайдер, который сохранится, если его серверы ответят
+pub struct ProviderCheck {
+    pub provider: DNSProvider,
+    // Имя редактируемой записи; None - новый провайдер
+    pub original: Option<String>,
+    pub run: SpeedTestRun,
+}
+
+// Строка статуса по результатам операции на каждом адаптере
+pub fn changes_summary(changes: &[AdapterChange]) -> String {
+    changes
+        .iter()
+        .map(|change| match &change.result {
+            Ok(applied) => format!("✅ {}: {}", change.adapter, applied),
+            Err(e) => format!("❌ {}: {}", change.adapter, e),
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+// На Linux права дает helper через pkexec, на Windows нужно само приложение от администратора
+#[cfg(target_os = "linux")]
+const PRIVILEGE_HINT: &str = " 🔑 Подтвердите запрос авторизации polkit (pkexec) - DNS меняет helper";
+#[cfg(not(target_os = "linux"))]
+const PRIVILEGE_HINT: &str = " 🔑 Перезапустите приложение с правами администратора";
+
+// Подсказка, что делать пользователю с конкретной ошибкой
+fn error_hint(error: &DnsError) -> &'static str {
+    match error {
+        DnsError::PrivilegeMissing(_) => PRIVILEGE_HINT,
+        DnsError::AdapterNotFound(_) => " 🔌 Обновите список на вкладке Network",
+        DnsError::BackendUnavailable(_) => " 🛰️ Проверьте, что служба DNS запущена",
+        DnsError::CommandTimeout(_) => " ⏳ Система не ответила, попробуйте еще раз",
+        DnsError::InvalidAddress(_) => " ✏️ Проверьте адрес сервера",
+        _ => "",
+    }
+}
+
+// Итоговый статус операции: успех, частичный успех или полный провал
+pub fn outcome_status(result: Result<ApplyReport, DnsError>, success: &str, failure: &str) -> String {
+    match result {
+        Ok(report) if report.changes.is_empty() => "🛸 Ни один адаптер не подходит под выбор".to_string(),
+        Ok(report) => format!("{} [{}]", success, changes_summary(&report.changes)),
+        Err(DnsError::PartialSuccess(report)) => {
+            format!("⚠️ Частичный успех ({}): {}", report.backend, changes_summary(&report.changes))
+        }
+        Err(e) => error_status(failure, &e),
+    }
+}
+
+pub fn error_status(failure: &str, error: &DnsError) -> String {
+    format!("{} {}.{}", failure, error, error_hint(error))
+}
+
+fn servers_label(servers: &[String]) -> String {
+    if servers.is_empty() {
+        "не заданы".to_string()
+    } else {
+        servers.join(", ")
+    }
+}
+
+// Диалог подтверждения: по каждому адаптеру было/станет и команды, которые будут выполнены
+pub fn show_plan_dialog(app: &mut DNSManager, ctx: &Context) {
+    let Some(pending) = &app.pending_change else {
+        return;
+    };
+
+    let mut apply = false;
+    let mut cancel = false;
+
+    egui::Window::new("🧭 План изменений")
+        .collapsible(false)
+        .resizable(true)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            ui.label(format!("Бэкенд: {}", pending.plan.backend));
+            ui.separator();
+
+            egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                if pending.plan.adapters.is_empty() {
+                    ui.label("🛸 Ни один адаптер не подходит под выбор - менять нечего");
+                }
+
+                for adapter in &pending.plan.adapters {
+                    ui.label(RichText::new(format!("📡 {}", adapter.adapter)).strong());
+                    let source = if adapter.from_dhcp { " (DHCP)" } else { "" };
+                    ui.label(format!("   Сейчас: {}{}", servers_label(&adapter.current), source));
+                    if adapter.to_dhcp {
+                        ui.label("   Станет: DHCP (автоматически)");
+                    } else {
+                        ui.label(format!("   Станет: {}", servers_label(&adapter.new)));
+                    }
+                    for command in &adapter.commands {
+                        ui.label(RichText::new(command).monospace().small());
+                    }
+                    ui.add_space(6.0);
+                }
+
+                for name in &pending.plan.missing {
+                    ui.label(format!("⚠️ Адаптер не найден: {}", name));
+                }
+            });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                apply = ui.button("✅ Применить").clicked();
+                cancel = ui.button("❌ Отмена").clicked();
+            });
+        });
+
+    if apply {
+        app.apply_pending_change();
+    } else if cancel {
+        app.pending_change = None;
+        app.status = "🛑 Изменения отменены".to_string();
+    }
+}
+
+pub fn selection_label(selection: &AdapterSelection) -> String {
+    match selection {
+        AdapterSelection::AllUp => "все активные сетевые адаптеры".to_string(),
+        AdapterSelection::Names(names) if names.is_empty() => "адаптеры не выбраны".to_string(),
+        AdapterSelection::Names(names) => names.join(", "),
+        AdapterSelection::Filter { adapter_type, name_pattern } => {
+            let type_label = adapter_type.map_or("любого типа", |t| t.label());
+            if name_pattern.is_empty() {
+                format!("активные адаптеры ({})", type_label)
+            } else {
+                format!("активные адаптеры ({}) по шаблону \"{}\"", type_label, name_pattern)
+            }
+        }
+    }
+}
+
+// "Сохранить изменения?" с обратным отсчетом до автоматического отката
+pub fn show_transaction_dialog(app: &mut DNSManager, ctx: &Context) {
+    let Some(transaction) = &app.transaction else {
+        return;
+    };
+
+    let mut keep = false;
+    let mut revert = false;
+    let remaining = transaction.remaining(std::time::Instant::now());
+
+    egui::Window::new("🛡️ Сохранить новые DNS?")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .show(ctx, |ui| {
+            match &transaction.probe {
+                ProbeState::Running => ui.label("⏳ Проверяем резолвинг и связь..."),
+                ProbeState::Passed(elapsed) => {
+                    ui.label(format!("✅ Связь есть: имя разрешено и сервер ответил за {} мс", elapsed.as_millis()))
+                }
+                ProbeState::Failed(e) => ui.label(format!("❌ Проверка не прошла: {}", e)),
+            };
+            ui.label(format!("↩️ Автоматический откат через {} с", remaining.as_secs() + 1));
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                keep = ui
+                    .add_enabled(matches!(transaction.probe, ProbeState::Passed(_)), egui::Button::new("✅ Оставить"))
+                    .clicked();
+                revert = ui.button("↩️ Откатить сейчас").clicked();
+            });
+        });
+
+    if keep {
+        app.keep_changes();
+    } else if revert {
+        app.revert_transaction(RevertReason::Requested);
+    }
+}
@@ -2,6 +2,17 @@
 
 use egui::{Context, Ui};
 
+use crate::dns::doh::{DohMethod, DohResult};
+use crate::dns::doq::DoqResult;
+use crate::dns::dot::DotResult;
+use crate::dns::providers::{DNSProvider, Ecs, Filtering, ProviderSort, Transport};
+use crate::dns::tcp::{TcpResult, TRUNCATION_QUESTION};
+use crate::dns::speed_test::{
+    rank_results, reference_baseline, relative_to_baseline, CacheState, RankingStatistic, ServerPair, ServerResult,
+};
+use crate::dns::workload::WorkloadResult;
+use crate::network::adapters::{AdapterSelection, AdapterType};
+use crate::ui::components::{outcome_status, selection_label};
 use crate::DNSManager;
 
 pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
@@ -18,14 +29,11 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
             app.status = "🛸 Navigation system activated...".to_string();
             ctx.request_repaint();
 
-            match crate::dns::providers::get_current_dns() {
+            match app.get_current_dns() {
                 Ok(current) => {
-                    if current.contains("1.1.1.1") || current.contains("8.8.8.8") || current.contains("9.9.9.9") || current.contains("208.67.222.222") || current.contains("94.140.14.14") {
+                    if app.catalog.providers.iter().any(|provider| current.contains(&provider.primary)) {
                         // Reset to automatic
-                        match crate::dns::providers::reset_dns() {
-                            Ok(_) => app.status = "🔄 Returned to autopilot".to_string(),
-                            Err(e) => app.status = format!("💥 System failure: {}", e),
-                        }
+                        app.confirm_reset_dns("🔄 Returned to autopilot", "💥 System failure:");
                     } else {
                         app.status = "🌌 Coordinates received".to_string();
                     }
@@ -37,8 +45,11 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
         ui.add_space(10.0);
 
         if ui.add_sized([ui.available_width(), 40.0], egui::Button::new("🛰️ Space Scanning")).clicked() {
-            match crate::dns::providers::get_current_dns() {
-                Ok(dns) => app.status = format!("🛰️ Coordinates received: {}", dns),
+            match app.get_current_dns() {
+                Ok(dns) => {
+                    app.status = format!("🛰️ Coordinates received: {}", dns);
+                    app.current_dns = dns;
+                }
                 Err(e) => app.status = format!("💫 Cosmic noise: {}", e),
             }
             ctx.request_repaint();
@@ -47,13 +58,22 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
         ui.add_space(10.0);
 
         if ui.add_sized([ui.available_width(), 40.0], egui::Button::new("🔄 Reset to DHCP")).clicked() {
-            match crate::dns::providers::reset_dns() {
-                Ok(_) => app.status = "🔄 Returned to autopilot".to_string(),
-                Err(e) => app.status = format!("💥 Engine failure: {}", e),
-            }
+            app.confirm_reset_dns("🔄 Returned to autopilot", "💥 Engine failure:");
+            ctx.request_repaint();
+        }
+
+        ui.add_space(10.0);
+
+        if ui.add_sized([ui.available_width(), 40.0], egui::Button::new("🧹 Flush DNS Cache")).clicked() {
+            app.status = outcome_status(app.flush_dns_cache(), "🧹 Space dust cleared", "💥 Cleaning failure:");
             ctx.request_repaint();
         }
 
+        if !app.current_dns.is_empty() {
+            ui.add_space(10.0);
+            ui.label(format!("🌌 DNS координаты: {}", app.current_dns));
+        }
+
         ui.add_space(20.0);
         ui.label("📊 Текущий статус:");
         ui.add_space(5.0);
@@ -67,74 +87,90 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
         ui.separator();
 
         ui.label("⭐ Выберите DNS провайдер для космического путешествия:");
-        ui.add_space(15.0);
-
-        // Cloudflare
-        if ui.add_sized([ui.available_width(), 45.0], egui::Button::new("☁️ Cloudflare DNS\n1.1.1.1, 1.0.0.1")).clicked() {
-            match crate::dns::providers::set_dns("1.1.1.1", "1.0.0.1") {
-                Ok(_) => app.status = "🎉 Arrived at Cloudflare: 1.1.1.1, 1.0.0.1!".to_string(),
-                Err(e) => app.status = format!("💥 Ship crashed: {}", e),
-            }
-            ctx.request_repaint();
-        }
-
         ui.add_space(8.0);
+        show_provider_filter(app, ui);
+        ui.add_space(10.0);
 
-        // Google
-        if ui.add_sized([ui.available_width(), 45.0], egui::Button::new("🔍 Google DNS\n8.8.8.8, 8.8.4.4")).clicked() {
-            match crate::dns::providers::set_dns("8.8.8.8", "8.8.4.4") {
-                Ok(_) => app.status = "🎉 Welcome to Google: 8.8.8.8, 8.8.4.4!".to_string(),
-                Err(e) => app.status = format!("💥 System malfunction: {}", e),
-            }
-            ctx.request_repaint();
+        let mut providers: Vec<DNSProvider> =
+            app.catalog.providers.iter().filter(|provider| app.provider_filter.matches(provider)).cloned().collect();
+        app.provider_sort.sort(&mut providers);
+        if providers.is_empty() {
+            ui.label("🔭 Под фильтр не подходит ни один провайдер");
         }
 
-        ui.add_space(8.0);
-
-        // Quad9
-        if ui.add_sized([ui.available_width(), 45.0], egui::Button::new("🔒 Quad9 DNS\n9.9.9.9, 149.112.112.112")).clicked() {
-            match crate::dns::providers::set_dns("9.9.9.9", "149.112.112.112") {
-                Ok(_) => app.status = "🎉 Secured with Quad9: 9.9.9.9, 149.112.112.112!".to_string(),
-                Err(e) => app.status = format!("💥 Security breach: {}", e),
-            }
-            ctx.request_repaint();
-        }
+        for provider in providers {
+            let servers = format!("{}, {}", provider.primary, provider.secondary);
+            let mut hover = match (&provider.primary_v6, &provider.secondary_v6) {
+                (Some(primary), Some(secondary)) => format!("{}\nIPv6: {}, {}", provider.description, primary, secondary),
+                (Some(primary), None) => format!("{}\nIPv6: {}", provider.description, primary),
+                _ => provider.description.clone(),
+            };
+            // Сервер из штампа DNSCrypt или DoH: системе нечего прописать - обычного DNS там может не быть
+            let (servers, applicable) = if provider.encrypted_only {
+                let transports: Vec<&str> = provider.transports().into_iter().map(|transport| transport.label()).collect();
+                hover.push_str("\nТолько зашифрованный протокол - системным DNS не ставится");
+                (format!("только {}", transports.join(", ")), false)
+            } else {
+                (servers, true)
+            };
+            ui.horizontal(|ui| {
+                let button = egui::Button::new(format!("🛰️ {}\n{}", provider.name, servers));
+                let response = ui.add_enabled_ui(applicable, |ui| ui.add_sized([ui.available_width() - 145.0, 45.0], button)).inner;
+                let response = if hover.trim().is_empty() {
+                    response
+                } else {
+                    response.on_hover_text(hover.trim()).on_disabled_hover_text(hover.trim())
+                };
+                if response.clicked() {
+                    let success = format!("🎉 {}: {}!", provider.name, servers);
+                    app.confirm_set_dns(provider.servers(), &success, "💥 Ship crashed:");
+                    ctx.request_repaint();
+                }
 
-        ui.add_space(8.0);
+                if ui.button("✏️").on_hover_text("Изменить").clicked() {
+                    app.edit_provider(&provider, false);
+                }
+                if ui.button("📄").on_hover_text("Дублировать").clicked() {
+                    app.edit_provider(&provider, true);
+                }
+                if ui.button("🔖").on_hover_text("Скопировать как DNS Stamp (sdns://)").clicked() {
+                    if let Some(stamps) = app.export_stamps(&provider) {
+                        ctx.output_mut(|output| output.copied_text = stamps);
+                    }
+                }
+                if ui.button("🗑").on_hover_text("Удалить из каталога").clicked() {
+                    app.delete_provider(&provider.name);
+                }
+            });
+            show_provider_metadata(ui, &provider);
 
-        // OpenDNS
-        if ui.add_sized([ui.available_width(), 45.0], egui::Button::new("👨‍👩‍👧‍👦 OpenDNS\n208.67.222.222, 208.67.220.220")).clicked() {
-            match crate::dns::providers::set_dns("208.67.222.222", "208.67.220.220") {
-                Ok(_) => app.status = "🎉 Family protection activated!".to_string(),
-                Err(e) => app.status = format!("💥 Family emergency: {}", e),
-            }
-            ctx.request_repaint();
+            ui.add_space(8.0);
         }
 
-        ui.add_space(8.0);
+        ui.add_space(12.0);
+        show_provider_editor(app, ui);
 
-        // AdGuard
-        if ui.add_sized([ui.available_width(), 45.0], egui::Button::new("🚫 AdGuard DNS\n94.140.14.14, 94.140.15.15")).clicked() {
-            match crate::dns::providers::set_dns("94.140.14.14", "94.140.15.15") {
-                Ok(_) => app.status = "🎉 Ads blocked: 94.140.14.14, 94.140.15.15!".to_string(),
-                Err(e) => app.status = format!("💥 Ad blocking failure: {}", e),
+        ui.add_space(12.0);
+        ui.horizontal(|ui| {
+            ui.label("🔖 DNS Stamp:");
+            ui.add(egui::TextEdit::singleline(&mut app.stamp_input).hint_text("sdns://...").desired_width(320.0));
+            if ui.add_enabled(!app.stamp_input.trim().is_empty(), egui::Button::new("📥 Импорт")).clicked() {
+                app.import_stamp();
             }
-            ctx.request_repaint();
-        }
-
-        ui.add_space(8.0);
+        });
+        ui.small("Штампы DNS, DoH, DoT, DoQ и DNSCrypt; провайдер сохраняется в каталог сразу, без проверки связи");
 
-        // CleanBrowsing
-        if ui.add_sized([ui.available_width(), 45.0], egui::Button::new("🧹 CleanBrowsing\n185.228.168.9, 185.228.169.9")).clicked() {
-            match crate::dns::providers::set_dns("185.228.168.9", "185.228.169.9") {
-                Ok(_) => app.status = "🎉 Clean browsing activated!".to_string(),
-                Err(e) => app.status = format!("💥 Cleaning failure: {}", e),
-            }
-            ctx.request_repaint();
+        if let Some(path) = &app.catalog.path {
+            ui.add_space(12.0);
+            ui.small(format!("📚 Каталог: {} (изменения подхватываются сами)", path.display()));
+        }
+        if let Some(e) = &app.catalog.error {
+            ui.colored_label(egui::Color32::from_rgb(239, 68, 68), format!("⚠️ Файл каталога не принят, используются прежние провайдеры; изменения не сохраняются, пока файл не исправлен:\n{}", e));
         }
 
         ui.add_space(20.0);
-        ui.label("💡 Все изменения применяются ко всем активным сетевым адаптерам");
+        ui.label(format!("💡 Изменения применяются к: {}", selection_label(&app.adapter_selection)));
+        ui.small("🎯 Выбор адаптеров - на вкладке 📡 Сеть");
     }
 
     pub fn show_lab_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
@@ -147,13 +183,79 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
         ui.add_space(10.0);
 
         // DNS Speed Test
-        let button_text = if app.is_speed_testing {
-            "⏳ Тестирование выполняется...".to_string()
-        } else {
-            "⚡ DNS Speed Test - Тестировать все провайдеры".to_string()
+        ui.horizontal(|ui| {
+            ui.label("🔁 Запросов на сервер:");
+            ui.add(egui::DragValue::new(&mut app.benchmark_config.samples).clamp_range(1..=200));
+            ui.label("🔥 Прогрев:");
+            ui.add(egui::DragValue::new(&mut app.benchmark_config.warmup).clamp_range(0..=20));
+            ui.label("🔀 Параллельно:");
+            ui.add(egui::DragValue::new(&mut app.benchmark_config.parallelism).clamp_range(1..=16));
+            ui.checkbox(&mut app.benchmark_config.doh, "🔒 DoH");
+            ui.checkbox(&mut app.benchmark_config.dot, "🔐 DoT");
+            ui.checkbox(&mut app.benchmark_config.doq, "⚡ DoQ");
+            ui.checkbox(&mut app.benchmark_config.tcp, "🧱 TCP");
+            ui.checkbox(&mut app.benchmark_config.ipv6, "🌐 IPv6");
+        });
+
+        // Свой набор доменов: простой список или CSV "домен,тип,вес"
+        ui.horizontal(|ui| {
+            ui.label("📂 Набор доменов:");
+            ui.add(egui::TextEdit::singleline(&mut app.workload_path).hint_text("путь к .txt или .csv").desired_width(260.0));
+            if ui.button("Загрузить").clicked() {
+                app.load_workload();
+            }
+            if app.workload.is_some() && ui.button("✖ Сбросить").clicked() {
+                app.workload = None;
+            }
+        });
+        match &app.workload {
+            Some(workload) => ui.small(format!(
+                "📂 {}: {} имен, {} запросов на сервер",
+                workload.source,
+                workload.entries.len(),
+                workload.total_queries()
+            )),
+            None => ui.small("Без набора замер идет по встроенным популярным именам"),
         };
 
-        if ui.add_sized([ui.available_width(), 40.0], egui::Button::new(button_text)).clicked() && !app.is_speed_testing {
+        ui.horizontal(|ui| {
+            ui.label("🏁 Сортировать по:");
+            let before = (app.ranking_statistic, app.ranking_cache);
+            egui::ComboBox::from_id_source("ranking_statistic")
+                .selected_text(app.ranking_statistic.label())
+                .show_ui(ui, |ui| {
+                    for statistic in RankingStatistic::ALL {
+                        ui.selectable_value(&mut app.ranking_statistic, statistic, statistic.label());
+                    }
+                });
+            egui::ComboBox::from_id_source("ranking_cache")
+                .selected_text(app.ranking_cache.label())
+                .show_ui(ui, |ui| {
+                    for cache in CacheState::ALL {
+                        ui.selectable_value(&mut app.ranking_cache, cache, cache.label());
+                    }
+                });
+            if (app.ranking_statistic, app.ranking_cache) != before {
+                rank_results(&mut app.speed_results, app.ranking_statistic, app.ranking_cache);
+            }
+        });
+
+        ui.add_space(5.0);
+
+        if let Some(run) = &app.speed_test {
+            let progress = if run.total == 0 { 1.0 } else { run.completed as f32 / run.total as f32 };
+            ui.add(
+                egui::ProgressBar::new(progress)
+                    .text(format!("⏳ Готово {} из {}", run.completed, run.total))
+                    .animate(true),
+            );
+            if ui.add_sized([ui.available_width(), 40.0], egui::Button::new("⛔ Отменить тестирование")).clicked() {
+                app.cancel_speed_test();
+            }
+        } else if ui
+            .add_sized([ui.available_width(), 40.0], egui::Button::new("⚡ DNS Speed Test - Тестировать все провайдеры"))
+            .clicked()
+        {
             app.start_speed_test();
             ctx.request_repaint();
         }
@@ -164,10 +266,15 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
         // Speed Test Results
         if !app.speed_results.is_empty() {
             ui.add_space(10.0);
-            ui.label("📊 Скорость DNS серверов (отсортировано по задержке):");
+            ui.label(format!(
+                "📊 Скорость DNS серверов (отсортировано по {}, {}):",
+                app.ranking_statistic.label(),
+                app.ranking_cache.label()
+            ));
 
             ui.separator();
 
+            let baseline = reference_baseline(&app.speed_results, app.ranking_statistic, app.ranking_cache);
             for (index, result) in app.speed_results.iter().enumerate() {
                 let medal = match index {
                     0 => "🥇",
@@ -176,31 +283,91 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
                     _ => "📍",
                 };
 
-                let avg_text = match result.avg_ping {
-                    Some(avg) => format!("{:.1}ms", avg),
-                    None => "N/A".to_string(),
-                };
-
-                let primary_text = match result.primary_ping {
-                    Some(p) => format!("{:.1}ms", p),
-                    None => "❌".to_string(),
-                };
-
-                let secondary_text = match result.secondary_ping {
-                    Some(p) => format!("{:.1}ms", p),
-                    None => "❌".to_string(),
-                };
+                let statistic = app.ranking_statistic;
 
                 ui.horizontal(|ui| {
                     ui.label(format!("{} {}:", medal, result.provider));
+                    if result.baseline {
+                        ui.small("опорный");
+                    } else if let Some(baseline) = baseline {
+                        if let Some(relative) = relative_to_baseline(result, baseline, statistic, app.ranking_cache) {
+                            let (text, color) = if relative >= 0.0 {
+                                (format!("⚖️ быстрее на {:.0}%", relative), egui::Color32::from_rgb(34, 197, 94))
+                            } else {
+                                (format!("⚖️ медленнее на {:.0}%", -relative), egui::Color32::from_rgb(239, 68, 68))
+                            };
+                            ui.small(egui::RichText::new(text).color(color))
+                                .on_hover_text(format!("По сравнению с {}", baseline.provider));
+                        }
+                    }
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        ui.label(format!("Avg: {} | P1: {} | P2: {}", avg_text, primary_text, secondary_text));
+                        ui.label(format!(
+                            "{}: 🔥 {} / ❄️ {} | P1: {} | P2: {} | Loss: {:.0}%",
+                            statistic.label(),
+                            statistic.format(&result.warm),
+                            statistic.format(&result.cold),
+                            server_result_text(&result.primary, statistic, app.ranking_cache),
+                            server_result_text(&result.secondary, statistic, app.ranking_cache),
+                            result.stats(app.ranking_cache).loss_percent()
+                        ))
+                        .on_hover_text(server_result_details(&[&result.primary, &result.secondary]));
                     });
                 });
+
+                if let Some(doh) = &result.doh {
+                    ui.horizontal(|ui| {
+                        ui.add_space(20.0);
+                        ui.small(doh_result_text(doh, statistic)).on_hover_text(doh_result_details(doh));
+                    });
+                }
+
+                if let Some(dot) = &result.dot {
+                    ui.horizontal(|ui| {
+                        ui.add_space(20.0);
+                        ui.small(dot_result_text(dot, statistic)).on_hover_text(dot_result_details(dot));
+                    });
+                }
+
+                if let Some(doq) = &result.doq {
+                    ui.horizontal(|ui| {
+                        ui.add_space(20.0);
+                        ui.small(doq_result_text(doq, statistic)).on_hover_text(doq_result_details(doq));
+                    });
+                }
+
+                if let Some(tcp) = &result.tcp {
+                    ui.horizontal(|ui| {
+                        ui.add_space(20.0);
+                        ui.small(tcp_result_text(tcp, statistic)).on_hover_text(tcp_result_details(tcp));
+                    });
+                }
+
+                if let Some(ipv6) = &result.ipv6 {
+                    ui.horizontal(|ui| {
+                        ui.add_space(20.0);
+                        ui.small(ipv6_result_text(ipv6, statistic, app.ranking_cache))
+                            .on_hover_text(server_result_details(&[&ipv6.primary, &ipv6.secondary]));
+                    });
+                }
+
+                if let Some(workload) = &result.workload {
+                    ui.horizontal(|ui| {
+                        ui.add_space(20.0);
+                        show_workload_result(ui, &result.provider, workload, statistic);
+                    });
+                }
             }
 
             ui.add_space(5.0);
-            ui.small("💡 Чем меньше задержка - тем быстрее DNS!");
+            ui.small("💡 Задержка - время ответа на A/AAAA запрос по UDP/53. ⚠️ - сервер отвечает, но с ошибкой, ❌ - не отвечает");
+            ui.small("🔥 - популярные имена из кэша резолвера, ❄️ - случайные поддомены, которые резолвер ищет заново");
+            ui.small("🔒 DoH: установка соединения (TCP + TLS) отдельно от запросов GET/POST по уже открытому HTTP/2 соединению");
+            ui.small("🔐 DoT: TCP и TLS на порт 853, затем запросы по одному и пачкой по тому же соединению");
+            ui.small("⚡ DoQ: рукопожатие QUIC, первый ответ без 0-RTT и с 0-RTT при повторном подключении, запросы по открытому соединению");
+            ui.small("📌 Опорные - резолверы, которые уже настроены в системе; ⚖️ - разница с самым быстрым из них");
+            ui.small("🧱 TCP/53: соединение и запросы по нему, затем переход UDP → TCP после ответа с флагом TC (обрезан)");
+            ui.small("🌐 IPv6: те же запросы к IPv6 адресам провайдера; в рейтинг не идут - IPv6 есть не у каждого подключения");
+            ui.small("📂 Набор: каждое имя запрошено столько раз, каков его вес, у обоих серверов провайдера");
         } else {
             ui.add_space(10.0);
             ui.label("🔭 Запустите тестирование, чтобы увидеть результаты");
@@ -217,18 +384,26 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
         ui.add_space(10.0);
 
         if ui.button("🔄 Обновить информацию о сети").clicked() {
-            app.network_adapters = crate::network::adapters::get_network_adapters();
+            app.network_adapters = app.get_network_adapters();
+            app.network_connections = DNSManager::get_network_connections();
             app.status = "✅ Информация о сети обновлена!".to_string();
         }
 
         ui.add_space(10.0);
 
+        show_adapter_selection(app, ui);
+
+        ui.add_space(10.0);
+
         if app.network_adapters.is_empty() {
             ui.label("❌ Нет активных сетевых адаптеров");
         } else {
             for adapter in &app.network_adapters {
                 ui.add_space(5.0);
-                ui.label(format!("🔌 **{}** ({})", adapter.name, adapter.status));
+                let title = ui.label(format!("🔌 **{}** ({})", adapter.name, adapter.status));
+                if adapter.index > 0 {
+                    title.on_hover_text(format!("ifindex {}", adapter.index));
+                }
 
                 ui.horizontal(|ui| {
                     ui.label("📍 MAC адрес:");
@@ -237,25 +412,90 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
                     });
                 });
 
+                let (ipv4, ipv6) = split_families(&adapter.ip_addresses);
                 ui.horizontal(|ui| {
                     ui.label("🌐 IP адрес:");
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        ui.label(adapter.ip_addresses.join(", "));
+                        ui.label(ipv4.join(", "));
                     });
                 });
+                if !ipv6.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.label("🌐 IPv6 адрес:");
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.label(ipv6.join(", "));
+                        });
+                    });
+                }
 
+                let (dns_v4, dns_v6) = split_families(&adapter.dns_servers);
                 ui.horizontal(|ui| {
                     ui.label("🔧 DNS серверы:");
                     ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        ui.label(adapter.dns_servers.join(", "));
+                        if dns_v4.is_empty() && dns_v6.is_empty() {
+                            ui.label("Не настроен");
+                        } else if dns_v4.is_empty() {
+                            ui.label("Только IPv6");
+                        } else {
+                            ui.label(dns_v4.join(", "));
+                        }
                     });
                 });
+                if !dns_v6.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.label("🔧 DNS IPv6:");
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.label(dns_v6.join(", "));
+                        });
+                    });
+                }
+
+                if adapter.mtu > 0 {
+                    ui.horizontal(|ui| {
+                        ui.label("📏 MTU:");
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.label(adapter.mtu.to_string());
+                        });
+                    });
+                }
+
+                if !adapter.flags.is_empty() {
+                    ui.horizontal(|ui| {
+                        ui.label("🚩 Флаги:");
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.label(adapter.flags.join(" "));
+                        });
+                    });
+                }
 
                 ui.add_space(5.0);
                 ui.separator();
             }
         }
 
+        if !app.network_connections.is_empty() {
+            ui.add_space(10.0);
+            ui.label("🔗 Профили подключений NetworkManager:");
+
+            for connection in &app.network_connections {
+                let state = if connection.active {
+                    format!("🟢 {}", connection.device)
+                } else {
+                    "⚪ не активно".to_string()
+                };
+
+                ui.horizontal(|ui| {
+                    ui.label(format!("📶 {} ({})", connection.id, connection.connection_type))
+                        .on_hover_text(&connection.uuid);
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.label(state);
+                    });
+                });
+            }
+
+            ui.separator();
+        }
+
         ui.add_space(20.0);
         ui.label("💡 Эта вкладка показывает все активные сетевые подключения");
         ui.label("🔄 Используйте кнопку обновления для получения актуальной информации");
@@ -280,7 +520,7 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
         ui.horizontal(|ui| {
             ui.label("🔧 Провайдеров DNS:");
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                ui.label("6 доступных");
+                ui.label(format!("{} доступных", app.catalog.providers.len()));
             });
         });
 
@@ -295,12 +535,9 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
         ui.label("ℹ️ Информация о DNS:");
         ui.add_space(10.0);
 
-        ui.label("☁️ **Cloudflare (1.1.1.1)**: Быстрый и приватный DNS от Cloudflare");
-        ui.label("🔍 **Google (8.8.8.8)**: Надежный DNS от Google");
-        ui.label("🔒 **Quad9 (9.9.9.9)**: Защита от вредоносных сайтов");
-        ui.label("👨‍👩‍👧‍👦 **OpenDNS (208.67.222.222)**: Семейная фильтрация контента");
-        ui.label("🚫 **AdGuard (94.140.14.14)**: Блокировка рекламы");
-        ui.label("🧹 **CleanBrowsing (185.228.168.9)**: Безопасный интернет для детей");
+        for provider in &app.catalog.providers {
+            ui.label(format!("🛰️ **{} ({})**: {}", provider.name, provider.primary, provider.description));
+        }
 
         ui.add_space(20.0);
         ui.label("🔗 Полезные ссылки:");
@@ -311,3 +548,406 @@ pub fn show_main_tab(app: &mut DNSManager, ui: &mut Ui, ctx: &Context) {
         ui.hyperlink_to("🔍 Google Public DNS", "https://dns.google/");
         ui.hyperlink_to("🔒 Quad9", "https://www.quad9.net/");
     }
+
+// Отбор провайдеров по протоколу, фильтрации и приватности, и порядок списка
+fn show_provider_filter(app: &mut DNSManager, ui: &mut Ui) {
+    let filter = &mut app.provider_filter;
+    ui.horizontal_wrapped(|ui| {
+        ui.label("🔎 Протокол:");
+        egui::ComboBox::from_id_source("provider_transport_filter")
+            .selected_text(filter.transport.map_or("любой", Transport::label))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut filter.transport, None, "любой");
+                for transport in Transport::ALL.into_iter().filter(|transport| *transport != Transport::Plain) {
+                    ui.selectable_value(&mut filter.transport, Some(transport), transport.label());
+                }
+            });
+
+        ui.label("🛡 Фильтрация:");
+        egui::ComboBox::from_id_source("provider_filtering_filter")
+            .selected_text(filter.filtering.map_or("любая", Filtering::label))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut filter.filtering, None, "любая");
+                for filtering in Filtering::ALL {
+                    ui.selectable_value(&mut filter.filtering, Some(filtering), filtering.label());
+                }
+            });
+
+        ui.checkbox(&mut filter.dnssec, "🔏 DNSSEC");
+        ui.checkbox(&mut filter.no_ecs, "🙈 без ECS");
+        ui.checkbox(&mut filter.no_logs, "📵 без журналов");
+
+        ui.label("↕ Порядок:");
+        egui::ComboBox::from_id_source("provider_sort")
+            .selected_text(app.provider_sort.label())
+            .show_ui(ui, |ui| {
+                for sort in ProviderSort::ALL {
+                    ui.selectable_value(&mut app.provider_sort, sort, sort.label());
+                }
+            });
+    });
+    ui.small("🛡 \"вредоносные\" находит и тех, кто блокирует их вместе с рекламой или сайтами для взрослых");
+}
+
+// Строка свойств под кнопкой провайдера; адреса протоколов - во всплывающей подсказке
+fn show_provider_metadata(ui: &mut Ui, provider: &DNSProvider) {
+    ui.horizontal_wrapped(|ui| {
+        ui.add_space(10.0);
+        for transport in provider.transports() {
+            let endpoint = provider.endpoint(transport).unwrap_or_default();
+            ui.small(format!("[{}]", transport.label())).on_hover_text(endpoint);
+        }
+        ui.small(format!("🛡 {}", provider.filtering.map_or("фильтрация неизвестна", Filtering::label)));
+        if provider.dnssec {
+            ui.small("🔏 DNSSEC");
+        }
+        match provider.ecs {
+            Some(Ecs::Off) => ui.small(format!("🙈 {}", Ecs::Off.label())),
+            Some(Ecs::Subnet) => ui.small(format!("📍 {}", Ecs::Subnet.label())),
+            None => ui.small("ECS неизвестно"),
+        };
+        if provider.no_logs {
+            ui.small("📵 без журналов");
+        }
+        if let Some(url) = &provider.logging_policy {
+            ui.hyperlink_to(egui::RichText::new("📜 политика журналов").small(), url);
+        }
+    });
+}
+
+// Форма своего провайдера: новый, изменение или копия записи из каталога
+fn show_provider_editor(app: &mut DNSManager, ui: &mut Ui) {
+    match &app.custom_original {
+        Some(name) => ui.label(format!("✏️ Изменение провайдера {}:", name)),
+        None => ui.label("➕ Свой провайдер:"),
+    };
+
+    egui::Grid::new("provider_editor").num_columns(2).spacing([10.0, 6.0]).show(ui, |ui| {
+        ui.label("Имя:");
+        ui.text_edit_singleline(&mut app.custom_name);
+        ui.end_row();
+
+        ui.label("Описание:");
+        ui.text_edit_singleline(&mut app.custom_description);
+        ui.end_row();
+
+        for (label, value) in [("Основной DNS:", &mut app.custom_primary), ("Резервный DNS:", &mut app.custom_secondary)] {
+            ui.label(label);
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(value).hint_text("IPv4 или IPv6"));
+                if !value.trim().is_empty() && value.trim().parse::<std::net::IpAddr>().is_err() {
+                    ui.colored_label(egui::Color32::from_rgb(239, 68, 68), "не IP адрес");
+                }
+            });
+            ui.end_row();
+        }
+
+        for (label, value) in [("Основной IPv6:", &mut app.custom_primary_v6), ("Резервный IPv6:", &mut app.custom_secondary_v6)] {
+            ui.label(label);
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(value).hint_text("необязательно"));
+                if !value.trim().is_empty() && value.trim().parse::<std::net::Ipv6Addr>().is_err() {
+                    ui.colored_label(egui::Color32::from_rgb(239, 68, 68), "не IPv6 адрес");
+                }
+            });
+            ui.end_row();
+        }
+    });
+
+    ui.horizontal(|ui| {
+        if app.custom_check.is_some() {
+            ui.spinner();
+            ui.label("📡 Проверяем, отвечают ли серверы...");
+        } else if ui.button("💾 Проверить и сохранить").clicked() {
+            app.check_and_save_provider();
+        }
+        if (app.custom_original.is_some() || !app.custom_name.is_empty()) && ui.button("✖ Очистить").clicked() {
+            app.clear_provider_form();
+        }
+    });
+    ui.small("Перед сохранением оба сервера должны ответить на запрос по UDP/53; IPv6 адреса не проверяются");
+}
+
+// Выбор адаптеров, к которым применяются провайдеры
+fn show_adapter_selection(app: &mut DNSManager, ui: &mut Ui) {
+    ui.label("🎯 Адаптеры для смены DNS:");
+
+    ui.horizontal(|ui| {
+        let selection = &mut app.adapter_selection;
+        if ui.radio(matches!(selection, AdapterSelection::AllUp), "Все активные").clicked() {
+            *selection = AdapterSelection::AllUp;
+        }
+        if ui.radio(matches!(selection, AdapterSelection::Names(_)), "Выбранные").clicked()
+            && !matches!(selection, AdapterSelection::Names(_))
+        {
+            *selection = AdapterSelection::Names(Vec::new());
+        }
+        if ui.radio(matches!(selection, AdapterSelection::Filter { .. }), "Фильтр").clicked()
+            && !matches!(selection, AdapterSelection::Filter { .. })
+        {
+            *selection = AdapterSelection::Filter { adapter_type: None, name_pattern: String::new() };
+        }
+    });
+
+    match &mut app.adapter_selection {
+        AdapterSelection::AllUp => {}
+        AdapterSelection::Names(names) => {
+            for adapter in &app.network_adapters {
+                let mut checked = names.contains(&adapter.name);
+                if ui.checkbox(&mut checked, format!("{} {}", adapter.adapter_type.label(), adapter.name)).changed() {
+                    if checked {
+                        names.push(adapter.name.clone());
+                    } else {
+                        names.retain(|name| *name != adapter.name);
+                    }
+                }
+            }
+        }
+        AdapterSelection::Filter { adapter_type, name_pattern } => {
+            ui.horizontal(|ui| {
+                ui.label("Тип:");
+                egui::ComboBox::from_id_source("adapter_type_filter")
+                    .selected_text(adapter_type.map_or("Любой", |t| t.label()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(adapter_type, None, "Любой");
+                        for t in AdapterType::ALL {
+                            ui.selectable_value(adapter_type, Some(t), t.label());
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                ui.label("Имя (шаблон * ?):");
+                ui.text_edit_singleline(name_pattern);
+            });
+        }
+    }
+
+    let matched: Vec<&str> = app
+        .network_adapters
+        .iter()
+        .filter(|adapter| app.adapter_selection.matches(adapter))
+        .map(|adapter| adapter.name.as_str())
+        .collect();
+    ui.small(format!("➡️ Сейчас под выбор попадают: {}", if matched.is_empty() { "никто".to_string() } else { matched.join(", ") }));
+    ui.separator();
+}
+
+// Статистика, если сервер корректно резолвит; иначе - отвечает ли он вообще
+fn server_result_text(result: &ServerResult, statistic: RankingStatistic, cache: CacheState) -> String {
+    match (result.stats(cache).has_answers(), result.reachable) {
+        (true, _) => statistic.format(result.stats(cache)),
+        (false, true) => "⚠️".to_string(),
+        (false, false) => "❌".to_string(),
+    }
+}
+
+// Адреса адаптера по семействам; подписи вроде "Не назначен" остаются среди IPv4.
+// У IPv6 в записи всегда есть двоеточие, у IPv4 (в том числе "адрес/префикс") - никогда
+fn split_families(values: &[String]) -> (Vec<&str>, Vec<&str>) {
+    values.iter().map(String::as_str).partition(|value| !value.contains(':'))
+}
+
+// Строка IPv6 под провайдером - в том же виде, что и основная по IPv4
+fn ipv6_result_text(result: &ServerPair, statistic: RankingStatistic, cache: CacheState) -> String {
+    if !result.primary.reachable && !result.secondary.reachable {
+        return "🌐 IPv6: ❌ не отвечает - возможно, у подключения нет IPv6".to_string();
+    }
+    format!(
+        "🌐 IPv6: 🔥 {} / ❄️ {} | P1: {} | P2: {} | Loss: {:.0}%",
+        statistic.format(&result.warm),
+        statistic.format(&result.cold),
+        server_result_text(&result.primary, statistic, cache),
+        server_result_text(&result.secondary, statistic, cache),
+        result.stats(cache).loss_percent()
+    )
+}
+
+// Полная статистика по каждому серверу для всплывающей подсказки
+fn server_result_details(results: &[&ServerResult]) -> String {
+    results
+        .iter()
+        .map(|result| {
+            let reachability = if result.reachable { "отвечает" } else { "не отвечает" };
+            let mut details = format!("{}: {}", result.server, reachability);
+            for cache in CacheState::ALL {
+                let stats = result.stats(cache);
+                if stats.has_answers() {
+                    details.push_str(&format!(
+                        "\n  {}: min {:.1} | median {:.1} | p90 {:.1} | p95 {:.1} | max {:.1} ms\n    σ {:.1} ms | jitter {:.1} ms | таймаутов {} из {} | потери {:.0}%",
+                        cache.label(), stats.min, stats.median, stats.p90, stats.p95, stats.max,
+                        stats.stddev, stats.jitter, stats.timeouts, stats.sent, stats.loss_percent()
+                    ));
+                }
+            }
+            if let Some(e) = &result.error {
+                details.push_str(&format!("\n  последняя ошибка: {}", e));
+            }
+            details
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Строка DoH под провайдером: установка соединения и запросы по нему
+fn doh_result_text(result: &DohResult, statistic: RankingStatistic) -> String {
+    match &result.setup {
+        Some(setup) => format!(
+            "🔒 DoH: соединение {:.1}ms | GET {} | POST {}",
+            setup.total(),
+            statistic.format(result.stats(DohMethod::Get)),
+            statistic.format(result.stats(DohMethod::Post))
+        ),
+        None => "🔒 DoH: ❌ нет соединения".to_string(),
+    }
+}
+
+fn doh_result_details(result: &DohResult) -> String {
+    let mut details = result.url.clone();
+    if let Some(setup) = &result.setup {
+        details.push_str(&format!("\n  TCP {:.1} ms + TLS {:.1} ms", setup.tcp_connect, setup.tls_handshake));
+    }
+    for method in DohMethod::ALL {
+        let stats = result.stats(method);
+        if stats.has_answers() {
+            details.push_str(&format!(
+                "\n  {}: min {:.1} | median {:.1} | p95 {:.1} | max {:.1} ms | потери {:.0}%",
+                method.label(), stats.min, stats.median, stats.p95, stats.max, stats.loss_percent()
+            ));
+        }
+    }
+    if let Some(e) = &result.error {
+        details.push_str(&format!("\n  последняя ошибка: {}", e));
+    }
+    details
+}
+
+// Строка DoT под провайдером: TCP, TLS и запросы по открытому соединению
+fn dot_result_text(result: &DotResult, statistic: RankingStatistic) -> String {
+    match &result.setup {
+        Some(setup) => format!(
+            "🔐 DoT: TCP {:.1}ms + TLS {:.1}ms | по одному {} | пачкой {}",
+            setup.tcp_connect,
+            setup.tls_handshake,
+            statistic.format(&result.sequential),
+            statistic.format(&result.pipelined)
+        ),
+        None => "🔐 DoT: ❌ нет соединения".to_string(),
+    }
+}
+
+fn dot_result_details(result: &DotResult) -> String {
+    let mut details = format!("{}:853, сертификат {}", result.server, result.auth_name);
+    for (label, stats) in [("по одному", &result.sequential), ("пачкой", &result.pipelined)] {
+        if stats.has_answers() {
+            details.push_str(&format!(
+                "\n  {}: min {:.1} | median {:.1} | p95 {:.1} | max {:.1} ms | потери {:.0}%",
+                label, stats.min, stats.median, stats.p95, stats.max, stats.loss_percent()
+            ));
+        }
+    }
+    if let Some(e) = &result.error {
+        details.push_str(&format!("\n  последняя ошибка: {}", e));
+    }
+    details
+}
+
+// Строка TCP/53 под провайдером: запросы по соединению и работает ли переход после TC
+fn tcp_result_text(result: &TcpResult, statistic: RankingStatistic) -> String {
+    let Some(connect) = result.connect else {
+        return "🧱 TCP/53: ❌ нет соединения".to_string();
+    };
+    let fallback = match &result.fallback {
+        Some(fallback) if fallback.works() => format!("✅ {:.1}ms", fallback.total.unwrap_or_default()),
+        Some(fallback) if !fallback.truncated && fallback.error.is_none() => "TC не пришел".to_string(),
+        _ => "❌".to_string(),
+    };
+    format!(
+        "🧱 TCP/53: соединение {:.1}ms | запросы {} | UDP → TCP {}",
+        connect,
+        statistic.format(&result.queries),
+        fallback
+    )
+}
+
+fn tcp_result_details(result: &TcpResult) -> String {
+    let mut details = format!("{}:53 (TCP)", result.server);
+    let stats = &result.queries;
+    if stats.has_answers() {
+        details.push_str(&format!(
+            "\n  запросы: min {:.1} | median {:.1} | p95 {:.1} | max {:.1} ms | потери {:.0}%",
+            stats.min, stats.median, stats.p95, stats.max, stats.loss_percent()
+        ));
+    }
+    if let Some(fallback) = &result.fallback {
+        let (name, record_type) = TRUNCATION_QUESTION;
+        details.push_str(&format!("\n  переход на TCP: {} {} по UDP", name, record_type.label()));
+        match (fallback.truncated, fallback.total) {
+            (true, Some(total)) => details.push_str(&format!(" обрезан, полный ответ по TCP за {:.1}ms", total)),
+            (true, None) => details.push_str(" обрезан, по TCP ответа нет"),
+            (false, _) => details.push_str(" пришел целиком - переход не проверен"),
+        }
+        if let Some(e) = &fallback.error {
+            details.push_str(&format!(" ({})", e));
+        }
+    }
+    if let Some(e) = &result.error {
+        details.push_str(&format!("\n  последняя ошибка: {}", e));
+    }
+    details
+}
+
+// Итог по набору доменов под провайдером; по клику - задержка каждого имени
+fn show_workload_result(ui: &mut Ui, provider: &str, result: &WorkloadResult, statistic: RankingStatistic) {
+    let title = format!(
+        "📂 {}: {} {} | потери {:.0}%",
+        result.source,
+        statistic.label(),
+        statistic.format(&result.aggregate),
+        result.aggregate.loss_percent()
+    );
+    egui::CollapsingHeader::new(egui::RichText::new(title).small())
+        .id_source(("workload", provider))
+        .show(ui, |ui| {
+            for domain in &result.domains {
+                ui.small(format!(
+                    "{} {} ×{}: {} | потери {:.0}%",
+                    domain.name,
+                    domain.record_type.label(),
+                    domain.weight,
+                    statistic.format(&domain.stats),
+                    domain.stats.loss_percent()
+                ));
+            }
+        });
+}
+
+// Строка DoQ под провайдером: цена рукопожатия с 0-RTT и без, запросы по соединению
+fn doq_result_text(result: &DoqResult, statistic: RankingStatistic) -> String {
+    let millis = |value: Option<f64>| value.map(|value| format!("{:.1}ms", value)).unwrap_or_else(|| "N/A".to_string());
+    match result.handshake {
+        Some(handshake) => format!(
+            "⚡ DoQ: рукопожатие {:.1}ms | первый ответ {} / {} {} | запросы {}",
+            handshake,
+            millis(result.first_answer),
+            millis(result.resumed_first_answer),
+            if result.zero_rtt { "(0-RTT)" } else { "(без 0-RTT)" },
+            statistic.format(&result.queries)
+        ),
+        None => "⚡ DoQ: ❌ нет соединения".to_string(),
+    }
+}
+
+fn doq_result_details(result: &DoqResult) -> String {
+    let mut details = format!("{}:853 (QUIC), сертификат {}", result.server, result.auth_name);
+    let stats = &result.queries;
+    if stats.has_answers() {
+        details.push_str(&format!(
+            "\n  запросы: min {:.1} | median {:.1} | p95 {:.1} | max {:.1} ms | потери {:.0}%",
+            stats.min, stats.median, stats.p95, stats.max, stats.loss_percent()
+        ));
+    }
+    if let Some(e) = &result.error {
+        details.push_str(&format!("\n  последняя ошибка: {}", e));
+    }
+    details
+}